@@ -1,68 +1,9 @@
-use async_trait::async_trait;
-use base64::Engine;
-use dogecoin::{block::*, err_string, jsonrpc::*};
+use dogecoin::{
+    block::*, blockfile::BlockDir, chainparams::DOGE_MAIN_NET_CHAIN, httpagent::HttpRPCAgent,
+    jsonrpc::*,
+};
 use dotenvy::dotenv;
-use reqwest::{header, Client, ClientBuilder, Url};
-use std::str::FromStr;
-use std::time::Duration;
-
-struct RPCAgent {
-    client: Client,
-    url: Url,
-}
-
-impl RPCAgent {
-    pub fn new() -> Self {
-        let rpcurl = std::env::var("RPC_URL").unwrap();
-        let rpcuser = std::env::var("RPC_USER").unwrap_or_default();
-        let rpcpassword = std::env::var("RPC_PASSWORD").unwrap_or_default();
-
-        let mut common_headers = header::HeaderMap::with_capacity(4);
-        common_headers.insert(header::ACCEPT, "application/json".parse().unwrap());
-        common_headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
-        common_headers.insert(header::ACCEPT_ENCODING, "gzip".parse().unwrap());
-
-        let url = reqwest::Url::parse(&rpcurl).unwrap();
-        if !rpcuser.is_empty() && !rpcpassword.is_empty() {
-            let auth = format!("{}:{}", rpcuser, rpcpassword);
-            let auth = format!(
-                "Basic {}",
-                base64::engine::general_purpose::STANDARD.encode(auth)
-            );
-            common_headers.insert(header::AUTHORIZATION, auth.parse().unwrap());
-        }
-
-        let client = ClientBuilder::new()
-            .use_rustls_tls()
-            .no_proxy()
-            .connect_timeout(Duration::from_secs(10))
-            .timeout(Duration::from_secs(30))
-            .user_agent(APP_AGENT)
-            .default_headers(common_headers)
-            .gzip(true)
-            .build()
-            .unwrap();
-
-        Self { client, url }
-    }
-}
-
-#[async_trait]
-impl JsonRPCAgent for &RPCAgent {
-    async fn post(&self, _idempotency_key: String, body: Vec<u8>) -> Result<bytes::Bytes, String> {
-        let req = self.client.post(self.url.clone()).body(body);
-        let res = req.send().await.map_err(err_string)?;
-        if res.status().is_success() {
-            res.bytes().await.map_err(err_string)
-        } else {
-            Err(format!(
-                "HTTP error: {}, {}",
-                res.status(),
-                res.text().await.unwrap()
-            ))
-        }
-    }
-}
+use std::{path::Path, str::FromStr};
 
 // cargo run -p check-blocks
 // cargo run -p check-blocks --release
@@ -70,7 +11,13 @@ impl JsonRPCAgent for &RPCAgent {
 async fn main() {
     dotenv().expect(".env file not found");
 
-    let agent = RPCAgent::new();
+    // BLOCKS_DIR=~/.dogecoin/blocks checks the block files of a copied datadir instead
+    if let Ok(dir) = std::env::var("BLOCKS_DIR") {
+        check_block_files(Path::new(&dir));
+        return;
+    }
+
+    let agent = HttpRPCAgent::from_env().unwrap();
     assert!(DogecoinRPC::ping(&agent, "".to_string()).await.is_ok());
 
     let mut height = 5179564u64;
@@ -97,3 +44,32 @@ async fn main() {
         }
     }
 }
+
+fn check_block_files(dir: &Path) {
+    let blocks_dir = BlockDir::open(dir, &DOGE_MAIN_NET_CHAIN).unwrap();
+    let index = blocks_dir.index().unwrap();
+    let tip = index.tip().expect("no blocks connected to genesis");
+    println!(
+        "blocks: {}, unconnected: {}, skipped: {}, tip({}): {:?}",
+        index.len(),
+        index.unconnected_len(),
+        index.skipped().len(),
+        tip.height,
+        tip.hash
+    );
+
+    let mut prev_blockhash = BlockHash::default();
+    for res in blocks_dir.best_chain_blocks(&index, 0) {
+        let (height, block) = res.unwrap();
+        assert_eq!(block.header.prev_blockhash, prev_blockhash);
+        assert!(
+            block.check_merkle_root(),
+            "Block({}): bad merkle root",
+            height
+        );
+        prev_blockhash = block.block_hash();
+        if height % 100000 == 0 {
+            println!("Block({}): {:?}", height, prev_blockhash);
+        }
+    }
+}
@@ -1,77 +1,17 @@
-use async_trait::async_trait;
-use base64::Engine;
 use dogecoin::transaction::{OutPoint, Transaction, TxIn, TxOut, Txid};
-use dogecoin::{chainparams::DOGE_TEST_NET_CHAIN, err_string, jsonrpc::*, script::*, sighash::*};
+use dogecoin::{
+    chainparams::DOGE_TEST_NET_CHAIN, httpagent::HttpRPCAgent, jsonrpc::*, script::*, sighash::*,
+};
 use dotenvy::dotenv;
 use hex::test_hex_unwrap as hex;
-use reqwest::{header, Client, ClientBuilder, Url};
 use std::str::FromStr;
-use std::time::Duration;
-
-struct RPCAgent {
-    client: Client,
-    url: Url,
-}
-
-impl RPCAgent {
-    pub fn new() -> Self {
-        let rpcurl = std::env::var("RPC_URL").unwrap();
-        let rpcuser = std::env::var("RPC_USER").unwrap_or_default();
-        let rpcpassword = std::env::var("RPC_PASSWORD").unwrap_or_default();
-
-        let mut common_headers = header::HeaderMap::with_capacity(4);
-        common_headers.insert(header::ACCEPT, "application/json".parse().unwrap());
-        common_headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
-        common_headers.insert(header::ACCEPT_ENCODING, "gzip".parse().unwrap());
-
-        let url = reqwest::Url::parse(&rpcurl).unwrap();
-        if !rpcuser.is_empty() && !rpcpassword.is_empty() {
-            let auth = format!("{}:{}", rpcuser, rpcpassword);
-            let auth = format!(
-                "Basic {}",
-                base64::engine::general_purpose::STANDARD.encode(auth)
-            );
-            common_headers.insert(header::AUTHORIZATION, auth.parse().unwrap());
-        }
-
-        let client = ClientBuilder::new()
-            .use_rustls_tls()
-            .no_proxy()
-            .connect_timeout(Duration::from_secs(10))
-            .timeout(Duration::from_secs(30))
-            .user_agent(APP_AGENT)
-            .default_headers(common_headers)
-            .gzip(true)
-            .build()
-            .unwrap();
-
-        Self { client, url }
-    }
-}
-
-#[async_trait]
-impl JsonRPCAgent for &RPCAgent {
-    async fn post(&self, _idempotency_key: String, body: Vec<u8>) -> Result<bytes::Bytes, String> {
-        let req = self.client.post(self.url.clone()).body(body);
-        let res = req.send().await.map_err(err_string)?;
-        if res.status().is_success() {
-            res.bytes().await.map_err(err_string)
-        } else {
-            Err(format!(
-                "HTTP error: {}, {}",
-                res.status(),
-                res.text().await.unwrap()
-            ))
-        }
-    }
-}
 
 // cargo run -p send-tx
 #[tokio::main]
 async fn main() {
     dotenv().expect(".env file not found");
 
-    let agent = RPCAgent::new();
+    let agent = HttpRPCAgent::from_env().unwrap();
     assert!(DogecoinRPC::ping(&agent, "".to_string()).await.is_ok());
 
     let chain = &DOGE_TEST_NET_CHAIN;
@@ -112,7 +52,7 @@ async fn main() {
     };
     let fee = 1_000_000;
     send_tx.output[0].value = tx1.output[0].value + tx2.output[0].value - fee;
-    send_tx.output[0].script_pubkey = spend_addr.to_script(chain); // send to self
+    send_tx.output[0].script_pubkey = spend_addr.try_to_script(chain).unwrap(); // send to self
 
     let secp = Secp256k1::new();
     let sk = decode_secretkey_wif(&std::env::var("SECRET_KEY").unwrap()).unwrap();
@@ -1,5 +1,5 @@
 use candid::CandidType;
-use dogecoin::block::BlockHash;
+use dogecoin::{block::BlockHash, chainparams::chain_from_key_bits};
 use serde::Deserialize;
 use std::{str::FromStr, time::Duration};
 
@@ -13,7 +13,7 @@ pub enum ChainArgs {
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct InitArgs {
-    chain: u8,              // TEST_NET_DOGE: 32, MAIN_NET_DOGE: 16
+    chain: u8,              // TEST_NET_DOGE: 32, MAIN_NET_DOGE: 16, REG_TEST_DOGE: 128
     min_confirmations: u32, // recommended: 42
     ecdsa_key_name: String, // Use "dfx_test_key" for local replica and "test_key_1" for a testing key for testnet and mainnet
     prev_start_height: u64,
@@ -25,10 +25,20 @@ pub struct UpgradeArgs {
     min_confirmations: Option<u32>, // recommended: 42
 }
 
+impl InitArgs {
+    fn validate(&self) -> Result<(), String> {
+        chain_from_key_bits(self.chain)?;
+        Ok(())
+    }
+}
+
 #[ic_cdk::init]
 fn init(args: Option<ChainArgs>) {
     match args.expect("Init args is missing") {
         ChainArgs::Init(args) => {
+            if let Err(err) = args.validate() {
+                ic_cdk::trap(&err);
+            }
             store::state::with_mut(|s| {
                 s.chain = args.chain;
                 s.min_confirmations = args.min_confirmations;
@@ -61,6 +71,9 @@ fn pre_upgrade() {
 #[ic_cdk::post_upgrade]
 fn post_upgrade(args: Option<ChainArgs>) {
     store::state::load();
+    if let Err(err) = store::state::with(|s| chain_from_key_bits(s.chain)) {
+        ic_cdk::trap(&format!("invalid chain in the stored state: {}", err));
+    }
 
     match args {
         Some(ChainArgs::Upgrade(args)) => {
@@ -92,3 +105,28 @@ fn post_upgrade(args: Option<ChainArgs>) {
         ));
     });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dogecoin::chainparams::{
+        KEY_ECPRIV, MAIN_NET_BTC, MAIN_NET_DOGE, REG_TEST_DOGE, TEST_NET_DOGE,
+    };
+
+    #[test]
+    fn test_init_args_chain() {
+        let args = |chain| InitArgs {
+            chain,
+            min_confirmations: 42,
+            ecdsa_key_name: "dfx_test_key".to_string(),
+            prev_start_height: 0,
+            prev_start_blockhash: "".to_string(),
+        };
+        for chain in [MAIN_NET_DOGE, TEST_NET_DOGE, REG_TEST_DOGE] {
+            assert!(args(chain).validate().is_ok());
+        }
+        for chain in [0, KEY_ECPRIV, MAIN_NET_BTC, MAIN_NET_DOGE | TEST_NET_DOGE] {
+            assert!(args(chain).validate().is_err());
+        }
+    }
+}
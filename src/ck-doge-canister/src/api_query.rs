@@ -1,6 +1,12 @@
 use bitcoin::hashes::sha256d;
 use candid::{CandidType, Principal};
-use dogecoin::canister::*;
+use dogecoin::{
+    bip32::ExtendedPubKey,
+    canister::*,
+    sighash::Secp256k1,
+    wallet::{discover_account, DEFAULT_GAP_LIMIT},
+};
+use ic_cdk::api::management_canister::http_request::{HttpResponse, TransformArgs};
 use serde_bytes::ByteArray;
 use std::{collections::BTreeSet, str::FromStr};
 
@@ -119,7 +125,7 @@ fn get_tx_status(txid: ByteArray<32>) -> Option<TxStatus> {
 
 #[ic_cdk::query]
 fn list_utxos(addr: String, take: u16, confirmed: bool) -> Result<UtxosOutput, String> {
-    let address = Address::from_str(&addr)?;
+    let address = Address::parse(&addr, store::state::with(|s| s.chain_params()))?;
     let utxos = store::list_utxos(&address.0, take.clamp(10, 10000) as usize, confirmed);
     store::state::with(|s| {
         Ok(UtxosOutput {
@@ -133,6 +139,7 @@ fn list_utxos(addr: String, take: u16, confirmed: bool) -> Result<UtxosOutput, S
 
 #[ic_cdk::query]
 fn list_utxos_b(address: ByteArray<21>, take: u16, confirmed: bool) -> Result<UtxosOutput, String> {
+    Address(address).to_typed(store::state::with(|s| s.chain_params()))?;
     let utxos = store::list_utxos(&address, take.clamp(10, 10000) as usize, confirmed);
     store::state::with(|s| {
         Ok(UtxosOutput {
@@ -146,11 +153,60 @@ fn list_utxos_b(address: ByteArray<21>, take: u16, confirmed: bool) -> Result<Ut
 
 #[ic_cdk::query]
 fn get_balance(addr: String) -> Result<u64, String> {
-    let address = Address::from_str(&addr)?;
+    let address = Address::parse(&addr, store::state::with(|s| s.chain_params()))?;
     Ok(store::get_balance(&address.0))
 }
 
+/// Traps on an address of another network, see [`get_balance_b_checked`].
 #[ic_cdk::query]
 fn get_balance_b(address: ByteArray<21>) -> u64 {
-    store::get_balance(&address)
+    get_balance_b_checked(address).unwrap_or_else(|err| ic_cdk::trap(&err))
+}
+
+#[ic_cdk::query]
+fn get_balance_b_checked(address: ByteArray<21>) -> Result<u64, String> {
+    Address(address).to_typed(store::state::with(|s| s.chain_params()))?;
+    Ok(store::get_balance(&address))
+}
+
+/// Balance of a BIP44 account imported from another wallet by its account-level
+/// extended public key (dgub or tpub), scanned with the given gap limit.
+#[ic_cdk::query]
+async fn get_xpub_balance(
+    xpub: String,
+    gap_limit: Option<u32>,
+) -> Result<XpubBalanceOutput, String> {
+    let (chain, tip_height) = store::state::with(|s| (s.chain_params(), s.tip_height));
+    let mut xpub = ExtendedPubKey::from_str(&xpub)?;
+    if xpub.chain.bip32_pubkey_prefix != chain.bip32_pubkey_prefix {
+        return Err(format!("xpub is not for {}", chain.chain_name));
+    }
+    // testnet and regtest share the tpub prefix
+    xpub.chain = chain;
+
+    let secp = Secp256k1::verification_only();
+    let gap_limit = gap_limit.unwrap_or(DEFAULT_GAP_LIMIT).clamp(1, 100);
+    let usage = discover_account(&secp, &xpub, &store::UtxoHistory, gap_limit).await?;
+    let addresses: Vec<AddressBalance> = usage
+        .used
+        .into_iter()
+        .map(|used| AddressBalance {
+            balance: store::get_balance(&used.address.0.into()),
+            address: used.address.0.into(),
+            change: used.change,
+            index: used.index,
+        })
+        .collect();
+    Ok(XpubBalanceOutput {
+        balance: addresses.iter().map(|a| a.balance).sum(),
+        addresses,
+        next_receive_index: usage.next_index[0],
+        next_change_index: usage.next_index[1],
+        tip_height,
+    })
+}
+
+#[ic_cdk::query(hidden = true)]
+fn transform_jsonrpc(args: TransformArgs) -> HttpResponse {
+    dogecoin::canister::transform_jsonrpc(args)
 }
@@ -1,14 +1,19 @@
 use dogecoin::{
-    amount, canister, err_string, jsonrpc::DogecoinRPC, script, sighash::*, transaction::*,
+    address::Address, amount, canister, coinselect, err_string, jsonrpc::DogecoinRPC, policy,
+    script, sighash::*, transaction::*,
 };
 use serde_bytes::ByteBuf;
-use std::str::FromStr;
 
 use crate::{ecdsa, is_authenticated, store, Account};
 
+const MAX_TX_INPUTS: usize = 500;
+
 #[ic_cdk::update(guard = "is_authenticated")]
 async fn send_tx(input: canister::SendTxInput) -> Result<canister::SendTxOutput, String> {
-    let tx = Transaction::try_from(input.tx.as_ref())?;
+    let mut tx = Transaction::try_from(input.tx.as_ref())?;
+    // high-S signatures are malleable and not relayed, fix them before the outcall
+    policy::normalize_signatures(&mut tx);
+    policy::check_standard(&tx).map_err(err_string)?;
     let txid = tx.compute_txid();
     let agent = store::state::get_agent();
     let txid = DogecoinRPC::send_transaction(&agent, txid.to_string(), &tx).await?;
@@ -27,17 +32,17 @@ async fn create_tx(input: canister::CreateTxInput) -> Result<canister::CreateTxO
             amount::DUST_LIMIT
         ));
     }
-    let receiver = script::Address::from_str(&input.address)?;
+    let chain = store::state::with(|s| s.chain_params());
+    let receiver = Address::parse(&input.address, chain).map_err(err_string)?;
     let sender = Account {
         owner: ic_cdk::caller(),
         subaccount: input.from_subaccount.map(|v| *v),
     };
     let sender_key_path = ecdsa::account_path(&sender);
 
-    let chain = store::state::with(|s| s.chain_params());
     let sender_key = store::get_public_key(sender_key_path.clone())?;
     let myaddr = script::p2pkh_address(&sender_key.public_key, chain)?;
-    let script_pubkey = myaddr.to_script(chain);
+    let script_pubkey = myaddr.try_to_script(chain)?;
 
     let utxos = if input.utxos.is_empty() {
         store::list_utxos(&myaddr.0.into(), 1000, false)
@@ -45,44 +50,47 @@ async fn create_tx(input: canister::CreateTxInput) -> Result<canister::CreateTxO
         input.utxos
     };
 
-    let total_value: u64 = utxos.iter().map(|u| u.value).sum();
-
-    let mut send_tx = Transaction {
+    let outputs = vec![TxOut {
+        value: input.amount,
+        script_pubkey: receiver.script_pubkey(),
+    }];
+    let coins: Vec<coinselect::Coin> = utxos
+        .iter()
+        .map(|u| coinselect::Coin {
+            value: u.value,
+            input_size: coinselect::P2PKH_INPUT_SIZE,
+        })
+        .collect();
+    let params = coinselect::SelectionParams::new(&outputs, input.fee_rate, MAX_TX_INPUTS);
+    // prefer a changeless spend, otherwise spend as few inputs as possible
+    let selection =
+        coinselect::select_coins(&coins, &params, coinselect::CoinSelection::BranchAndBound)
+            .or_else(|_| {
+                coinselect::select_coins(&coins, &params, coinselect::CoinSelection::LargestFirst)
+            })?;
+
+    // the selection owns the fee and change, the transaction just follows it
+    let mut output = outputs;
+    if selection.change > 0 {
+        output.push(TxOut {
+            value: selection.change,
+            script_pubkey,
+        });
+    }
+    let send_tx = Transaction {
         version: Transaction::CURRENT_VERSION,
         lock_time: 0,
-        input: utxos.into_iter().map(|u| u.into()).collect(),
-        output: vec![
-            TxOut {
-                value: input.amount,
-                script_pubkey: receiver.to_script(chain),
-            },
-            TxOut {
-                value: total_value.saturating_sub(input.amount),
-                script_pubkey: script_pubkey.clone(),
-            },
-        ],
+        input: selection
+            .selected
+            .iter()
+            .map(|&i| TxIn::from(utxos[i].clone()))
+            .collect(),
+        output,
     };
 
-    let mut fee = amount::fee_by_size(send_tx.estimate_size() as u64, input.fee_rate);
-    if total_value < input.amount + fee {
-        return Err(format!(
-            "insufficient balance, expected: {}, got {}",
-            input.amount + fee,
-            total_value
-        ));
-    }
-
-    let change = total_value.saturating_sub(input.amount + fee);
-    if change < amount::DUST_LIMIT {
-        send_tx.output.pop();
-        fee = total_value.saturating_sub(input.amount)
-    } else {
-        send_tx.output[1].value = change;
-    }
-
     Ok(canister::CreateTxOutput {
         tx: ByteBuf::from(send_tx.to_bytes()),
-        fee,
+        fee: selection.fee,
         tip_height: store::state::with(|s| s.tip_height),
         instructions: ic_cdk::api::performance_counter(1),
     })
@@ -104,12 +112,13 @@ async fn sign_and_send_tx(input: canister::SendTxInput) -> Result<canister::Send
     let sender_key = store::get_public_key(sender_key_path.clone())?;
     let myaddr = script::p2pkh_address(&sender_key.public_key, chain)?;
     let pubkey = PublicKey::from_slice(&sender_key.public_key).map_err(err_string)?;
-    let script_pubkey = myaddr.to_script(chain);
+    let script_pubkey = myaddr.try_to_script(chain)?;
 
     for i in 0..input_len {
         let hash = sighasher.signature_hash(i, &script_pubkey, EcdsaSighashType::All)?;
         let sig = ecdsa::sign_with(&key_name, sender_key_path.clone(), *hash).await?;
-        let signature = Signature::from_compact(&sig).map_err(err_string)?;
+        let mut signature = Signature::from_compact(&sig).map_err(err_string)?;
+        signature.normalize_s();
         sighasher
             .set_input_script(
                 i,
@@ -123,6 +132,7 @@ async fn sign_and_send_tx(input: canister::SendTxInput) -> Result<canister::Send
     }
 
     let tx = sighasher.transaction();
+    policy::check_standard(tx).map_err(err_string)?;
     let txid = tx.compute_txid();
     let agent = store::state::get_agent();
     let txid = DogecoinRPC::send_transaction(&agent, txid.to_string(), tx).await?;
@@ -1,3 +1,4 @@
+use async_trait::async_trait;
 use candid::Principal;
 use ciborium::{from_reader, into_writer};
 use dogecoin::{
@@ -5,6 +6,7 @@ use dogecoin::{
     canister::*,
     chainparams::{chain_from_key_bits, ChainParams, KeyBits},
     script, transaction,
+    wallet::AddressHistory,
 };
 use ic_stable_structures::{
     memory_manager::{MemoryId, MemoryManager, VirtualMemory},
@@ -69,7 +71,7 @@ pub struct State {
 
 impl State {
     pub fn chain_params(&self) -> &'static ChainParams {
-        chain_from_key_bits(self.chain)
+        chain_from_key_bits(self.chain).expect("chain is checked on init and upgrade")
     }
 
     pub fn append_error(&mut self, err: String) {
@@ -184,6 +186,7 @@ impl Storable for UtxoStates {
 const STATE_MEMORY_ID: MemoryId = MemoryId::new(0);
 const UT_MEMORY_ID: MemoryId = MemoryId::new(1);
 const XO_MEMORY_ID: MemoryId = MemoryId::new(2);
+const RX_MEMORY_ID: MemoryId = MemoryId::new(3);
 
 #[derive(Default)]
 pub struct SyncingState {
@@ -222,6 +225,13 @@ thread_local! {
             MEMORY_MANAGER.with_borrow(|m| m.get(XO_MEMORY_ID)),
         )
     );
+
+    // address -> height of the first confirmed output it received
+    static RECEIVED: RefCell<StableBTreeMap<[u8; 21], u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with_borrow(|m| m.get(RX_MEMORY_ID)),
+        )
+    );
 }
 
 pub mod syncing {
@@ -398,7 +408,7 @@ pub fn process_block() -> Result<bool, String> {
                         ));
                     }
 
-                    let chain = chain_from_key_bits(s.chain);
+                    let chain = s.chain_params();
                     UTXS.with(|utr| {
                         let utm = utr.borrow();
 
@@ -483,16 +493,19 @@ pub fn confirm_utxos() -> Result<bool, String> {
 
         UTXS.with(|utr| {
             UTXOS.with(|xor| {
-                flush_confirmed_utxos(
-                    &mut s.unconfirmed_utxs,
-                    &mut s.unconfirmed_utxos,
-                    &mut utr.borrow_mut(),
-                    &mut xor.borrow_mut(),
-                    confirmed_height,
-                )?;
-                s.confirmed_height = confirmed_height;
-                s.confirmed_blockhash = confirmed_blockhash;
-                Ok(UNPROCESSED_BLOCKS.with(|r| !r.borrow().is_empty()))
+                RECEIVED.with(|rxr| {
+                    flush_confirmed_utxos(
+                        &mut s.unconfirmed_utxs,
+                        &mut s.unconfirmed_utxos,
+                        &mut utr.borrow_mut(),
+                        &mut xor.borrow_mut(),
+                        &mut rxr.borrow_mut(),
+                        confirmed_height,
+                    )?;
+                    s.confirmed_height = confirmed_height;
+                    s.confirmed_blockhash = confirmed_blockhash;
+                    Ok(UNPROCESSED_BLOCKS.with(|r| !r.borrow().is_empty()))
+                })
             })
         })
     })
@@ -512,6 +525,24 @@ pub fn get_tx_block_height(txid: &ByteArray<32>) -> Option<u64> {
     })
 }
 
+/// Treats addresses that ever received an output as used, for BIP44 account discovery.
+pub struct UtxoHistory;
+
+#[async_trait]
+impl AddressHistory for UtxoHistory {
+    async fn has_history(&self, address: &script::Address) -> Result<bool, String> {
+        Ok(has_received(&address.0.into()))
+    }
+}
+
+/// Whether the address received an output since the start height, spent or not.
+pub fn has_received(addr: &ByteArray<21>) -> bool {
+    // UTXOS covers the addresses confirmed before RECEIVED was indexed
+    RECEIVED.with(|r| r.borrow().contains_key(addr))
+        || UTXOS.with(|r| r.borrow().contains_key(addr))
+        || state::with(|s| s.unconfirmed_utxos.contains_key(addr))
+}
+
 pub fn get_balance(addr: &ByteArray<21>) -> u64 {
     let mut res = UTXOS.with(|r| r.borrow().get(addr).unwrap_or_default()).0;
     state::with(|s| {
@@ -649,6 +680,7 @@ fn flush_confirmed_utxos(
     unconfirmed_utxos: &mut BTreeMap<ByteArray<21>, (UtxoStates, SpentUtxos)>,
     utm: &mut StableBTreeMap<[u8; 32], UnspentTxState, Memory>,
     xom: &mut StableBTreeMap<[u8; 21], UtxoStates, Memory>,
+    rxm: &mut StableBTreeMap<[u8; 21], u64, Memory>,
     confirmed_height: u64,
 ) -> Result<(), String> {
     let confirmed_txids: Vec<ByteArray<32>> = unconfirmed_utxs
@@ -707,6 +739,18 @@ fn flush_confirmed_utxos(
                 }
             });
 
+            // an output spent before it was confirmed is still a received one
+            let first_height = confirmed_utxos
+                .iter()
+                .chain(confirmed_stxos.iter())
+                .map(|ts| ts.0)
+                .min();
+            if let Some(height) = first_height {
+                if !rxm.contains_key(addr) {
+                    rxm.insert(**addr, height);
+                }
+            }
+
             if !confirmed_utxos.is_empty() || !confirmed_stxos.is_empty() {
                 match xom.get(addr) {
                     Some(mut uts) => {
@@ -56,16 +56,16 @@ pub async fn fetch_block() {
         let key = format!("blk-{height}-{ts}");
         let blockhash = DogecoinRPC::get_blockhash(&agent, key.clone(), height)
             .await
-            .map_err(FetchBlockError::ShouldWait)?;
+            .map_err(|err| FetchBlockError::ShouldWait(err.to_string()))?;
         let block = DogecoinRPC::get_block(&agent, blockhash.to_string(), &blockhash)
             .await
-            .map_err(FetchBlockError::Other)?;
+            .map_err(|err| FetchBlockError::Other(err.to_string()))?;
         store::append_block(height, blockhash, block).map_err(FetchBlockError::Reorg)?;
 
         for attester in store::state::get_attest_agents() {
             let hash = DogecoinRPC::get_blockhash(&attester, key.clone(), height)
                 .await
-                .map_err(FetchBlockError::Other)?;
+                .map_err(|err| FetchBlockError::Other(err.to_string()))?;
             if hash != blockhash {
                 return Err(FetchBlockError::Other(format!(
                     "attester {} returned different blockhash at {}: {:?}, expected {:?}",
@@ -1,4 +1,5 @@
 use candid::{CandidType, Principal};
+use dogecoin::chainparams::chain_from_key_bits;
 use serde::Deserialize;
 use std::time::Duration;
 
@@ -12,16 +13,26 @@ pub enum MinterArgs {
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct InitArgs {
-    chain: u8,              // TEST_NET_DOGE: 32, MAIN_NET_DOGE: 16
+    chain: u8,              // TEST_NET_DOGE: 32, MAIN_NET_DOGE: 16, REG_TEST_DOGE: 128
     ecdsa_key_name: String, // Use "dfx_test_key" for local replica and "test_key_1" for a testing key for testnet and mainnet
     ledger_canister: Option<Principal>,
     chain_canister: Option<Principal>,
 }
 
+impl InitArgs {
+    fn validate(&self) -> Result<(), String> {
+        chain_from_key_bits(self.chain)?;
+        Ok(())
+    }
+}
+
 #[ic_cdk::init]
 fn init(args: Option<MinterArgs>) {
     match args.expect("Init args is missing") {
         MinterArgs::Init(args) => {
+            if let Err(err) = args.validate() {
+                ic_cdk::trap(&err);
+            }
             store::state::with_mut(|s| {
                 s.chain = args.chain;
                 s.ecdsa_key_name = args.ecdsa_key_name;
@@ -63,6 +74,9 @@ pub struct UpgradeArgs {
 #[ic_cdk::post_upgrade]
 fn post_upgrade(args: Option<MinterArgs>) {
     store::state::load();
+    if let Err(err) = store::state::with(|s| chain_from_key_bits(s.chain)) {
+        ic_cdk::trap(&format!("invalid chain in the stored state: {}", err));
+    }
 
     match args {
         Some(MinterArgs::Upgrade(args)) => {
@@ -91,3 +105,27 @@ fn post_upgrade(args: Option<MinterArgs>) {
         ic_cdk::spawn(task::collect_and_clear_utxos())
     });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dogecoin::chainparams::{
+        KEY_ECPRIV, MAIN_NET_BTC, MAIN_NET_DOGE, REG_TEST_DOGE, TEST_NET_DOGE,
+    };
+
+    #[test]
+    fn test_init_args_chain() {
+        let args = |chain| InitArgs {
+            chain,
+            ecdsa_key_name: "dfx_test_key".to_string(),
+            ledger_canister: None,
+            chain_canister: None,
+        };
+        for chain in [MAIN_NET_DOGE, TEST_NET_DOGE, REG_TEST_DOGE] {
+            assert!(args(chain).validate().is_ok());
+        }
+        for chain in [0, KEY_ECPRIV, MAIN_NET_BTC, MAIN_NET_DOGE | TEST_NET_DOGE] {
+            assert!(args(chain).validate().is_err());
+        }
+    }
+}
@@ -1,12 +1,13 @@
 use candid::Principal;
 use ciborium::{from_reader, into_writer};
 use dogecoin::{
-    amount::{fee_by_size, DUST_LIMIT},
-    canister,
+    address::Address,
+    amount::DUST_LIMIT,
+    builder, canister,
     chainparams::{chain_from_key_bits, ChainParams, KeyBits},
-    err_string, script,
+    coinselect, err_string, policy, script,
     sighash::*,
-    transaction::{OutPoint, Transaction, TxIn, TxOut},
+    transaction::{OutPoint, TxOut},
 };
 use ic_stable_structures::{
     memory_manager::{MemoryId, MemoryManager, VirtualMemory},
@@ -16,7 +17,6 @@ use ic_stable_structures::{
 use icrc_ledger_types::icrc1::transfer::Memo;
 use serde::{Deserialize, Serialize};
 use serde_bytes::{ByteArray, ByteBuf};
-use std::str::FromStr;
 use std::{
     borrow::Cow,
     cell::RefCell,
@@ -73,7 +73,7 @@ pub struct State {
 
 impl State {
     pub fn chain_params(&self) -> &'static ChainParams {
-        chain_from_key_bits(self.chain)
+        chain_from_key_bits(self.chain).expect("chain is checked on init and upgrade")
     }
 
     pub fn get_chain(&self) -> Result<chain::Chain, String> {
@@ -408,6 +408,7 @@ pub async fn mint_ckdoge(caller: Principal) -> Result<u64, String> {
 }
 
 const MAX_RETRIEVE_BATCH_SIZE: usize = 100;
+const MAX_RETRIEVE_CANDIDATES: usize = 10 * MAX_RETRIEVE_BATCH_SIZE;
 
 pub async fn burn_ckdoge(
     caller: Principal,
@@ -426,10 +427,11 @@ pub async fn burn_ckdoge(
 
     let chain_params = state::with(|s| s.chain_params());
     let ledger = state::with(|s| s.get_ledger())?;
-    let receiver = script::Address::from_str(&address)?;
-    if !receiver.is_p2pkh(chain_params) {
+    let receiver = Address::parse(&address, chain_params).map_err(err_string)?;
+    if !receiver.is_p2pkh() {
         return Err("invalid p2pkh address".to_string());
     }
+    let receiver = script::Address::from(receiver);
 
     let balance = ledger.balance_of(ckdoge_acc).await?;
     if amount > balance {
@@ -438,32 +440,42 @@ pub async fn burn_ckdoge(
         ));
     }
 
-    let (utxos, total) = COLLECTED_UTXOS_HEAP.with(|r| {
+    let outputs = [TxOut {
+        value: amount,
+        script_pubkey: receiver.try_to_script(chain_params)?,
+    }];
+    // the fee is deducted from the amount sent to the receiver
+    let params = coinselect::SelectionParams {
+        subtract_fee: true,
+        ..coinselect::SelectionParams::new(&outputs, fee_rate, MAX_RETRIEVE_BATCH_SIZE)
+    };
+    let utxos = COLLECTED_UTXOS_HEAP.with(|r| {
         let m = r.borrow();
-        let batch_size = (m.len() / 200).max(1);
-        let mut total: u64 = 0;
-        let mut utxos: Vec<(UtxoState, Principal)> = vec![];
-        for (utxo, v) in m.iter() {
-            if v.1 == 0 {
-                total += utxo.3;
-                utxos.push((utxo.clone(), v.0));
-                if (utxos.len() >= batch_size && total >= amount)
-                    || utxos.len() >= MAX_RETRIEVE_BATCH_SIZE
-                {
-                    break;
-                }
-            }
-        }
-
-        (utxos, total)
-    });
-
-    if total < amount {
-        let size = utxos.len();
-        return Err(format!(
-            "The latest batch of {size} UTXOs has a total balance of {total}. This withdrawal cannot exceed the limit."
-        ));
-    }
+        // the oldest unused UTXOs
+        let candidates: Vec<(&UtxoState, Principal)> = m
+            .iter()
+            .filter(|(_, v)| v.1 == 0)
+            .take(MAX_RETRIEVE_CANDIDATES)
+            .map(|(utxo, v)| (utxo, v.0))
+            .collect();
+        let coins: Vec<coinselect::Coin> = candidates
+            .iter()
+            .map(|(utxo, _)| coinselect::Coin {
+                value: utxo.3,
+                input_size: coinselect::P2PKH_INPUT_SIZE,
+            })
+            .collect();
+        // consolidate the small UTXOs collected from deposits
+        coinselect::select_coins(&coins, &params, coinselect::CoinSelection::SmallestFirst)
+            .map(|selection| {
+                selection
+                    .selected
+                    .into_iter()
+                    .map(|i| (candidates[i].0.clone(), candidates[i].1))
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|err| format!("This withdrawal cannot exceed the limit: {err}"))
+    })?;
 
     let memo = to_cbor_bytes(&types::BurnMemo {
         address: receiver.clone().into(),
@@ -727,43 +739,31 @@ async fn burn_utxos(
     let mut kc = KeysCache::new(&ecdsa_public_key, chain_params);
     let minter = kc.get_or_set(ic_cdk::id())?;
 
-    let total: u64 = utxos.iter().map(|u| u.0 .3).sum();
-    let mut send_tx = Transaction {
-        version: Transaction::CURRENT_VERSION,
-        lock_time: 0,
-        input: utxos
-            .iter()
-            .map(|tx| {
-                TxIn::with_outpoint(OutPoint {
-                    txid: canister::Txid::from(tx.0 .1).into(),
-                    vout: tx.0 .2,
-                })
-            })
-            .collect(),
-        output: vec![
-            TxOut {
-                value: amount,
-                script_pubkey: receiver.to_script(chain_params),
-            },
-            TxOut {
-                value: total.saturating_sub(amount),
-                script_pubkey: minter.script_pubkey.clone(),
+    // the receiver pays the fee, the rest goes back to the minter
+    let mut tx_builder = builder::TransactionBuilder::new()
+        .fee_rate(fee_rate)
+        .add_output(receiver.try_to_script(chain_params)?, amount)
+        .change_to(minter.script_pubkey.clone())
+        .subtract_fee_from(0);
+    for utxo in utxos.iter() {
+        tx_builder = tx_builder.add_input(
+            OutPoint {
+                txid: canister::Txid::from(utxo.0 .1).into(),
+                vout: utxo.0 .2,
             },
-        ],
-    };
-
-    let fee = fee_by_size(send_tx.estimate_size() as u64, fee_rate);
-    send_tx.output[0].value = amount.saturating_sub(fee);
-    if send_tx.output[1].value <= DUST_LIMIT {
-        send_tx.output.pop();
+            utxo.0 .3,
+            builder::InputKind::P2PKH { compressed: true },
+        );
     }
+    let (mut send_tx, fee) = tx_builder.build().map_err(err_string)?;
 
     let mut sighasher = SighashCache::new(&mut send_tx);
     for (i, utxo) in utxos.iter().enumerate() {
         let acc = kc.get_or_set(utxo.1)?;
         let hash = sighasher.signature_hash(i, &acc.script_pubkey, EcdsaSighashType::All)?;
         let sig = sign_with(&key_name, acc.key_path.clone(), *hash).await?;
-        let signature = Signature::from_compact(&sig).map_err(err_string)?;
+        let mut signature = Signature::from_compact(&sig).map_err(err_string)?;
+        signature.normalize_s();
         sighasher
             .set_input_script(
                 i,
@@ -776,7 +776,10 @@ async fn burn_utxos(
             .map_err(err_string)?;
     }
 
-    let res = chain.send_tx(sighasher.transaction()).await?;
+    let tx = sighasher.transaction();
+    policy::check_standard(tx).map_err(err_string)?;
+    policy::check_fee(tx, fee).map_err(err_string)?;
+    let res = chain.send_tx(tx).await?;
 
     COLLECTED_UTXOS_HEAP.with(|r| {
         let mut m = r.borrow_mut();
@@ -830,7 +833,7 @@ impl<'a> KeysCache<'a> {
             let pk = derive_public_key(self.ecdsa_public_key, key_path.clone());
             let address = script::p2pkh_address(&pk.public_key, self.chain_params)?;
             let public_key = PublicKey::from_slice(&pk.public_key).map_err(err_string)?;
-            let script_pubkey = address.to_script(self.chain_params);
+            let script_pubkey = address.try_to_script(self.chain_params)?;
             let info = KeyInfo {
                 key_path,
                 public_key,
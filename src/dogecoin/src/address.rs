@@ -0,0 +1,256 @@
+use bitcoin::base58;
+use bitcoin::hashes::Hash;
+
+use crate::chainparams::{
+    ChainParams, DOGE_MAIN_NET_CHAIN, DOGE_REG_TEST_CHAIN, DOGE_TEST_NET_CHAIN,
+};
+use crate::script::{self, classify_script, PubkeyHash, ScriptBuf, ScriptHash, ScriptType};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Network {
+    Main,
+    Test,
+    RegTest,
+}
+
+impl Network {
+    pub fn from_chain(chain: &ChainParams) -> Result<Self, AddressError> {
+        if chain == &DOGE_MAIN_NET_CHAIN {
+            Ok(Network::Main)
+        } else if chain == &DOGE_TEST_NET_CHAIN {
+            Ok(Network::Test)
+        } else if chain == &DOGE_REG_TEST_CHAIN {
+            Ok(Network::RegTest)
+        } else {
+            Err(AddressError::UnknownChain(chain.chain_name))
+        }
+    }
+
+    pub fn chain_params(&self) -> &'static ChainParams {
+        match self {
+            Network::Main => &DOGE_MAIN_NET_CHAIN,
+            Network::Test => &DOGE_TEST_NET_CHAIN,
+            Network::RegTest => &DOGE_REG_TEST_CHAIN,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum AddressKind {
+    P2PKH,
+    P2SH,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AddressError {
+    Base58,
+    InvalidLength(usize),
+    /// The version byte is not a P2PKH or P2SH prefix of the expected chain.
+    WrongNetwork {
+        prefix: u8,
+        chain: &'static str,
+    },
+    UnknownChain(&'static str),
+    /// The script is not a P2PKH or P2SH script_pubkey.
+    UnsupportedScript(ScriptType),
+}
+
+impl std::fmt::Display for AddressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressError::Base58 => write!(f, "invalid address"),
+            AddressError::InvalidLength(len) => write!(f, "invalid address length {}", len),
+            AddressError::WrongNetwork { prefix, chain } => write!(
+                f,
+                "address prefix {:#04x} is not valid on the {} chain",
+                prefix, chain
+            ),
+            AddressError::UnknownChain(name) => write!(f, "unknown chain {}", name),
+            AddressError::UnsupportedScript(t) => {
+                write!(f, "no address for {} script", t)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// A P2PKH or P2SH address bound to its network. Testnet and regtest share the
+/// P2SH prefix, so addresses are always parsed against the expected chain.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Address {
+    pub network: Network,
+    pub kind: AddressKind,
+    /// hash160 of the public key or redeem script
+    pub payload: [u8; 20],
+}
+
+impl Address {
+    pub fn new(network: Network, kind: AddressKind, payload: [u8; 20]) -> Self {
+        Address {
+            network,
+            kind,
+            payload,
+        }
+    }
+
+    /// Parses a base58check address, rejecting addresses of other chains.
+    pub fn parse(s: &str, chain: &ChainParams) -> Result<Self, AddressError> {
+        let data = base58::decode_check(s).map_err(|_| AddressError::Base58)?;
+        Self::from_bytes(&data, chain)
+    }
+
+    /// Decodes the 21-byte version byte + payload form, as stored by `script::Address`.
+    pub fn from_bytes(data: &[u8], chain: &ChainParams) -> Result<Self, AddressError> {
+        if data.len() != 21 {
+            return Err(AddressError::InvalidLength(data.len()));
+        }
+        let network = Network::from_chain(chain)?;
+        let kind = if data[0] == chain.p2pkh_address_prefix {
+            AddressKind::P2PKH
+        } else if data[0] == chain.p2sh_address_prefix {
+            AddressKind::P2SH
+        } else {
+            return Err(AddressError::WrongNetwork {
+                prefix: data[0],
+                chain: chain.chain_name,
+            });
+        };
+        let mut payload = [0u8; 20];
+        payload.copy_from_slice(&data[1..]);
+        Ok(Address::new(network, kind, payload))
+    }
+
+    pub fn from_script_pubkey(script: &[u8], chain: &ChainParams) -> Result<Self, AddressError> {
+        match classify_script(script, chain) {
+            (ScriptType::PubKeyHash, Some(addr)) | (ScriptType::ScriptHash, Some(addr)) => {
+                Self::from_bytes(&addr.0, chain)
+            }
+            (t, _) => Err(AddressError::UnsupportedScript(t)),
+        }
+    }
+
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        match self.kind {
+            AddressKind::P2PKH => ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array(self.payload)),
+            AddressKind::P2SH => ScriptBuf::new_p2sh(&ScriptHash::from_byte_array(self.payload)),
+        }
+    }
+
+    pub fn chain(&self) -> &'static ChainParams {
+        self.network.chain_params()
+    }
+
+    pub fn is_p2pkh(&self) -> bool {
+        self.kind == AddressKind::P2PKH
+    }
+
+    pub fn is_p2sh(&self) -> bool {
+        self.kind == AddressKind::P2SH
+    }
+
+    pub fn prefix(&self) -> u8 {
+        match self.kind {
+            AddressKind::P2PKH => self.chain().p2pkh_address_prefix,
+            AddressKind::P2SH => self.chain().p2sh_address_prefix,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 21] {
+        let mut data = [0u8; 21];
+        data[0] = self.prefix();
+        data[1..].copy_from_slice(&self.payload);
+        data
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", base58::encode_check(&self.to_bytes()))
+    }
+}
+
+impl From<&Address> for script::Address {
+    fn from(addr: &Address) -> Self {
+        script::Address(addr.to_bytes())
+    }
+}
+
+impl From<Address> for script::Address {
+    fn from(addr: Address) -> Self {
+        script::Address(addr.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::{hash160_to_address, p2pkh_address};
+    use hex::test_hex_unwrap as hex;
+
+    #[test]
+    fn test_parse_and_script_pubkey() {
+        let pubkey = hex!("03b0da749730dc9b4b1f4a14d6902877a92541f5368778853d9c4a0cb7802dcfb2");
+        for chain in [
+            &DOGE_MAIN_NET_CHAIN,
+            &DOGE_TEST_NET_CHAIN,
+            &DOGE_REG_TEST_CHAIN,
+        ] {
+            let legacy = p2pkh_address(&pubkey, chain).unwrap();
+            let addr = Address::parse(&legacy.to_string(), chain).unwrap();
+            assert_eq!(addr.network.chain_params(), chain);
+            assert!(addr.is_p2pkh());
+            assert_eq!(addr.to_string(), legacy.to_string());
+            assert_eq!(script::Address::from(&addr), legacy);
+            assert_eq!(addr.script_pubkey(), legacy.try_to_script(chain).unwrap());
+            assert_eq!(
+                Address::from_script_pubkey(addr.script_pubkey().as_bytes(), chain).unwrap(),
+                addr
+            );
+
+            let p2sh = hash160_to_address(&[7u8; 20], chain.p2sh_address_prefix);
+            let addr = Address::parse(&p2sh.to_string(), chain).unwrap();
+            assert_eq!(addr.kind, AddressKind::P2SH);
+            assert_eq!(addr.payload, [7u8; 20]);
+            assert_eq!(
+                Address::from_script_pubkey(addr.script_pubkey().as_bytes(), chain).unwrap(),
+                addr
+            );
+        }
+
+        // testnet (n...) and regtest (m...) P2PKH prefixes differ
+        let testnet = p2pkh_address(&pubkey, &DOGE_TEST_NET_CHAIN).unwrap();
+        assert_eq!(
+            Address::parse(&testnet.to_string(), &DOGE_MAIN_NET_CHAIN).unwrap_err(),
+            AddressError::WrongNetwork {
+                prefix: 0x71,
+                chain: "main"
+            }
+        );
+        assert!(Address::parse(&testnet.to_string(), &DOGE_REG_TEST_CHAIN).is_err());
+        assert!(script::Address::parse(&testnet.to_string(), &DOGE_MAIN_NET_CHAIN).is_err());
+        assert_eq!(
+            script::Address::parse(&testnet.to_string(), &DOGE_TEST_NET_CHAIN).unwrap(),
+            testnet
+        );
+        assert!(testnet.try_to_script(&DOGE_MAIN_NET_CHAIN).is_err());
+        // the deprecated FromStr does not check the network
+        assert_eq!(
+            testnet.to_string().parse::<script::Address>().unwrap(),
+            testnet
+        );
+
+        assert_eq!(
+            Address::parse("DBus3bamQjgJULBJtYXpEzDWQRwF5iwxgD", &DOGE_MAIN_NET_CHAIN),
+            Err(AddressError::Base58)
+        );
+        assert_eq!(
+            Address::from_bytes(&[0x1e; 20], &DOGE_MAIN_NET_CHAIN),
+            Err(AddressError::InvalidLength(20))
+        );
+        assert_eq!(
+            Address::from_script_pubkey(&hex!("6a0568656c6c6f"), &DOGE_MAIN_NET_CHAIN),
+            Err(AddressError::UnsupportedScript(ScriptType::NullData))
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use hex::prelude::*;
+
+use crate::interpreter::{is_valid_signature_encoding, scriptnum_decode, scriptnum_encode};
+use crate::opcodes::*;
+use crate::script::{get_op, PushBytes, ScriptBuf};
+
+// https://github.com/dogecoin/dogecoin/blob/master/src/core_write.cpp#L65
+const SIGHASH_NAMES: [(u8, &str); 6] = [
+    (0x01, "ALL"),
+    (0x02, "NONE"),
+    (0x03, "SINGLE"),
+    (0x81, "ALL|ANYONECANPAY"),
+    (0x82, "NONE|ANYONECANPAY"),
+    (0x83, "SINGLE|ANYONECANPAY"),
+];
+
+/// Renders a script as ASM like Dogecoin Core's ScriptToAsmStr.
+/// Pushes of up to 4 bytes are printed as numbers, larger pushes as hex. With
+/// `attempt_sighash_decode` (used for scriptSigs), signatures are printed as
+/// `<DER hex>[ALL]` instead of including the sighash byte.
+pub fn script_to_asm(script: &[u8], attempt_sighash_decode: bool) -> String {
+    let unspendable = script.first() == Some(&OP_RETURN);
+    let mut res: Vec<String> = Vec::new();
+    let mut pc = 0;
+    while pc < script.len() {
+        let (opcode, data) = match get_op(script, &mut pc) {
+            Some(op) => op,
+            None => {
+                res.push("[error]".to_string());
+                break;
+            }
+        };
+        if opcode > OP_PUSHDATA4 {
+            res.push(opcode_name(opcode).to_string());
+        } else if data.len() <= 4 {
+            res.push(
+                scriptnum_decode(data, 4)
+                    .expect("at most 4 bytes")
+                    .to_string(),
+            );
+        } else if attempt_sighash_decode && !unspendable && is_valid_signature_encoding(data) {
+            let (hash_type, sig) = data.split_last().unwrap();
+            match SIGHASH_NAMES.iter().find(|(v, _)| v == hash_type) {
+                Some((_, name)) => res.push(format!("{}[{}]", sig.to_lower_hex_string(), name)),
+                None => res.push(data.to_lower_hex_string()),
+            }
+        } else {
+            res.push(data.to_lower_hex_string());
+        }
+    }
+    res.join(" ")
+}
+
+/// Parses ASM into a script, accepting the syntax of Dogecoin Core's ParseScript:
+/// - decimal numbers, encoded as OP_0, OP_1NEGATE, OP_1..OP_16 or a minimal number push;
+/// - `0x` prefixed hex, inserted into the script as raw bytes;
+/// - `'quoted'` strings, pushed as data;
+/// - opcode names, with or without the `OP_` prefix.
+///
+/// It also accepts the output of `script_to_asm`: bare hex is pushed as data and
+/// `<hex>[ALL]` signatures get the sighash byte appended. A digit-only token is read
+/// as a number if it fits in 4 bytes, use the `0x` form for such data pushes.
+pub fn asm_to_script(asm: &str) -> Result<ScriptBuf, String> {
+    let mut script = ScriptBuf::new();
+    for token in asm.split_whitespace() {
+        if let Some(n) = parse_number(token) {
+            match n {
+                0 => script.push_opcode(OP_0.into()),
+                -1 => script.push_opcode(OP_1NEGATE.into()),
+                1..=16 => script.push_opcode((OP_1 + n as u8 - 1).into()),
+                _ => push_data(&mut script, &scriptnum_encode(n))?,
+            }
+        } else if let Some(raw) = token.strip_prefix("0x") {
+            let data =
+                Vec::from_hex(raw).map_err(|_| format!("asm: invalid hex token {}", token))?;
+            let mut bytes = script.into_bytes();
+            bytes.extend_from_slice(&data);
+            script = ScriptBuf::from_bytes(bytes);
+        } else if token.len() >= 2 && token.starts_with('\'') && token.ends_with('\'') {
+            push_data(&mut script, &token.as_bytes()[1..token.len() - 1])?;
+        } else if let Some(op) = opcode_from_name(token) {
+            script.push_opcode(op.into());
+        } else if let Some(data) = parse_data(token) {
+            push_data(&mut script, &data)?;
+        } else {
+            return Err(format!("asm: invalid token {}", token));
+        }
+    }
+    Ok(script)
+}
+
+fn parse_number(token: &str) -> Option<i64> {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // larger values can't come from a 4-byte push, so they are hex data
+    token
+        .parse::<i64>()
+        .ok()
+        .filter(|n| n.unsigned_abs() <= i32::MAX as u64)
+}
+
+fn parse_data(token: &str) -> Option<Vec<u8>> {
+    if let Some((sig, hash_type)) = token.strip_suffix(']').and_then(|t| t.split_once('[')) {
+        let (v, _) = SIGHASH_NAMES.iter().find(|(_, name)| *name == hash_type)?;
+        let mut data = Vec::from_hex(sig).ok()?;
+        data.push(*v);
+        return Some(data);
+    }
+    Vec::from_hex(token).ok()
+}
+
+fn push_data(script: &mut ScriptBuf, data: &[u8]) -> Result<(), String> {
+    let data = <&PushBytes>::try_from(data).map_err(|_| "asm: data too large".to_string())?;
+    script.push_slice(data);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex::test_hex_unwrap as hex;
+
+    #[test]
+    fn test_disassemble_and_assemble() {
+        for (asm, script) in [
+            ("OP_DUP OP_HASH160 8c3b27bd5e3e4b4a4ca8b5bfdb6ad0fd6ea4a4a1 OP_EQUALVERIFY OP_CHECKSIG", "76a9148c3b27bd5e3e4b4a4ca8b5bfdb6ad0fd6ea4a4a188ac"),
+            ("OP_HASH160 e9c3dd0c07aac76179ebc76a6c78d4d67c6c160a OP_EQUAL", "a914e9c3dd0c07aac76179ebc76a6c78d4d67c6c160a87"),
+            ("0 -1 1 16 17 -17 1000 2147483647", "004f51600111019102e80304ffffff7f"),
+            ("OP_RETURN 68656c6c6f20646f6765", "6a0a68656c6c6f20646f6765"),
+            ("500000 OP_CHECKLOCKTIMEVERIFY OP_DROP OP_CHECKSEQUENCEVERIFY OP_NOP10", "0320a107b175b2b9"),
+            ("OP_IF OP_ELSE OP_ENDIF OP_RESERVED OP_UNKNOWN OP_INVALIDOPCODE", "636768 50 ba ff"),
+        ] {
+            let script = hex!(&script.replace(' ', ""));
+            assert_eq!(script_to_asm(&script, false), asm);
+            if !asm.contains("OP_UNKNOWN") {
+                assert_eq!(asm_to_script(asm).unwrap().as_bytes(), &script[..], "{}", asm);
+            }
+        }
+
+        // truncated push
+        assert_eq!(
+            script_to_asm(&hex!("76a914aabb"), false),
+            "OP_DUP OP_HASH160 [error]"
+        );
+        // pushes over 4 bytes are printed as hex
+        assert_eq!(script_to_asm(&hex!("050102030405"), false), "0102030405");
+    }
+
+    #[test]
+    fn test_sighash_decode() {
+        let sig = "3044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5d794d12d482f0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb";
+        let pubkey = "03b0da749730dc9b4b1f4a14d6902877a92541f5368778853d9c4a0cb7802dcfb2";
+        let script = hex!(&format!("47{}0121{}", sig, pubkey));
+        let asm = script_to_asm(&script, true);
+        assert_eq!(asm, format!("{}[ALL] {}", sig, pubkey));
+        assert_eq!(
+            script_to_asm(&script, false),
+            format!("{}01 {}", sig, pubkey)
+        );
+        assert_eq!(asm_to_script(&asm).unwrap().as_bytes(), &script[..]);
+    }
+
+    #[test]
+    fn test_parse_core_syntax() {
+        for (asm, script) in [
+            (
+                "DUP HASH160 0x14 0x8c3b27bd5e3e4b4a4ca8b5bfdb6ad0fd6ea4a4a1 EQUALVERIFY CHECKSIG",
+                "76a9148c3b27bd5e3e4b4a4ca8b5bfdb6ad0fd6ea4a4a188ac",
+            ),
+            ("'dog' NOP2 OP_NOP3", "03646f67b1b2"),
+            ("0x4c 0x01 0x07", "4c0107"),
+        ] {
+            assert_eq!(
+                asm_to_script(asm).unwrap().as_bytes(),
+                &hex!(script)[..],
+                "{}",
+                asm
+            );
+        }
+        assert!(asm_to_script("OP_FOO").is_err());
+        assert!(asm_to_script("0xzz").is_err());
+        assert!(asm_to_script("abc").is_err());
+        assert!(asm_to_script("00[FOO]").is_err());
+    }
+}
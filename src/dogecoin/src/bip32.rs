@@ -0,0 +1,251 @@
+use bitcoin::base58;
+use bitcoin::bip32::{Xpriv, Xpub};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, Signing, Verification};
+use bitcoin::NetworkKind;
+use std::str::FromStr;
+
+use crate::chainparams::{chain_from_bip32_prefix, ChainParams};
+use crate::err_string;
+use crate::key::PrivateKey;
+use crate::script::{p2pkh_address, Address};
+
+pub use bitcoin::bip32::{ChainCode, ChildNumber, DerivationPath, Fingerprint};
+
+// bitcoin's bip32 codec only accepts its own version bytes, so Dogecoin prefixes are
+// swapped with the bitcoin mainnet ones around encode/decode.
+const BTC_XPRV_PREFIX: u32 = 0x0488ade4;
+const BTC_XPUB_PREFIX: u32 = 0x0488b21e;
+
+/// BIP32 extended private key, serialized as dgpv on mainnet and tprv on testnet/regtest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ExtendedPrivKey {
+    pub chain: &'static ChainParams,
+    pub inner: Xpriv,
+}
+
+/// BIP32 extended public key, serialized as dgub on mainnet and tpub on testnet/regtest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ExtendedPubKey {
+    pub chain: &'static ChainParams,
+    pub inner: Xpub,
+}
+
+impl ExtendedPrivKey {
+    pub fn new_master(chain: &'static ChainParams, seed: &[u8]) -> Result<Self, String> {
+        let inner = Xpriv::new_master(NetworkKind::Main, seed).map_err(err_string)?;
+        Ok(ExtendedPrivKey { chain, inner })
+    }
+
+    /// Derives a child key; indexes >= 0x80000000 (`ChildNumber::Hardened`) use hardened derivation.
+    pub fn ckd_priv<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        i: ChildNumber,
+    ) -> Result<Self, String> {
+        self.derive_priv(secp, &[i])
+    }
+
+    pub fn derive_priv<C: Signing, P: AsRef<[ChildNumber]>>(
+        &self,
+        secp: &Secp256k1<C>,
+        path: &P,
+    ) -> Result<Self, String> {
+        let inner = self.inner.derive_priv(secp, path).map_err(err_string)?;
+        Ok(ExtendedPrivKey {
+            chain: self.chain,
+            inner,
+        })
+    }
+
+    pub fn fingerprint<C: Signing>(&self, secp: &Secp256k1<C>) -> Fingerprint {
+        self.inner.fingerprint(secp)
+    }
+
+    pub fn to_private_key(&self) -> PrivateKey {
+        PrivateKey::new(self.inner.private_key, self.chain)
+    }
+
+    pub fn encode(&self) -> [u8; 78] {
+        let mut data = self.inner.encode();
+        data[0..4].copy_from_slice(&self.chain.bip32_privkey_prefix.to_be_bytes());
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let chain = match decode_prefix(data)? {
+            (chain, true) => chain,
+            (_, false) => return Err("bip32: not an extended private key".to_string()),
+        };
+        let mut data = data.to_vec();
+        data[0..4].copy_from_slice(&BTC_XPRV_PREFIX.to_be_bytes());
+        let inner = Xpriv::decode(&data).map_err(err_string)?;
+        Ok(ExtendedPrivKey { chain, inner })
+    }
+}
+
+impl ExtendedPubKey {
+    pub fn from_priv<C: Signing>(secp: &Secp256k1<C>, sk: &ExtendedPrivKey) -> Self {
+        ExtendedPubKey {
+            chain: sk.chain,
+            inner: Xpub::from_priv(secp, &sk.inner),
+        }
+    }
+
+    /// Derives a child key, fails for hardened indexes.
+    pub fn ckd_pub<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        i: ChildNumber,
+    ) -> Result<Self, String> {
+        self.derive_pub(secp, &[i])
+    }
+
+    pub fn derive_pub<C: Verification, P: AsRef<[ChildNumber]>>(
+        &self,
+        secp: &Secp256k1<C>,
+        path: &P,
+    ) -> Result<Self, String> {
+        let inner = self.inner.derive_pub(secp, path).map_err(err_string)?;
+        Ok(ExtendedPubKey {
+            chain: self.chain,
+            inner,
+        })
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.inner.public_key
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.inner.fingerprint()
+    }
+
+    pub fn p2pkh_address(&self) -> Address {
+        p2pkh_address(&self.inner.public_key.serialize(), self.chain).expect("valid public key")
+    }
+
+    pub fn encode(&self) -> [u8; 78] {
+        let mut data = self.inner.encode();
+        data[0..4].copy_from_slice(&self.chain.bip32_pubkey_prefix.to_be_bytes());
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let chain = match decode_prefix(data)? {
+            (chain, false) => chain,
+            (_, true) => return Err("bip32: not an extended public key".to_string()),
+        };
+        let mut data = data.to_vec();
+        data[0..4].copy_from_slice(&BTC_XPUB_PREFIX.to_be_bytes());
+        let inner = Xpub::decode(&data).map_err(err_string)?;
+        Ok(ExtendedPubKey { chain, inner })
+    }
+}
+
+fn decode_prefix(data: &[u8]) -> Result<(&'static ChainParams, bool), String> {
+    if data.len() != 78 {
+        return Err(format!("bip32: invalid extended key length {}", data.len()));
+    }
+    let prefix = u32::from_be_bytes(data[0..4].try_into().unwrap());
+    chain_from_bip32_prefix(prefix)
+        .ok_or_else(|| format!("bip32: unknown version {:#010x}", prefix))
+}
+
+impl std::fmt::Display for ExtendedPrivKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", base58::encode_check(&self.encode()))
+    }
+}
+
+impl FromStr for ExtendedPrivKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = base58::decode_check(s).map_err(err_string)?;
+        Self::decode(&data)
+    }
+}
+
+impl std::fmt::Display for ExtendedPubKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", base58::encode_check(&self.encode()))
+    }
+}
+
+impl FromStr for ExtendedPubKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = base58::decode_check(s).map_err(err_string)?;
+        Self::decode(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::*;
+    use hex::test_hex_unwrap as hex;
+
+    // re-encodes a bitcoin xprv/xpub with the given version prefix
+    fn with_prefix(s: &str, prefix: u32) -> String {
+        let mut data = base58::decode_check(s).unwrap();
+        data[0..4].copy_from_slice(&prefix.to_be_bytes());
+        base58::encode_check(&data)
+    }
+
+    #[test]
+    fn bip32_vector1() {
+        // https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki#test-vector-1
+        let vectors = [
+            ("m", "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi", "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"),
+            ("m/0'", "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7", "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw"),
+            ("m/0'/1", "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs", "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ"),
+        ];
+
+        let secp = Secp256k1::new();
+        let seed = hex!("000102030405060708090a0b0c0d0e0f");
+        for chain in [&DOGE_MAIN_NET_CHAIN, &DOGE_TEST_NET_CHAIN] {
+            let master = ExtendedPrivKey::new_master(chain, &seed).unwrap();
+            for (path, xprv, xpub) in vectors {
+                let path = DerivationPath::from_str(path).unwrap();
+                let sk = master.derive_priv(&secp, &path).unwrap();
+                let pk = ExtendedPubKey::from_priv(&secp, &sk);
+                let sk_str = sk.to_string();
+                let pk_str = pk.to_string();
+                assert!(sk_str.starts_with(chain.bip32_wif_privkey_prefix));
+                assert!(pk_str.starts_with(chain.bip32_wif_pubkey_prefix));
+                assert_eq!(sk_str, with_prefix(xprv, chain.bip32_privkey_prefix));
+                assert_eq!(pk_str, with_prefix(xpub, chain.bip32_pubkey_prefix));
+
+                assert_eq!(ExtendedPrivKey::from_str(&sk_str).unwrap(), sk);
+                assert_eq!(ExtendedPubKey::from_str(&pk_str).unwrap(), pk);
+                assert!(ExtendedPubKey::from_str(&sk_str).is_err());
+                assert!(ExtendedPrivKey::from_str(&pk_str).is_err());
+            }
+        }
+        assert!(ExtendedPrivKey::from_str(vectors[0].1).is_err());
+    }
+
+    #[test]
+    fn test_public_derivation() {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(&DOGE_MAIN_NET_CHAIN, &[7u8; 32]).unwrap();
+        let account = master
+            .derive_priv(&secp, &DerivationPath::from_str("m/44'/3'/0'").unwrap())
+            .unwrap();
+        let xpub = ExtendedPubKey::from_priv(&secp, &account);
+        let normal = DerivationPath::from_str("m/0/5").unwrap();
+        let child = account.derive_priv(&secp, &normal).unwrap();
+        let child_pub = xpub.derive_pub(&secp, &normal).unwrap();
+        assert_eq!(ExtendedPubKey::from_priv(&secp, &child), child_pub);
+        assert_eq!(child_pub.fingerprint(), child.fingerprint(&secp));
+        assert_eq!(
+            child.to_private_key().p2pkh_address(&secp),
+            child_pub.p2pkh_address()
+        );
+        assert!(xpub
+            .ckd_pub(&secp, ChildNumber::from_hardened_idx(0).unwrap())
+            .is_err());
+    }
+}
@@ -1,9 +1,11 @@
 use bitcoin::consensus::{encode, Decodable, Encodable};
-use bitcoin::hashes::{hash_newtype, sha256d, Hash};
+use bitcoin::hashes::{hash_newtype, sha256d, Hash, HashEngine};
 use bitcoin::merkle_tree;
 use bitcoin_io::{Error, Read, Write};
 use std::ops::Deref;
 
+use crate::chainparams::ChainParams;
+use crate::pow::{block_proof, check_proof_of_work, scrypt_hash, U256};
 use crate::transaction::Transaction;
 use crate::{consensus_decode_from_vec, consensus_encode_vec};
 
@@ -85,6 +87,7 @@ impl Decodable for TxMerkleNode {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockHeader {
     /// Block version, now repurposed for soft fork signalling.
     pub version: u32,
@@ -128,6 +131,68 @@ impl BlockHeader {
             .expect("engines don't error");
         BlockHash::from_engine(enc)
     }
+
+    /// Returns the scrypt proof-of-work hash of the header.
+    pub fn pow_hash(&self) -> BlockHash {
+        let mut buf = Vec::with_capacity(80);
+        self.consensus_encode(&mut buf)
+            .expect("vec writers don't error");
+        BlockHash::from(scrypt_hash(&buf))
+    }
+
+    /// Returns the target expanded from the compact `bits`.
+    pub fn target(&self) -> U256 {
+        U256::from_compact(self.bits).0
+    }
+
+    /// Returns the amount of work this header represents.
+    pub fn work(&self) -> U256 {
+        block_proof(self.bits)
+    }
+
+    /// Validates the header's proof of work, mirroring CheckAuxPowProofOfWork.
+    /// Legacy headers must meet their target with their own scrypt hash, while
+    /// AuxPoW headers must come with an `auxpow` proof whose parent block meets it.
+    pub fn validate_pow(
+        &self,
+        auxpow: Option<&MerkleTx>,
+        chain: &ChainParams,
+    ) -> Result<(), String> {
+        if !self.is_legacy() && chain.strict_chain_id && self.chain_id() != chain.auxpow_chain_id {
+            return Err(format!(
+                "block does not have our chain ID, expected {}, got {}",
+                chain.auxpow_chain_id,
+                self.chain_id()
+            ));
+        }
+
+        let (target, negative, overflow) = U256::from_compact(self.bits);
+        if negative || overflow || target.is_zero() {
+            return Err(format!("invalid compact target bits {:#010x}", self.bits));
+        }
+        if target > U256::from_compact(chain.pow_limit_bits).0 {
+            return Err(format!(
+                "target bits {:#010x} is above the proof of work limit",
+                self.bits
+            ));
+        }
+
+        match auxpow {
+            None => {
+                if self.is_auxpow() {
+                    return Err("no auxpow on block with auxpow version".to_string());
+                }
+                check_proof_of_work(&self.pow_hash(), self.bits)
+            }
+            Some(auxpow) => {
+                if !self.is_auxpow() {
+                    return Err("auxpow on block with non-auxpow version".to_string());
+                }
+                auxpow.check_proof_of_work(self.bits)?;
+                auxpow.check(&self.block_hash(), self.chain_id(), chain.strict_chain_id)
+            }
+        }
+    }
 }
 
 impl Encodable for BlockHeader {
@@ -160,11 +225,39 @@ impl Decodable for BlockHeader {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MerkleBranch {
     pub hash: Vec<TxMerkleNode>,
     pub side_mask: u32,
 }
 
+impl MerkleBranch {
+    /// Computes the merkle root from a leaf hash and this branch.
+    /// https://github.com/dogecoin/dogecoin/blob/master/src/auxpow.cpp#L165
+    pub fn compute_root(&self, leaf: sha256d::Hash) -> sha256d::Hash {
+        if self.side_mask == u32::MAX {
+            // nIndex == -1
+            return sha256d::Hash::all_zeros();
+        }
+
+        let mut hash = leaf;
+        let mut index = self.side_mask;
+        for node in self.hash.iter() {
+            let mut enc = sha256d::Hash::engine();
+            if index & 1 != 0 {
+                enc.input(node.as_ref());
+                enc.input(hash.as_ref());
+            } else {
+                enc.input(hash.as_ref());
+                enc.input(node.as_ref());
+            }
+            hash = sha256d::Hash::from_engine(enc);
+            index >>= 1;
+        }
+        hash
+    }
+}
+
 impl Encodable for MerkleBranch {
     fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
         let mut len = 0;
@@ -186,6 +279,7 @@ impl Decodable for MerkleBranch {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MerkleTx {
     pub coinbase_tx: Transaction,
     pub parent_hash: TxMerkleNode,
@@ -194,6 +288,126 @@ pub struct MerkleTx {
     pub parent_block: BlockHeader,
 }
 
+impl MerkleTx {
+    /// Magic bytes that precede the chain merkle root in the parent coinbase.
+    pub const MERGED_MINING_HEADER: [u8; 4] = [0xfa, 0xbe, b'm', b'm'];
+    /// Maximum length of the chain merkle branch.
+    pub const MAX_CHAIN_MERKLE_BRANCH: usize = 30;
+
+    /// Checks the AuxPoW proof structure for the auxiliary block `aux_block_hash`.
+    /// It does not check the proof of work of the parent block.
+    /// https://github.com/dogecoin/dogecoin/blob/master/src/auxpow.cpp#L76
+    pub fn check(
+        &self,
+        aux_block_hash: &BlockHash,
+        chain_id: u32,
+        strict_chain_id: bool,
+    ) -> Result<(), String> {
+        if self.coinbase_branch.side_mask != 0 {
+            return Err("auxpow is not a generate".to_string());
+        }
+
+        if strict_chain_id && self.parent_block.chain_id() == chain_id {
+            return Err("auxpow parent has our chain ID".to_string());
+        }
+
+        let merkle_height = self.blockchain_branch.hash.len();
+        if merkle_height > Self::MAX_CHAIN_MERKLE_BRANCH {
+            return Err("auxpow chain merkle branch too long".to_string());
+        }
+
+        // Check that the chain merkle root is in the coinbase
+        let root_hash = self
+            .blockchain_branch
+            .compute_root(aux_block_hash.to_raw_hash());
+        let mut root_hash = root_hash.to_byte_array();
+        root_hash.reverse(); // correct endian
+
+        // Check that we are in the parent block merkle tree
+        let coinbase_txid = self.coinbase_tx.compute_txid().to_raw_hash();
+        if self.coinbase_branch.compute_root(coinbase_txid)
+            != self.parent_block.merkle_root.to_raw_hash()
+        {
+            return Err("auxpow merkle root incorrect".to_string());
+        }
+
+        let script = self
+            .coinbase_tx
+            .input
+            .first()
+            .ok_or("auxpow coinbase has no input")?
+            .script
+            .as_bytes();
+
+        let head = find_bytes(script, &Self::MERGED_MINING_HEADER);
+        let pc = find_bytes(script, &root_hash)
+            .ok_or("auxpow missing chain merkle root in parent coinbase")?;
+
+        match head {
+            Some(head) => {
+                // Enforce only one chain merkle root by checking that a single instance of the
+                // merged mining header exists just before.
+                if find_bytes(&script[head + 1..], &Self::MERGED_MINING_HEADER).is_some() {
+                    return Err("multiple merged mining headers in coinbase".to_string());
+                }
+                if head + Self::MERGED_MINING_HEADER.len() != pc {
+                    return Err(
+                        "merged mining header is not just before chain merkle root".to_string()
+                    );
+                }
+            }
+            None => {
+                // For backward compatibility.
+                // Enforce only one chain merkle root by checking that it starts early in the coinbase.
+                // 8-12 bytes are enough to encode extraNonce and nBits.
+                if pc > 20 {
+                    return Err("auxpow chain merkle root must start in the first 20 bytes of the parent coinbase".to_string());
+                }
+            }
+        }
+
+        // Ensure we are at a deterministic point in the merkle leaves by hashing
+        // a nonce and our chain ID and comparing to the index.
+        let pc = pc + root_hash.len();
+        if script.len() - pc < 8 {
+            return Err(
+                "auxpow missing chain merkle tree size and nonce in parent coinbase".to_string(),
+            );
+        }
+
+        let size = u32::from_le_bytes(script[pc..pc + 4].try_into().unwrap());
+        if size != (1u32 << merkle_height) {
+            return Err("auxpow merkle branch size does not match parent coinbase".to_string());
+        }
+
+        let nonce = u32::from_le_bytes(script[pc + 4..pc + 8].try_into().unwrap());
+        if self.blockchain_branch.side_mask != expected_index(nonce, chain_id, merkle_height) {
+            return Err("auxpow wrong index".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the parent block's scrypt hash meets the target of the auxiliary block.
+    pub fn check_proof_of_work(&self, bits: u32) -> Result<(), String> {
+        check_proof_of_work(&self.parent_block.pow_hash(), bits)
+            .map_err(|err| format!("auxpow proof of work failed: {}", err))
+    }
+}
+
+// https://github.com/dogecoin/dogecoin/blob/master/src/auxpow.cpp#L183
+fn expected_index(nonce: u32, chain_id: u32, merkle_height: usize) -> u32 {
+    let mut rand = nonce;
+    rand = rand.wrapping_mul(1103515245).wrapping_add(12345);
+    rand = rand.wrapping_add(chain_id);
+    rand = rand.wrapping_mul(1103515245).wrapping_add(12345);
+    rand % (1u32 << merkle_height)
+}
+
+fn find_bytes(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
 impl Encodable for MerkleTx {
     fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
         let mut len = 0;
@@ -222,6 +436,7 @@ impl Decodable for MerkleTx {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     /// The block header
     pub header: BlockHeader,
@@ -251,6 +466,28 @@ impl Block {
             .map(|obj| obj.compute_txid().to_raw_hash());
         merkle_tree::calculate_root(hashes).map(|h| h.into())
     }
+
+    /// Validates the header's proof of work, see [`BlockHeader::validate_pow`].
+    pub fn validate_pow(&self, chain: &ChainParams) -> Result<(), String> {
+        self.header.validate_pow(self.auxpow.as_ref(), chain)
+    }
+
+    /// Verifies the AuxPoW proof of a merge-mined block, including the parent block's proof of work.
+    pub fn check_auxpow(&self, chain_id: u32, strict_chain_id: bool) -> Result<(), String> {
+        if !self.header.is_auxpow() {
+            return Err("block does not have auxpow version".to_string());
+        }
+        if strict_chain_id && self.header.chain_id() != chain_id {
+            return Err(format!(
+                "block does not have our chain ID, expected {}, got {}",
+                chain_id,
+                self.header.chain_id()
+            ));
+        }
+        let auxpow = self.auxpow.as_ref().ok_or("no auxpow on block")?;
+        auxpow.check(&self.block_hash(), self.header.chain_id(), strict_chain_id)?;
+        auxpow.check_proof_of_work(self.header.bits)
+    }
 }
 
 impl Encodable for Block {
@@ -309,6 +546,7 @@ impl From<&Block> for BlockHash {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chainparams::DOGE_MAIN_NET_CHAIN;
     use hex::test_hex_unwrap as hex;
     use hex::DisplayHex;
     use std::str::FromStr;
@@ -331,6 +569,19 @@ mod tests {
         let blk = Block::consensus_decode_from_finite_reader(&mut rd).unwrap();
         println!("Block: {:?}", blk);
         assert_eq!(blk.header.prev_blockhash, BlockHash::default());
+        assert!(blk.header.is_legacy());
+        blk.validate_pow(&DOGE_MAIN_NET_CHAIN).unwrap();
+        assert_eq!(blk.header.work(), U256::from_u64(0x100010));
+
+        let mut bad = blk.header.clone();
+        bad.nonce += 1;
+        assert!(bad.validate_pow(None, &DOGE_MAIN_NET_CHAIN).is_err());
+        let mut bad = blk.header.clone();
+        bad.bits = 0x1f0fffff;
+        assert!(bad
+            .validate_pow(None, &DOGE_MAIN_NET_CHAIN)
+            .unwrap_err()
+            .contains("proof of work limit"));
     }
 
     #[test]
@@ -369,6 +620,58 @@ mod tests {
         assert_eq!(blk.txdata.len(), 10);
         assert!(blk.header.is_auxpow());
         assert!(blk.check_merkle_root());
+        assert_eq!(blk.header.chain_id(), 0x62);
+        blk.check_auxpow(0x62, true).unwrap();
+        assert!(blk.check_auxpow(0x63, true).is_err());
+
+        let mut bad = blk.clone();
+        bad.header.nonce = 1;
+        assert_eq!(
+            bad.check_auxpow(0x62, true).unwrap_err(),
+            "auxpow missing chain merkle root in parent coinbase"
+        );
+        let mut bad = blk.clone();
+        let auxpow = bad.auxpow.as_mut().unwrap();
+        auxpow.blockchain_branch.side_mask ^= 1;
+        assert!(bad.check_auxpow(0x62, true).is_err());
+        let mut bad = blk.clone();
+        let auxpow = bad.auxpow.as_mut().unwrap();
+        auxpow.parent_block.nonce ^= 1;
+        assert!(bad.check_auxpow(0x62, true).is_err());
+        let auxpow = blk.auxpow.as_ref().unwrap();
+        auxpow.check_proof_of_work(blk.header.bits).unwrap();
+        assert!(auxpow.check_proof_of_work(0x1a000001).is_err());
+
+        blk.validate_pow(&DOGE_MAIN_NET_CHAIN).unwrap();
+        assert!(blk.header.validate_pow(None, &DOGE_MAIN_NET_CHAIN).is_err());
+        let mut bad = blk.header.clone();
+        bad.version &= !BlockHeader::VERSION_AUXPOW;
+        assert!(bad
+            .validate_pow(Some(auxpow), &DOGE_MAIN_NET_CHAIN)
+            .is_err());
+        let mut bad = blk.header.clone();
+        bad.version = (bad.version & 0xffff) | (0x63 << 16);
+        assert!(bad
+            .validate_pow(Some(auxpow), &DOGE_MAIN_NET_CHAIN)
+            .unwrap_err()
+            .contains("chain ID"));
+
+        #[cfg(feature = "serde")]
+        {
+            let data = serde_json::to_string(&blk).unwrap();
+            assert_eq!(serde_json::from_str::<Block>(&data).unwrap(), blk);
+            let json =
+                serde_json::to_value(crate::verbose::BlockJson::new(&blk, &DOGE_MAIN_NET_CHAIN))
+                    .unwrap();
+            assert_eq!(json["versionHex"], "00620104");
+            assert_eq!(json["bits"], "1a013c1b");
+            assert_eq!(json["tx"].as_array().unwrap().len(), 10);
+            assert_eq!(json["auxpow"]["chainindex"], 8);
+            assert_eq!(
+                json["auxpow"]["tx"]["txid"],
+                auxpow.coinbase_tx.compute_txid().to_string()
+            );
+        }
 
         let coins: Vec<u64> = blk
             .txdata
@@ -0,0 +1,720 @@
+use bitcoin::consensus::Decodable;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::block::{Block, BlockHash, BlockHeader, MerkleTx};
+use crate::chainparams::ChainParams;
+use crate::err_string;
+use crate::p2p::MAX_PAYLOAD_SIZE;
+use crate::pow::U256;
+
+/// Position of a raw block in the block files, `offset` points after the magic and size.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct BlockPos {
+    pub file: u32,
+    pub offset: u64,
+    pub size: u32,
+}
+
+/// Reads the magic and size prefixed blocks of a blk*.dat file in file order.
+/// Bytes before a magic are skipped, such as the zeroed space Dogecoin Core
+/// preallocates at the end of a file. After a corrupt record the search for the
+/// next magic resumes inside its magic like Dogecoin Core's reindex does.
+pub struct BlockFileReader<R> {
+    reader: R,
+    magic: [u8; 4],
+    file: u32,
+    offset: u64,
+    len: Option<u64>,
+    done: bool,
+}
+
+impl BlockFileReader<BufReader<File>> {
+    pub fn open(path: &Path, chain: &ChainParams) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| format!("failed to open {:?}: {}", path, err))?;
+        let num = parse_block_file_name(path).unwrap_or_default();
+        Ok(Self::new(BufReader::new(file), num, chain))
+    }
+}
+
+impl<R: Read> BlockFileReader<R> {
+    /// `file` is the number of the blk*.dat file, it is only used in the returned positions.
+    pub fn new(reader: R, file: u32, chain: &ChainParams) -> Self {
+        BlockFileReader {
+            reader,
+            magic: chain.magic,
+            file,
+            offset: 0,
+            len: None,
+            done: false,
+        }
+    }
+
+    fn find_magic(&mut self) -> Result<bool, String> {
+        let mut window = [0u8; 4];
+        let mut filled = 0;
+        let mut byte = [0u8; 1];
+        loop {
+            if !self.read_full(&mut byte)? {
+                return Ok(false);
+            }
+            if filled < 4 {
+                window[filled] = byte[0];
+                filled += 1;
+            } else {
+                window.rotate_left(1);
+                window[3] = byte[0];
+            }
+            if filled == 4 && window == self.magic {
+                return Ok(true);
+            }
+        }
+    }
+
+    // returns false on a clean or partial end of file
+    fn read_full(&mut self, buf: &mut [u8]) -> Result<bool, String> {
+        match self.reader.read_exact(buf) {
+            Ok(()) => {
+                self.offset += buf.len() as u64;
+                Ok(true)
+            }
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(false),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+// bodies smaller than the read buffer are read and dropped, seeking would discard the buffer
+const MIN_SEEK_SIZE: u64 = 8 * 1024;
+
+/// Raw block in a block file, or the reason the record was rejected.
+pub type RawRecord = (BlockPos, Result<Vec<u8>, String>);
+
+/// Header of a block in a block file with the AuxPoW of merge-mined blocks, or the
+/// reason the record could not be decoded.
+pub type HeaderRecord = (BlockPos, Result<(BlockHeader, Option<MerkleTx>), String>);
+
+impl<R: Read + Seek> BlockFileReader<R> {
+    /// Reads the next raw block, returns None at the end of the file. A record with
+    /// an invalid size is returned as an error, a block extending past the end of the
+    /// file is skipped. The outer error is an I/O failure.
+    pub fn next_raw(&mut self) -> Result<Option<RawRecord>, String> {
+        let pos = match self.next_record()? {
+            None => return Ok(None),
+            Some((pos, Some(err))) => return Ok(Some((pos, Err(err)))),
+            Some((pos, None)) => pos,
+        };
+        let mut data = vec![0u8; pos.size as usize];
+        if !self.read_full(&mut data)? {
+            return Ok(None);
+        }
+        Ok(Some((pos, Ok(data))))
+    }
+
+    /// Reads the header of the next block and skips its transactions, like
+    /// [`Self::next_raw`]. A record whose header can't be decoded is returned as an error.
+    pub fn next_header(&mut self) -> Result<Option<HeaderRecord>, String> {
+        let pos = match self.next_record()? {
+            None => return Ok(None),
+            Some((pos, Some(err))) => return Ok(Some((pos, Err(err)))),
+            Some((pos, None)) => pos,
+        };
+        let mut body = (&mut self.reader).take(pos.size as u64);
+        let res = BlockHeader::consensus_decode_from_finite_reader(&mut body).and_then(|header| {
+            let auxpow = if header.is_auxpow() {
+                Some(MerkleTx::consensus_decode_from_finite_reader(&mut body)?)
+            } else {
+                None
+            };
+            Ok((header, auxpow))
+        });
+        let remaining = body.limit();
+        self.offset = pos.offset + pos.size as u64 - remaining;
+        match res {
+            Ok(record) => {
+                if remaining < MIN_SEEK_SIZE {
+                    io::copy(&mut (&mut self.reader).take(remaining), &mut io::sink())
+                        .map_err(err_string)?;
+                } else {
+                    self.reader
+                        .seek(SeekFrom::Current(remaining as i64))
+                        .map_err(err_string)?;
+                }
+                self.offset += remaining;
+                Ok(Some((pos, Ok(record))))
+            }
+            Err(err) => {
+                self.rescan_from(&pos)?;
+                let err = format!("block file {} offset {}: {}", pos.file, pos.offset, err);
+                Ok(Some((pos, Err(err))))
+            }
+        }
+    }
+
+    // finds the next magic and size that fits in the file and leaves the reader at the
+    // start of the block, or returns the position and the error of an invalid size
+    fn next_record(&mut self) -> Result<Option<(BlockPos, Option<String>)>, String> {
+        let len = match self.len {
+            Some(len) => len,
+            None => {
+                let len = self.reader.seek(SeekFrom::End(0)).map_err(err_string)?;
+                self.reader
+                    .seek(SeekFrom::Start(self.offset))
+                    .map_err(err_string)?;
+                self.len = Some(len);
+                len
+            }
+        };
+        loop {
+            if !self.find_magic()? {
+                return Ok(None);
+            }
+            let mut size = [0u8; 4];
+            if !self.read_full(&mut size)? {
+                return Ok(None);
+            }
+            let pos = BlockPos {
+                file: self.file,
+                offset: self.offset,
+                size: u32::from_le_bytes(size),
+            };
+            if pos.size < 80 || pos.size as usize > MAX_PAYLOAD_SIZE {
+                self.rescan_from(&pos)?;
+                let err = format!(
+                    "block file {} offset {}: invalid block size {}",
+                    pos.file, pos.offset, pos.size
+                );
+                return Ok(Some((pos, Some(err))));
+            }
+            // a partially written block, or a corrupted size
+            if pos.offset + pos.size as u64 <= len {
+                return Ok(Some((pos, None)));
+            }
+            self.rescan_from(&pos)?;
+        }
+    }
+
+    // resumes the search for the next magic one byte into the magic of the record at `pos`
+    fn rescan_from(&mut self, pos: &BlockPos) -> Result<(), String> {
+        self.offset = pos.offset - 7;
+        self.reader
+            .seek(SeekFrom::Start(self.offset))
+            .map_err(err_string)?;
+        Ok(())
+    }
+}
+
+/// Yields the blocks of the file, and an error for each corrupt record. Iteration
+/// stops after the first I/O error.
+impl<R: Read + Seek> Iterator for BlockFileReader<R> {
+    type Item = Result<(BlockPos, Block), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_raw() {
+            Ok(Some((pos, Ok(data)))) => match decode_block(&pos, &data) {
+                Ok(blk) => Some(Ok((pos, blk))),
+                Err(err) => {
+                    if let Err(err) = self.rescan_from(&pos) {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                    Some(Err(err))
+                }
+            },
+            Ok(Some((_, Err(err)))) => Some(Err(err)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+fn decode_block(pos: &BlockPos, data: &[u8]) -> Result<Block, String> {
+    Block::consensus_decode_from_finite_reader(&mut &data[..])
+        .map_err(|err| format!("block file {} offset {}: {}", pos.file, pos.offset, err))
+}
+
+// blk00123.dat -> 123
+fn parse_block_file_name(path: &Path) -> Option<u32> {
+    let name = path.file_name()?.to_str()?;
+    u32::from_str(name.strip_prefix("blk")?.strip_suffix(".dat")?).ok()
+}
+
+/// The blocks directory of a Dogecoin Core datadir, e.g. `~/.dogecoin/blocks`.
+pub struct BlockDir {
+    chain: &'static ChainParams,
+    files: Vec<(u32, PathBuf)>,
+}
+
+impl BlockDir {
+    pub fn open(dir: &Path, chain: &'static ChainParams) -> Result<Self, String> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(err_string)? {
+            let path = entry.map_err(err_string)?.path();
+            if let Some(num) = parse_block_file_name(&path) {
+                files.push((num, path));
+            }
+        }
+        files.sort();
+        Ok(BlockDir { chain, files })
+    }
+
+    /// Returns the blk*.dat files ordered by number.
+    pub fn files(&self) -> &[(u32, PathBuf)] {
+        &self.files
+    }
+
+    /// Iterates over all blocks in file order, which is the order the node received
+    /// them in and not the height order. A corrupt record yields an error and the
+    /// blocks after it are still returned.
+    pub fn blocks(&self) -> impl Iterator<Item = Result<(BlockPos, Block), String>> + '_ {
+        self.files.iter().flat_map(|(num, path)| {
+            let reader: Box<dyn Iterator<Item = _>> = match File::open(path) {
+                Ok(file) => Box::new(BlockFileReader::new(BufReader::new(file), *num, self.chain)),
+                Err(err) => Box::new(std::iter::once(Err(format!(
+                    "failed to open {:?}: {}",
+                    path, err
+                )))),
+            };
+            reader
+        })
+    }
+
+    /// Scans the headers of all blocks and links them from the genesis block, records
+    /// that can't be decoded or fail the proof of work check are skipped.
+    ///
+    /// Dogecoin Core's LevelDB block index is not read, the index is rebuilt from the
+    /// block files instead, so blocks the node has not fully stored may be included.
+    pub fn index(&self) -> Result<BlockIndex, String> {
+        let mut index = BlockIndex::new(self.chain)?;
+        for (num, path) in &self.files {
+            let file =
+                File::open(path).map_err(|err| format!("failed to open {:?}: {}", path, err))?;
+            let mut reader = BlockFileReader::new(BufReader::new(file), *num, self.chain);
+            // corrupt records and headers without valid proof of work are skipped
+            while let Some((pos, res)) = reader.next_header()? {
+                let (header, auxpow) = match res {
+                    Ok(record) => record,
+                    Err(err) => {
+                        index.skipped.push((pos, err));
+                        continue;
+                    }
+                };
+                // the record is well formed, so scanning continues after it
+                if let Err(err) = header.validate_pow(auxpow.as_ref(), self.chain) {
+                    let err = format!("block file {} offset {}: {}", num, pos.offset, err);
+                    index.skipped.push((pos, err));
+                    continue;
+                }
+                index.insert(pos, header);
+            }
+        }
+        index.connect();
+        Ok(index)
+    }
+
+    pub fn read_block(&self, pos: &BlockPos) -> Result<Block, String> {
+        let (_, path) = self
+            .files
+            .iter()
+            .find(|(num, _)| *num == pos.file)
+            .ok_or_else(|| format!("block file {} not found", pos.file))?;
+        let mut file = File::open(path).map_err(err_string)?;
+        read_block_at(&mut file, pos)
+    }
+
+    /// Iterates over the blocks of the best chain in the index in height order,
+    /// starting at `from_height`.
+    pub fn best_chain_blocks<'a>(
+        &'a self,
+        index: &'a BlockIndex,
+        from_height: u32,
+    ) -> impl Iterator<Item = Result<(u32, Block), String>> + 'a {
+        let mut current: Option<(u32, File)> = None;
+        index
+            .best_chain()
+            .into_iter()
+            .skip(from_height as usize)
+            .map(move |entry| {
+                if current.as_ref().map(|(num, _)| *num) != Some(entry.pos.file) {
+                    let (_, path) = self
+                        .files
+                        .iter()
+                        .find(|(num, _)| *num == entry.pos.file)
+                        .ok_or_else(|| format!("block file {} not found", entry.pos.file))?;
+                    current = Some((entry.pos.file, File::open(path).map_err(err_string)?));
+                }
+                let (_, file) = current.as_mut().unwrap();
+                let blk = read_block_at(file, &entry.pos)?;
+                if blk.block_hash() != entry.hash {
+                    return Err(format!("block {} changed on disk", entry.hash));
+                }
+                Ok((entry.height, blk))
+            })
+    }
+}
+
+fn read_block_at(file: &mut File, pos: &BlockPos) -> Result<Block, String> {
+    file.seek(SeekFrom::Start(pos.offset)).map_err(err_string)?;
+    let mut data = vec![0u8; pos.size as usize];
+    file.read_exact(&mut data).map_err(err_string)?;
+    decode_block(pos, &data)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IndexEntry {
+    pub header: BlockHeader,
+    pub hash: BlockHash,
+    pub pos: BlockPos,
+    pub height: u32,
+    /// Cumulative work of the chain up to and including this block.
+    pub chainwork: U256,
+}
+
+/// Block positions keyed by hash, linked into chains from the genesis block.
+pub struct BlockIndex {
+    genesis: BlockHash,
+    unconnected: HashMap<BlockHash, (BlockPos, BlockHeader)>,
+    skipped: Vec<(BlockPos, String)>,
+    entries: HashMap<BlockHash, IndexEntry>,
+    best: Option<BlockHash>,
+}
+
+impl BlockIndex {
+    pub fn new(chain: &ChainParams) -> Result<Self, String> {
+        Ok(BlockIndex {
+            genesis: BlockHash::from_str(chain.genesis_block).map_err(err_string)?,
+            unconnected: HashMap::new(),
+            skipped: Vec::new(),
+            entries: HashMap::new(),
+            best: None,
+        })
+    }
+
+    /// Adds a block, call [`Self::connect`] after all blocks are inserted.
+    pub fn insert(&mut self, pos: BlockPos, header: BlockHeader) {
+        self.unconnected.insert(header.block_hash(), (pos, header));
+    }
+
+    /// Links the inserted blocks to the genesis block and selects the chain with the most
+    /// cumulative work. Blocks whose ancestors are missing stay unconnected.
+    pub fn connect(&mut self) {
+        let mut children: HashMap<BlockHash, Vec<BlockHash>> = HashMap::new();
+        for (hash, (_, header)) in &self.unconnected {
+            children
+                .entry(header.prev_blockhash)
+                .or_default()
+                .push(*hash);
+        }
+
+        let mut queue: Vec<BlockHash> = self.entries.keys().copied().collect();
+        if let Some((pos, header)) = self.unconnected.remove(&self.genesis) {
+            self.entries.insert(
+                self.genesis,
+                IndexEntry {
+                    hash: self.genesis,
+                    chainwork: header.work(),
+                    header,
+                    pos,
+                    height: 0,
+                },
+            );
+            queue.push(self.genesis);
+        }
+        while let Some(parent) = queue.pop() {
+            let (height, chainwork) = {
+                let entry = &self.entries[&parent];
+                (entry.height, entry.chainwork)
+            };
+            for hash in children.remove(&parent).unwrap_or_default() {
+                if let Some((pos, header)) = self.unconnected.remove(&hash) {
+                    self.entries.insert(
+                        hash,
+                        IndexEntry {
+                            hash,
+                            chainwork: chainwork + header.work(),
+                            header,
+                            pos,
+                            height: height + 1,
+                        },
+                    );
+                    queue.push(hash);
+                }
+            }
+        }
+
+        // on equal work, the block stored first wins like in Dogecoin Core
+        self.best = self
+            .entries
+            .values()
+            .max_by(|a, b| a.chainwork.cmp(&b.chainwork).then(b.pos.cmp(&a.pos)))
+            .map(|entry| entry.hash);
+    }
+
+    pub fn get(&self, hash: &BlockHash) -> Option<&IndexEntry> {
+        self.entries.get(hash)
+    }
+
+    /// Returns the tip of the chain with the most work.
+    pub fn tip(&self) -> Option<&IndexEntry> {
+        self.best.as_ref().map(|hash| &self.entries[hash])
+    }
+
+    /// Number of connected blocks.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of blocks whose ancestors are not in the block files.
+    pub fn unconnected_len(&self) -> usize {
+        self.unconnected.len()
+    }
+
+    /// Records skipped by [`BlockDir::index`] with the reason.
+    pub fn skipped(&self) -> &[(BlockPos, String)] {
+        &self.skipped
+    }
+
+    /// Returns the blocks of the best chain from the genesis block to the tip.
+    pub fn best_chain(&self) -> Vec<&IndexEntry> {
+        let mut chain = Vec::new();
+        let mut next = self.tip();
+        while let Some(entry) = next {
+            chain.push(entry);
+            next = if entry.height == 0 {
+                None
+            } else {
+                self.entries.get(&entry.header.prev_blockhash)
+            };
+        }
+        chain.reverse();
+        chain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::{DOGE_MAIN_NET_CHAIN, DOGE_REG_TEST_CHAIN};
+    use bitcoin::consensus::Encodable;
+    use hex::test_hex_unwrap as hex;
+    use std::io::Cursor;
+
+    fn child(parent: &Block, tag: u32) -> Block {
+        let mut blk = parent.clone();
+        blk.header.prev_blockhash = parent.block_hash();
+        blk.header.time = parent.header.time + tag;
+        blk.header.nonce = 0;
+        while blk.header.validate_pow(None, &DOGE_REG_TEST_CHAIN).is_err() {
+            blk.header.nonce += 1;
+        }
+        blk
+    }
+
+    fn record(blk: &Block) -> Vec<u8> {
+        let mut raw = Vec::new();
+        blk.consensus_encode(&mut raw).unwrap();
+        let mut data = DOGE_REG_TEST_CHAIN.magic.to_vec();
+        data.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        data.extend_from_slice(&raw);
+        data
+    }
+
+    #[test]
+    fn test_block_files() {
+        // block 0 on regtest
+        let data = hex!("010000000000000000000000000000000000000000000000000000000000000000000000696ad20e2dd4365c7459b4a4a5af743d5e92c6da3229e6532cd605f6533f2a5bdae5494dffff7f20020000000101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff1004ffff001d0104084e696e746f6e646fffffffff010058850c020000004341040184710fa689ad5023690c80f3a49c8f13f8d45b8c857fbcbc8bc4a8e4d3eb4b10f4d4604fa08dce601aaf0f470216fe1b51850b4acf21b179c45070ac7b03a9ac00000000");
+        let genesis = Block::consensus_decode_from_finite_reader(&mut &data[..]).unwrap();
+        assert_eq!(
+            genesis.block_hash().to_string(),
+            DOGE_REG_TEST_CHAIN.genesis_block
+        );
+        let b1 = child(&genesis, 1);
+        let b2 = child(&b1, 2);
+        let b3 = child(&b2, 3);
+        let fork1 = child(&genesis, 11);
+        let fork2 = child(&fork1, 12);
+        let fork3 = child(&fork2, 13);
+        let orphan = child(&child(&genesis, 21), 22);
+        let mut invalid_pow = child(&b3, 31);
+        while invalid_pow
+            .header
+            .validate_pow(None, &DOGE_REG_TEST_CHAIN)
+            .is_ok()
+        {
+            invalid_pow.header.nonce += 1;
+        }
+
+        let dir = std::env::temp_dir().join(format!("dogecoin-blocks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // blocks are stored out of order across files
+        let blk0: Vec<u8> = [&genesis, &b2, &fork1]
+            .into_iter()
+            .flat_map(record)
+            .collect();
+        std::fs::write(dir.join("blk00000.dat"), blk0).unwrap();
+        let blk1: Vec<u8> = [&fork2, &b1, &orphan, &invalid_pow]
+            .into_iter()
+            .flat_map(record)
+            .collect();
+        std::fs::write(dir.join("blk00001.dat"), blk1).unwrap();
+        // a corrupted record, zeroed preallocated space, a record with a size past the
+        // end of the file and a partially written block
+        let mut blk2 = vec![0u8; 7];
+        blk2.extend_from_slice(&DOGE_REG_TEST_CHAIN.magic);
+        blk2.extend_from_slice(&[4, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        blk2.extend_from_slice(&[0u8; 100]);
+        blk2.extend(record(&b3));
+        blk2.extend_from_slice(&DOGE_REG_TEST_CHAIN.magic);
+        blk2.extend_from_slice(&1000u32.to_le_bytes());
+        blk2.extend(record(&fork3));
+        blk2.extend_from_slice(&record(&b3)[..50]);
+        std::fs::write(dir.join("blk00002.dat"), &blk2).unwrap();
+        std::fs::write(dir.join("rev00000.dat"), [1u8; 10]).unwrap();
+
+        let blocks_dir = BlockDir::open(&dir, &DOGE_REG_TEST_CHAIN).unwrap();
+        assert_eq!(
+            blocks_dir
+                .files()
+                .iter()
+                .map(|(n, _)| *n)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        let blocks: Vec<_> = blocks_dir.blocks().collect();
+        assert_eq!(blocks.len(), 10);
+        assert!(blocks[7].is_err());
+        let (pos, blk) = blocks[8].as_ref().unwrap();
+        assert_eq!(blk, &b3);
+        assert_eq!(pos.file, 2);
+        assert_eq!(blocks_dir.read_block(pos).unwrap(), b3);
+        // found by rescanning the record with a size past the end of the file
+        assert_eq!(blocks[9].as_ref().unwrap().1, fork3);
+        let (pos, blk) = blocks[0].as_ref().unwrap();
+        assert_eq!(blk, &genesis);
+        assert_eq!(
+            *pos,
+            BlockPos {
+                file: 0,
+                offset: 8,
+                size: data.len() as u32,
+            }
+        );
+
+        let index = blocks_dir.index().unwrap();
+        assert_eq!(index.len(), 7);
+        assert_eq!(index.unconnected_len(), 1);
+        assert!(index.get(&invalid_pow.block_hash()).is_none());
+        assert_eq!(
+            index
+                .skipped()
+                .iter()
+                .map(|(pos, _)| (pos.file, pos.size))
+                .collect::<Vec<_>>(),
+            vec![(1, record(&invalid_pow).len() as u32 - 8), (2, 4)]
+        );
+        let tip = index.tip().unwrap();
+        assert_eq!(tip.hash, b3.block_hash());
+        assert_eq!(tip.height, 3);
+        assert_eq!(tip.chainwork, genesis.header.work().mul_u64(4));
+        assert_eq!(index.get(&fork2.block_hash()).unwrap().height, 2);
+        assert_eq!(index.get(&fork3.block_hash()).unwrap().height, 3);
+
+        let chain: Vec<_> = blocks_dir
+            .best_chain_blocks(&index, 0)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            chain,
+            vec![
+                (0, genesis.clone()),
+                (1, b1.clone()),
+                (2, b2.clone()),
+                (3, b3)
+            ]
+        );
+        let chain: Vec<_> = blocks_dir
+            .best_chain_blocks(&index, 2)
+            .map(|res| res.unwrap().0)
+            .collect();
+        assert_eq!(chain, vec![2, 3]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk error"))
+        }
+    }
+
+    impl Seek for FailingReader {
+        fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+            Ok(1000)
+        }
+    }
+
+    #[test]
+    fn test_read_error() {
+        let blocks: Vec<_> = BlockFileReader::new(FailingReader, 0, &DOGE_MAIN_NET_CHAIN).collect();
+        assert_eq!(blocks, vec![Err("disk error".to_string())]);
+    }
+
+    #[test]
+    fn test_next_header() {
+        // mainnet block merge-mined after height 371337, with AuxPoW and without transactions
+        let raw = hex!("040162000da1809fa62c133c8550bfd8b9cd10d4fe092bfde21e2878b1cb4e58af6a89bc832687bd8628066e1f0438bbb024fa0d887f8628481aa188235a13d5988b3bab2fb6dd641b3c011a0000000001000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4f03639426082f5669614254432f2cfabe6d6d2c1f86704524b309442ddaf53a0b12befc612d768b4061ef16f8c756d0ac8eae10000000000000001042e8fb01b6dd8dec3dc58ab82b00000000000000ffffffff0270084e25000000001976a914e16c28146ed4869c190b3f0bdc18d80d45f9213488ac0000000000000000266a24aa21a9edb157e30de8b33073d3306bc698a3a195af1ad403fd60039dce6949fd5c07f75400000000f8e994042c682997e6cbc8896cafc1272b0f7463e15de79c5c6dac28efc1399e07d9e698cf2defb464daad1ae9eafb4f8b322c4173aa42ebc416a266c04af1d1eac77feb6d1ecd018947547f09be57c71b5a124f8c92bb2e68f51ac188bdcc7dcd0b37b84bf014510523a0d947743663738cd9751725374a564ad84d8c2439a80297100afdaec455ff3bfd03dc6dbb2020392cffe6ad0c8aaf9df6ce6ac4fb00e81465b6291762e2637e44e6fee4da05e3f3daa1e0479795f8570389c61153ee4d2affa7b1efa60214706e02e0e2b6efb6e4a421dff1c1b3a1ce50b4cd153365c2a71619dc93c90d7ad10ee5c58343218020c497c82c43c8f1ca3a3e474eb31bea00000000040000000000000000000000000000000000000000000000000000000000000000e2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf97d24db2bfa41474bfb2f877d688fac5faa5e10a2808cf9de307370b93352e54894857d3e08918f70395d9206410fbfa942f1a889aa5ab8188ec33c2f6e207dc70800000000000020cb69a881eb9f5d7fd4bc222efd87cb2a9f5e6ffc415e53dd0e73366ab83c746d92bd765c621aba82551149c76600200f289f96e2994271ab9145d43648310d902fb6dd643ea1001a849aa3bf");
+        let mut data = Vec::new();
+        // small and large bodies, the large one is skipped with a seek
+        for padding in [10, 20_000] {
+            data.extend_from_slice(&DOGE_MAIN_NET_CHAIN.magic);
+            data.extend_from_slice(&((raw.len() + padding) as u32).to_le_bytes());
+            data.extend_from_slice(&raw);
+            data.resize(data.len() + padding, 0);
+        }
+        // an undecodable AuxPoW
+        data.extend_from_slice(&DOGE_MAIN_NET_CHAIN.magic);
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.extend_from_slice(&raw[..80]);
+        data.extend_from_slice(&[0xff; 20]);
+
+        let mut reader = BlockFileReader::new(Cursor::new(&data), 0, &DOGE_MAIN_NET_CHAIN);
+        let mut offset = 8;
+        for padding in [10, 20_000] {
+            let (pos, res) = reader.next_header().unwrap().unwrap();
+            assert_eq!(pos.offset, offset);
+            assert_eq!(pos.size as usize, raw.len() + padding);
+            let (header, auxpow) = res.unwrap();
+            assert_eq!(
+                header.block_hash().to_string(),
+                "fb5f5b5b7d70e660c2c67bca8d3328afae32ae8bb4c8d6cbc42d96ff876b0859"
+            );
+            header
+                .validate_pow(auxpow.as_ref(), &DOGE_MAIN_NET_CHAIN)
+                .unwrap();
+            offset += pos.size as u64 + 8;
+        }
+        let (pos, res) = reader.next_header().unwrap().unwrap();
+        assert_eq!(pos.offset, offset);
+        assert!(res.is_err());
+        assert!(reader.next_header().unwrap().is_none());
+    }
+}
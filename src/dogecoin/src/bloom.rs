@@ -0,0 +1,307 @@
+// https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki
+// https://github.com/dogecoin/dogecoin/blob/master/src/bloom.cpp
+
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::consensus::{encode, Decodable, Encodable};
+use bitcoin::Script;
+use bitcoin_io::{Error, Read, Write};
+
+use crate::chainparams::DOGE_MAIN_NET_CHAIN;
+use crate::script::{classify_script, ScriptType};
+use crate::transaction::{OutPoint, Transaction};
+
+/// 20,000 items with fp rate < 0.1% or 10,000 items and <0.0001%
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36000; // bytes
+pub const MAX_HASH_FUNCS: u32 = 50;
+
+/// Don't update the filter when matching outputs.
+pub const BLOOM_UPDATE_NONE: u8 = 0;
+/// Add the outpoint of every matched output to the filter.
+pub const BLOOM_UPDATE_ALL: u8 = 1;
+/// Only add outpoints of matched pay-to-pubkey and multisig outputs to the filter.
+pub const BLOOM_UPDATE_P2PUBKEY_ONLY: u8 = 2;
+pub const BLOOM_UPDATE_MASK: u8 = 3;
+
+const LN2SQUARED: f64 = 0.480_453_013_918_201_4;
+const LN2: f64 = std::f64::consts::LN_2;
+
+/// BIP37 bloom filter, as sent in the `filterload` message.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct BloomFilter {
+    pub data: Vec<u8>,
+    pub hash_funcs: u32,
+    pub tweak: u32,
+    pub flags: u8,
+}
+
+impl BloomFilter {
+    /// Creates a filter that will match `elements` items with the false positive rate `fp_rate`,
+    /// capped by `MAX_BLOOM_FILTER_SIZE` and `MAX_HASH_FUNCS`.
+    pub fn new(elements: u32, fp_rate: f64, tweak: u32, flags: u8) -> Self {
+        let elements = elements.max(1);
+        let size = ((-1.0 / LN2SQUARED * elements as f64 * fp_rate.ln()) as usize)
+            .min(MAX_BLOOM_FILTER_SIZE * 8)
+            / 8;
+        // integer division first, as Core's vData.size() * 8 / nElements * LN2
+        let hash_funcs = (((size * 8 / elements as usize) as f64 * LN2) as u32).min(MAX_HASH_FUNCS);
+        BloomFilter {
+            data: vec![0; size],
+            hash_funcs,
+            tweak,
+            flags,
+        }
+    }
+
+    pub fn is_within_size_constraints(&self) -> bool {
+        self.data.len() <= MAX_BLOOM_FILTER_SIZE && self.hash_funcs <= MAX_HASH_FUNCS
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        if self.data.is_empty() {
+            return;
+        }
+        for i in 0..self.hash_funcs {
+            let idx = self.hash(i, key);
+            self.data[idx >> 3] |= 1 << (idx & 7);
+        }
+    }
+
+    pub fn insert_outpoint(&mut self, outpoint: &OutPoint) {
+        self.insert(&bitcoin::consensus::encode::serialize(outpoint));
+    }
+
+    pub fn contains(&self, key: &[u8]) -> bool {
+        if self.data.is_empty() {
+            return false;
+        }
+        (0..self.hash_funcs).all(|i| {
+            let idx = self.hash(i, key);
+            self.data[idx >> 3] & (1 << (idx & 7)) != 0
+        })
+    }
+
+    pub fn contains_outpoint(&self, outpoint: &OutPoint) -> bool {
+        self.contains(&bitcoin::consensus::encode::serialize(outpoint))
+    }
+
+    /// Returns whether the transaction matches the filter, by its txid, a data push in
+    /// an output script, a spent outpoint or a data push in an input script.
+    /// Outpoints of matched outputs are inserted into the filter according to its flags.
+    pub fn is_relevant_and_update(&mut self, tx: &Transaction) -> bool {
+        if self.data.is_empty() {
+            return false;
+        }
+        let txid = tx.compute_txid();
+        let mut found = self.contains(txid.as_ref());
+        for (i, output) in tx.output.iter().enumerate() {
+            if !self.contains_script_data(&output.script_pubkey) {
+                continue;
+            }
+            found = true;
+            let update = match self.flags & BLOOM_UPDATE_MASK {
+                BLOOM_UPDATE_ALL => true,
+                BLOOM_UPDATE_P2PUBKEY_ONLY => {
+                    // the chain only affects the returned address
+                    let (typ, _) =
+                        classify_script(output.script_pubkey.as_bytes(), &DOGE_MAIN_NET_CHAIN);
+                    typ == ScriptType::PubKey || typ == ScriptType::MultiSig
+                }
+                _ => false,
+            };
+            if update {
+                self.insert_outpoint(&OutPoint {
+                    txid,
+                    vout: i as u32,
+                });
+            }
+        }
+        if found {
+            return true;
+        }
+
+        tx.input.iter().any(|input| {
+            self.contains_outpoint(&input.prevout) || self.contains_script_data(&input.script)
+        })
+    }
+
+    fn contains_script_data(&self, script: &Script) -> bool {
+        for ins in script.instructions() {
+            match ins {
+                Ok(Instruction::PushBytes(data)) => {
+                    if !data.is_empty() && self.contains(data.as_bytes()) {
+                        return true;
+                    }
+                }
+                Ok(Instruction::Op(_)) => {}
+                Err(_) => break,
+            }
+        }
+        false
+    }
+
+    fn hash(&self, n: u32, key: &[u8]) -> usize {
+        let seed = n.wrapping_mul(0xfba4c795).wrapping_add(self.tweak);
+        murmur3(seed, key) as usize % (self.data.len() * 8)
+    }
+}
+
+// MurmurHash3 x86_32, https://github.com/aappleby/smhasher/blob/master/src/MurmurHash3.cpp
+fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+
+    let mut h1 = seed;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k1 = u32::from_le_bytes(chunk.try_into().unwrap());
+        k1 = k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 ^= k1;
+        h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut k1 = 0u32;
+        for (i, b) in tail.iter().enumerate() {
+            k1 ^= (*b as u32) << (8 * i);
+        }
+        k1 = k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 ^= k1;
+    }
+
+    h1 ^= data.len() as u32;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85ebca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2ae35);
+    h1 ^= h1 >> 16;
+    h1
+}
+
+impl Encodable for BloomFilter {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.data.consensus_encode(w)?;
+        len += self.hash_funcs.consensus_encode(w)?;
+        len += self.tweak.consensus_encode(w)?;
+        len += self.flags.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for BloomFilter {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        Ok(BloomFilter {
+            data: Decodable::consensus_decode_from_finite_reader(r)?,
+            hash_funcs: Decodable::consensus_decode_from_finite_reader(r)?,
+            tweak: Decodable::consensus_decode_from_finite_reader(r)?,
+            flags: Decodable::consensus_decode_from_finite_reader(r)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{TxIn, TxOut};
+    use bitcoin::ScriptBuf;
+    use hex::test_hex_unwrap as hex;
+    use hex::DisplayHex;
+
+    #[test]
+    fn test_murmur3() {
+        // https://github.com/bitcoin/bitcoin/blob/master/src/test/hash_tests.cpp
+        assert_eq!(murmur3(0x00000000, &[]), 0x00000000);
+        assert_eq!(murmur3(0xFBA4C795, &[]), 0x6a396f08);
+        assert_eq!(murmur3(0xffffffff, &[]), 0x81f16f39);
+        assert_eq!(murmur3(0x00000000, &hex!("00")), 0x514e28b7);
+        assert_eq!(murmur3(0xFBA4C795, &hex!("00")), 0xea3f0b17);
+        assert_eq!(murmur3(0x00000000, &hex!("ff")), 0xfd6cf10d);
+        assert_eq!(murmur3(0x00000000, &hex!("0011")), 0x16c6b7ab);
+        assert_eq!(murmur3(0x00000000, &hex!("001122")), 0x8eb51c3d);
+        assert_eq!(murmur3(0x00000000, &hex!("00112233")), 0xb4471bf8);
+        assert_eq!(murmur3(0x00000000, &hex!("0011223344")), 0xe2301fa8);
+        assert_eq!(murmur3(0x00000000, &hex!("001122334455")), 0xfc2e4a15);
+        assert_eq!(murmur3(0x00000000, &hex!("00112233445566")), 0xb074502c);
+        assert_eq!(murmur3(0x00000000, &hex!("0011223344556677")), 0x8034d2a0);
+        assert_eq!(murmur3(0x00000000, &hex!("001122334455667788")), 0xb4698def);
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        // https://github.com/bitcoin/bitcoin/blob/master/src/test/bloom_tests.cpp
+        for (tweak, expected) in [
+            (0, "03614e9b050000000000000001"),
+            (2147483649, "03ce4299050000000100008001"),
+        ] {
+            let mut filter = BloomFilter::new(3, 0.01, tweak, BLOOM_UPDATE_ALL);
+            assert!(filter.is_within_size_constraints());
+            filter.insert(&hex!("99108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+            assert!(filter.contains(&hex!("99108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+            // one bit different in first byte
+            assert!(!filter.contains(&hex!("19108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+            filter.insert(&hex!("b5a2c786d9ef4658287ced5914b37a1b4aa32eee"));
+            assert!(filter.contains(&hex!("b5a2c786d9ef4658287ced5914b37a1b4aa32eee")));
+            filter.insert(&hex!("b9300670b4c5366e95b2699e8b18bc75e5f729c5"));
+            assert!(filter.contains(&hex!("b9300670b4c5366e95b2699e8b18bc75e5f729c5")));
+
+            let data = bitcoin::consensus::encode::serialize(&filter);
+            assert_eq!(data.to_lower_hex_string(), expected);
+            let filter2: BloomFilter = bitcoin::consensus::encode::deserialize(&data).unwrap();
+            assert_eq!(filter2, filter);
+        }
+
+        // (elements, fp_rate, size, hash_funcs) where float division would give one more hash func
+        for (elements, fp_rate, size, hash_funcs) in
+            [(5, 0.0001, 11, 11), (10, 0.01, 11, 5), (7, 0.1, 4, 2)]
+        {
+            let filter = BloomFilter::new(elements, fp_rate, 0, BLOOM_UPDATE_NONE);
+            assert_eq!(filter.data.len(), size);
+            assert_eq!(filter.hash_funcs, hash_funcs);
+        }
+    }
+
+    #[test]
+    fn test_is_relevant_and_update() {
+        let pkh = hex!("54f6fb64f14b756d118a96a57a2f9ebf4b4708fe");
+        let mut script = vec![0x76, 0xa9, 0x14];
+        script.extend_from_slice(&pkh);
+        script.extend_from_slice(&[0x88, 0xac]);
+        let tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: 100,
+                script_pubkey: ScriptBuf::from_bytes(script),
+            }],
+        };
+        let txid = tx.compute_txid();
+        let spend = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn::with_outpoint(OutPoint { txid, vout: 0 })],
+            output: vec![],
+        };
+
+        let mut filter = BloomFilter::new(10, 0.000001, 0, BLOOM_UPDATE_NONE);
+        filter.insert(&pkh);
+        assert!(filter.is_relevant_and_update(&tx));
+        assert!(!filter.is_relevant_and_update(&spend));
+
+        let mut filter = BloomFilter::new(10, 0.000001, 0, BLOOM_UPDATE_ALL);
+        filter.insert(&pkh);
+        assert!(filter.is_relevant_and_update(&tx));
+        assert!(filter.is_relevant_and_update(&spend));
+
+        let mut filter = BloomFilter::new(10, 0.000001, 0, BLOOM_UPDATE_ALL);
+        filter.insert(&[1u8; 32]);
+        assert!(!filter.is_relevant_and_update(&tx));
+        filter.insert(txid.as_ref());
+        assert!(filter.is_relevant_and_update(&tx));
+
+        assert!(!BloomFilter::default().is_relevant_and_update(&tx));
+    }
+}
@@ -0,0 +1,540 @@
+use bitcoin::VarInt;
+
+use crate::amount::{fee_by_size, DUST_LIMIT};
+use crate::opcodes::OP_RETURN;
+use crate::script::{PushBytes, ScriptBuf};
+use crate::timelock::TimeLock;
+use crate::transaction::{OutPoint, Transaction, TxIn, TxOut};
+
+/// Maximum size of the data carried by an OP_RETURN output, see Dogecoin Core's MAX_OP_RETURN_RELAY.
+pub const MAX_OP_RETURN_DATA_SIZE: usize = 80;
+
+// the size of a DER signature with a low S value plus the sighash type byte
+const MAX_SIGNATURE_SIZE: usize = 72;
+
+/// The script type of an input, used to estimate the size of its signed scriptSig.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputKind {
+    P2PKH {
+        compressed: bool,
+    },
+    /// P2SH m-of-n multisig with compressed public keys.
+    P2SHMultiSig {
+        required: usize,
+        total: usize,
+    },
+    /// P2SH CLTV/CSV timelock script with a compressed public key.
+    P2SHTimeLock {
+        lock: TimeLock,
+    },
+}
+
+impl InputKind {
+    pub fn script_sig_size(&self) -> usize {
+        match self {
+            InputKind::P2PKH { compressed } => {
+                let pubkey_size = if *compressed { 33 } else { 65 };
+                1 + MAX_SIGNATURE_SIZE + 1 + pubkey_size
+            }
+            InputKind::P2SHMultiSig { required, total } => {
+                // OP_m <pubkey:33 * n> OP_n OP_CHECKMULTISIG
+                let redeem_size = 3 + 34 * total;
+                // OP_0 <sig * m> <redeem script>
+                1 + required * (1 + MAX_SIGNATURE_SIZE) + push_size(redeem_size)
+            }
+            InputKind::P2SHTimeLock { lock } => {
+                // <n> OP_CHECKLOCKTIMEVERIFY|OP_CHECKSEQUENCEVERIFY OP_DROP <pubkey:33> OP_CHECKSIG
+                let redeem_size = lock.push_size() + 37;
+                // <sig> <redeem script>
+                1 + MAX_SIGNATURE_SIZE + push_size(redeem_size)
+            }
+        }
+    }
+
+    /// Returns the estimated size of the signed input.
+    pub fn input_size(&self) -> usize {
+        let len = self.script_sig_size();
+        OutPoint::SIZE + VarInt::from(len).size() + len + 4 // sequence
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BuildError {
+    NoInputs,
+    NoOutputs,
+    InsufficientFunds {
+        required: u64,
+        available: u64,
+    },
+    DustOutput {
+        index: usize,
+        value: u64,
+    },
+    DataTooLarge {
+        size: usize,
+        max: usize,
+    },
+    MultipleDataOutputs,
+    /// The change is above the dust limit but no change output is set.
+    MissingChange {
+        change: u64,
+    },
+    InvalidOutputIndex(usize),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::NoInputs => write!(f, "no inputs"),
+            BuildError::NoOutputs => write!(f, "no outputs"),
+            BuildError::InsufficientFunds {
+                required,
+                available,
+            } => write!(
+                f,
+                "insufficient funds, expected: {}, got {}",
+                required, available
+            ),
+            BuildError::DustOutput { index, value } => write!(
+                f,
+                "output {} value {} is below dust limit: {}",
+                index, value, DUST_LIMIT
+            ),
+            BuildError::DataTooLarge { size, max } => {
+                write!(f, "OP_RETURN data size {} exceeds {}", size, max)
+            }
+            BuildError::MultipleDataOutputs => write!(f, "only one OP_RETURN output is allowed"),
+            BuildError::MissingChange { change } => {
+                write!(f, "change {} requires a change output", change)
+            }
+            BuildError::InvalidOutputIndex(i) => write!(f, "invalid output index {}", i),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct BuilderInput {
+    prevout: OutPoint,
+    value: u64,
+    kind: InputKind,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TransactionBuilder {
+    version: u32,
+    lock_time: u32,
+    sequence: Option<u32>,
+    fee_rate: u64,
+    inputs: Vec<BuilderInput>,
+    outputs: Vec<TxOut>,
+    change: Option<ScriptBuf>,
+    subtract_fee_from: Option<usize>,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransactionBuilder {
+    pub fn new() -> Self {
+        TransactionBuilder {
+            version: Transaction::CURRENT_VERSION,
+            lock_time: 0,
+            sequence: None,
+            fee_rate: 0,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            change: None,
+            subtract_fee_from: None,
+        }
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the lock time. Inputs default to sequence 0xfffffffe when it is not 0,
+    /// otherwise the lock time is ignored.
+    pub fn lock_time(mut self, lock_time: u32) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// Sets the sequence of all inputs.
+    pub fn sequence(mut self, sequence: u32) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// Sets the fee rate in units per byte, at least MIN_FEE_RATE is used.
+    pub fn fee_rate(mut self, fee_rate: u64) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    pub fn add_input(mut self, prevout: OutPoint, value: u64, kind: InputKind) -> Self {
+        self.inputs.push(BuilderInput {
+            prevout,
+            value,
+            kind,
+        });
+        self
+    }
+
+    pub fn add_output(mut self, script_pubkey: ScriptBuf, value: u64) -> Self {
+        self.outputs.push(TxOut {
+            value,
+            script_pubkey,
+        });
+        self
+    }
+
+    /// Adds an OP_RETURN output carrying the data.
+    pub fn add_data(mut self, data: &[u8]) -> Result<Self, BuildError> {
+        if data.len() > MAX_OP_RETURN_DATA_SIZE {
+            return Err(BuildError::DataTooLarge {
+                size: data.len(),
+                max: MAX_OP_RETURN_DATA_SIZE,
+            });
+        }
+        if self.outputs.iter().any(|o| o.script_pubkey.is_op_return()) {
+            return Err(BuildError::MultipleDataOutputs);
+        }
+        let mut script = ScriptBuf::new();
+        script.push_opcode(OP_RETURN.into());
+        script.push_slice(<&PushBytes>::try_from(data).unwrap());
+        self.outputs.push(TxOut {
+            value: 0,
+            script_pubkey: script,
+        });
+        Ok(self)
+    }
+
+    /// Sets the output that receives the change.
+    pub fn change_to(mut self, script_pubkey: ScriptBuf) -> Self {
+        self.change = Some(script_pubkey);
+        self
+    }
+
+    /// Deducts the fee from the output at index instead of the inputs,
+    /// so the recipient pays the fee.
+    pub fn subtract_fee_from(mut self, index: usize) -> Self {
+        self.subtract_fee_from = Some(index);
+        self
+    }
+
+    /// Returns the estimated size of the signed transaction without change output.
+    pub fn estimate_size(&self) -> usize {
+        let mut size: usize = 4; // version
+        size += VarInt::from(self.inputs.len()).size();
+        size += self
+            .inputs
+            .iter()
+            .map(|i| i.kind.input_size())
+            .sum::<usize>();
+        size += VarInt::from(self.outputs.len()).size();
+        size += self.outputs.iter().map(|o| o.size()).sum::<usize>();
+        size + 4 // lock_time
+    }
+
+    /// Builds the unsigned transaction, returns it with the fee.
+    pub fn build(self) -> Result<(Transaction, u64), BuildError> {
+        if self.inputs.is_empty() {
+            return Err(BuildError::NoInputs);
+        }
+        if self.outputs.is_empty() {
+            return Err(BuildError::NoOutputs);
+        }
+        if let Some(i) = self.subtract_fee_from {
+            if i >= self.outputs.len() || self.outputs[i].script_pubkey.is_op_return() {
+                return Err(BuildError::InvalidOutputIndex(i));
+            }
+        }
+        self.check_dust()?;
+
+        let size = self.estimate_size();
+        let fee = fee_by_size(size as u64, self.fee_rate);
+        let (fee_with_change, change_output) = match &self.change {
+            Some(script) => {
+                let change_output = TxOut {
+                    value: 0,
+                    script_pubkey: script.clone(),
+                };
+                // adding an output may also grow the outputs count
+                let extra = change_output.size() + VarInt::from(self.outputs.len() + 1).size()
+                    - VarInt::from(self.outputs.len()).size();
+                (
+                    fee_by_size((size + extra) as u64, self.fee_rate),
+                    Some(change_output),
+                )
+            }
+            None => (fee, None),
+        };
+
+        let available: u64 = self.inputs.iter().map(|i| i.value).sum();
+        let spent: u64 = self.outputs.iter().map(|o| o.value).sum();
+        // the fee paid by the inputs
+        let (required, paid_fee, paid_fee_with_change) = match self.subtract_fee_from {
+            Some(_) => (spent, 0, 0),
+            None => (spent + fee, fee, fee_with_change),
+        };
+        if available < required {
+            return Err(BuildError::InsufficientFunds {
+                required,
+                available,
+            });
+        }
+
+        let mut outputs = self.outputs;
+        let change = available.saturating_sub(spent + paid_fee_with_change);
+        let fee = if change >= DUST_LIMIT {
+            let mut change_output = change_output.ok_or(BuildError::MissingChange { change })?;
+            change_output.value = change;
+            if let Some(i) = self.subtract_fee_from {
+                outputs[i].value = outputs[i].value.saturating_sub(fee_with_change);
+            }
+            outputs.push(change_output);
+            fee_with_change
+        } else {
+            // the change is too small, give it to the miners
+            let excess = available - spent - paid_fee;
+            if let Some(i) = self.subtract_fee_from {
+                outputs[i].value = outputs[i].value.saturating_sub(fee.saturating_sub(excess));
+            }
+            fee.max(excess + paid_fee)
+        };
+        if let Some(i) = self.subtract_fee_from {
+            if outputs[i].value < DUST_LIMIT {
+                return Err(BuildError::DustOutput {
+                    index: i,
+                    value: outputs[i].value,
+                });
+            }
+        }
+
+        let sequence = self.sequence.unwrap_or(if self.lock_time > 0 {
+            u32::MAX - 1
+        } else {
+            u32::MAX
+        });
+        let tx = Transaction {
+            version: self.version,
+            lock_time: self.lock_time,
+            input: self
+                .inputs
+                .iter()
+                .map(|i| TxIn {
+                    sequence,
+                    ..TxIn::with_outpoint(i.prevout)
+                })
+                .collect(),
+            output: outputs,
+        };
+        Ok((tx, fee))
+    }
+
+    fn check_dust(&self) -> Result<(), BuildError> {
+        for (index, output) in self.outputs.iter().enumerate() {
+            if !output.script_pubkey.is_op_return() && output.value < DUST_LIMIT {
+                return Err(BuildError::DustOutput {
+                    index,
+                    value: output.value,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn push_size(len: usize) -> usize {
+    match len {
+        0..=75 => 1 + len,
+        76..=0xff => 2 + len,
+        _ => 3 + len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::{DOGE, MIN_FEE};
+    use crate::coinselect::{P2PKH_INPUT_SIZE, P2PKH_OUTPUT_SIZE};
+    use crate::script::PubkeyHash;
+    use crate::transaction::Txid;
+    use bitcoin::hashes::Hash;
+    use hex::test_hex_unwrap as hex;
+
+    fn outpoint(vout: u32) -> OutPoint {
+        OutPoint {
+            txid: Txid::hash(b"builder"),
+            vout,
+        }
+    }
+
+    fn p2pkh(n: u8) -> ScriptBuf {
+        ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array([n; 20]))
+    }
+
+    const COMPRESSED: InputKind = InputKind::P2PKH { compressed: true };
+
+    #[test]
+    fn test_input_size() {
+        assert_eq!(COMPRESSED.input_size() as u64, P2PKH_INPUT_SIZE);
+        assert_eq!(InputKind::P2PKH { compressed: false }.input_size(), 180);
+        assert_eq!(p2pkh(1).len() + 9, P2PKH_OUTPUT_SIZE as usize);
+
+        // 2-of-2 P2SH multisig spend, from the block in block::tests::test_block
+        let tx_data = hex!("0100000001892bc2522115e7bbf227a93aa5708e22b621616a398ab4c7119237b1da0b37d84f000000da00483045022100c5163a753aa37f9dfed708ed9167474f2b10a2b57238b00a95b3edb4f302d3fe022073fdc9c7a2147c79fb04e121a0aa64ce82901e31d15f65fa9c7a0633f2923112014730440220368808f54756249843c30cbf9ff964d1eddf4473f13352084dc1ab2229209e3302202578414b8fa035ffb58c861e965f0bcb9111636eedd173a936a4a326c138259d0147522102e60e9ac8a490768b3cd74fa55acf36d92491012c46dda806367f6dd567ac7b012103e5ed64e1c73f6d341d2a36d3f987a3b325117843f65afc24a616879047c7971652ae000000000200ca9a3b000000001976a914c7935d8f471d3472d31b6c5682f4bc2aaf2b806488acc54b563e0000000017a914f033fcfbdeafc8d9547c9015e021375694c316008700000000");
+        let tx = Transaction::try_from(&tx_data[..]).unwrap();
+        let kind = InputKind::P2SHMultiSig {
+            required: 2,
+            total: 2,
+        };
+        let script_sig_size = tx.input[0].script.len();
+        assert!(kind.script_sig_size() >= script_sig_size);
+        assert!(kind.script_sig_size() - script_sig_size <= 2);
+
+        let builder = TransactionBuilder::new()
+            .add_input(outpoint(0), 0, kind)
+            .add_output(tx.output[0].script_pubkey.clone(), 0)
+            .add_output(tx.output[1].script_pubkey.clone(), 0);
+        assert!(builder.estimate_size() >= tx.size());
+        assert!(builder.estimate_size() - tx.size() <= 2);
+    }
+
+    #[test]
+    fn test_build() {
+        let (tx, fee) = TransactionBuilder::new()
+            .add_input(outpoint(0), 3 * DOGE, COMPRESSED)
+            .add_input(outpoint(1), 2 * DOGE, COMPRESSED)
+            .add_output(p2pkh(1), DOGE)
+            .add_output(p2pkh(2), 2 * DOGE)
+            .add_data(b"hello")
+            .unwrap()
+            .change_to(p2pkh(3))
+            .fee_rate(10_000)
+            .build()
+            .unwrap();
+        assert_eq!(tx.output.len(), 4);
+        assert_eq!(tx.output[2].value, 0);
+        assert_eq!(tx.output[3].script_pubkey, p2pkh(3));
+        // version + inputs + outputs with change + OP_RETURN output + lock_time
+        assert_eq!(fee, (4 + 1 + 148 * 2 + 1 + 34 * 3 + (9 + 7) + 4) * 10_000);
+        assert_eq!(tx.output[3].value, 2 * DOGE - fee);
+        assert!(tx.input.iter().all(|i| i.sequence == u32::MAX));
+
+        // the change below dust limit goes to the fee
+        let (tx, fee) = TransactionBuilder::new()
+            .add_input(outpoint(0), 3 * DOGE + MIN_FEE + DUST_LIMIT - 1, COMPRESSED)
+            .add_output(p2pkh(1), 3 * DOGE)
+            .change_to(p2pkh(3))
+            .lock_time(100)
+            .build()
+            .unwrap();
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(fee, MIN_FEE + DUST_LIMIT - 1);
+        assert_eq!(tx.lock_time, 100);
+        assert_eq!(tx.input[0].sequence, u32::MAX - 1);
+
+        // the receiver pays the fee
+        let (tx, fee) = TransactionBuilder::new()
+            .add_input(outpoint(0), 10 * DOGE, COMPRESSED)
+            .add_output(p2pkh(1), 4 * DOGE)
+            .change_to(p2pkh(3))
+            .subtract_fee_from(0)
+            .sequence(0)
+            .build()
+            .unwrap();
+        assert_eq!(fee, MIN_FEE);
+        assert_eq!(tx.output[0].value, 4 * DOGE - MIN_FEE);
+        assert_eq!(tx.output[1].value, 6 * DOGE);
+        assert_eq!(tx.input[0].sequence, 0);
+        let (tx, fee) = TransactionBuilder::new()
+            .add_input(outpoint(0), 4 * DOGE + 100, COMPRESSED)
+            .add_output(p2pkh(1), 4 * DOGE)
+            .subtract_fee_from(0)
+            .build()
+            .unwrap();
+        assert_eq!(fee, MIN_FEE);
+        assert_eq!(tx.output[0].value, 4 * DOGE + 100 - MIN_FEE);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let builder = TransactionBuilder::new()
+            .add_input(outpoint(0), 2 * DOGE, COMPRESSED)
+            .add_output(p2pkh(1), 2 * DOGE);
+        assert_eq!(
+            builder.clone().build().unwrap_err(),
+            BuildError::InsufficientFunds {
+                required: 2 * DOGE + MIN_FEE,
+                available: 2 * DOGE
+            }
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .add_output(p2pkh(2), 100)
+                .build()
+                .unwrap_err(),
+            BuildError::DustOutput {
+                index: 1,
+                value: 100
+            }
+        );
+        assert_eq!(
+            builder.clone().add_data(&[0u8; 81]).unwrap_err(),
+            BuildError::DataTooLarge { size: 81, max: 80 }
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .add_data(b"a")
+                .unwrap()
+                .add_data(b"b")
+                .unwrap_err(),
+            BuildError::MultipleDataOutputs
+        );
+        assert_eq!(
+            TransactionBuilder::new()
+                .add_output(p2pkh(1), DOGE)
+                .build()
+                .unwrap_err(),
+            BuildError::NoInputs
+        );
+        assert_eq!(
+            TransactionBuilder::new()
+                .add_input(outpoint(0), 3 * DOGE, COMPRESSED)
+                .add_output(p2pkh(1), DOGE)
+                .build()
+                .unwrap_err(),
+            BuildError::MissingChange {
+                change: 2 * DOGE - MIN_FEE
+            }
+        );
+        assert_eq!(
+            TransactionBuilder::new()
+                .add_input(outpoint(0), DOGE, COMPRESSED)
+                .add_output(p2pkh(1), DUST_LIMIT)
+                .change_to(p2pkh(3))
+                .subtract_fee_from(0)
+                .build()
+                .unwrap_err(),
+            BuildError::DustOutput {
+                index: 0,
+                value: DUST_LIMIT - MIN_FEE
+            }
+        );
+        assert!(builder
+            .subtract_fee_from(2)
+            .build()
+            .unwrap_err()
+            .to_string()
+            .contains("invalid output index"));
+    }
+}
@@ -6,7 +6,7 @@ use ic_cdk::api::management_canister::http_request::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::jsonrpc::{JsonRPCAgent, APP_AGENT};
+use crate::jsonrpc::{is_rpc_error_body, JsonRPCAgent, APP_AGENT};
 
 #[derive(CandidType, Default, Clone, Deserialize, Serialize)]
 pub struct RPCAgent {
@@ -69,7 +69,11 @@ impl JsonRPCAgent for &RPCAgent {
 
         match http_request(request, self.max_cycles as u128).await {
             Ok((res,)) => {
-                if res.status >= 200u64 && res.status < 300u64 {
+                // the transform drops the headers, so RPC errors are recognized by their body
+                if (res.status >= 200u64 && res.status < 300u64)
+                    || ((res.status == 500u64 || res.status == 404u64)
+                        && is_rpc_error_body(&res.body))
+                {
                     Ok(bytes::Bytes::from(res.body))
                 } else {
                     Err(format!(
@@ -88,8 +92,9 @@ impl JsonRPCAgent for &RPCAgent {
     }
 }
 
-#[ic_cdk::query(hidden = true)]
-fn transform_jsonrpc(args: TransformArgs) -> HttpResponse {
+/// Transform of the [`RPCAgent`] requests. Canisters using the agent must export it as
+/// a query named `transform_jsonrpc`, e.g. with `#[ic_cdk::query(hidden = true)]`.
+pub fn transform_jsonrpc(args: TransformArgs) -> HttpResponse {
     HttpResponse {
         status: args.response.status,
         body: args.response.body,
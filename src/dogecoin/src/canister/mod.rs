@@ -8,7 +8,7 @@ mod agent;
 
 pub use agent::*;
 
-use crate::{script, transaction};
+use crate::{address, chainparams::ChainParams, err_string, script, transaction};
 
 #[derive(CandidType, Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Txid(pub ByteArray<32>);
@@ -55,6 +55,25 @@ impl From<Txid> for transaction::Txid {
 #[derive(CandidType, Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Address(pub ByteArray<21>);
 
+impl Address {
+    /// Parses a P2PKH or P2SH address of the given chain.
+    pub fn parse(s: &str, chain: &ChainParams) -> Result<Self, String> {
+        let addr = address::Address::parse(s, chain).map_err(err_string)?;
+        Ok(addr.into())
+    }
+
+    /// Checks that the address is a P2PKH or P2SH address of the given chain.
+    pub fn to_typed(&self, chain: &ChainParams) -> Result<address::Address, String> {
+        address::Address::from_bytes(&*self.0, chain).map_err(err_string)
+    }
+}
+
+impl From<address::Address> for Address {
+    fn from(addr: address::Address) -> Self {
+        Self(addr.to_bytes().into())
+    }
+}
+
 impl From<ByteArray<21>> for Address {
     fn from(val: ByteArray<21>) -> Self {
         Self(val)
@@ -79,6 +98,7 @@ impl From<Address> for script::Address {
     }
 }
 
+/// Deprecated: the network is not checked, use [`Address::parse`] instead.
 impl std::str::FromStr for Address {
     type Err = String;
 
@@ -198,3 +218,20 @@ pub struct UtxosOutput {
     pub confirmed_height: u64,
     pub utxos: Vec<Utxo>,
 }
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+pub struct AddressBalance {
+    pub address: Address,
+    pub change: u32, // 0 for receiving addresses, 1 for change addresses
+    pub index: u32,
+    pub balance: u64,
+}
+
+#[derive(CandidType, Clone, Debug, Serialize, Deserialize)]
+pub struct XpubBalanceOutput {
+    pub balance: u64,
+    pub addresses: Vec<AddressBalance>,
+    pub next_receive_index: u32,
+    pub next_change_index: u32,
+    pub tip_height: u64,
+}
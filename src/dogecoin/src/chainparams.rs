@@ -1,9 +1,13 @@
+use bitcoin::base58;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 pub struct ChainParams {
     pub chain_name: &'static str,
     pub genesis_block: &'static str,
+    /// The message start bytes of the P2P protocol.
+    pub magic: [u8; 4],
+    pub default_port: u16,
     pub p2pkh_address_prefix: u8,
     pub p2sh_address_prefix: u8,
     pub pkey_prefix: u8,
@@ -11,11 +15,66 @@ pub struct ChainParams {
     pub bip32_pubkey_prefix: u32,
     pub bip32_wif_privkey_prefix: &'static str,
     pub bip32_wif_pubkey_prefix: &'static str,
+    /// The easiest allowed proof-of-work target, as compact bits.
+    pub pow_limit_bits: u32,
+    /// The chain ID that merge-mined (AuxPoW) blocks must carry.
+    pub auxpow_chain_id: u32,
+    /// Whether blocks must carry our chain ID in their version.
+    pub strict_chain_id: bool,
+    /// The height from which blocks must be merge-mined (AuxPoW) and legacy blocks are rejected.
+    pub auxpow_height: u32,
+    /// The target time between blocks, in seconds.
+    pub pow_target_spacing: u32,
+    /// The retarget timespan before DigiShield, in seconds.
+    pub pow_target_timespan: u32,
+    /// DigiShield (retarget every block) applies to blocks after this height.
+    pub digishield_height: u32,
+    /// Whether a block may use the minimum difficulty when it comes more than
+    /// twice the target spacing after its parent.
+    pub pow_allow_min_difficulty_blocks: bool,
+    /// Minimum difficulty blocks are disallowed between `digishield_height` and this height.
+    pub digishield_min_difficulty_height: u32,
+    /// Whether the difficulty never retargets.
+    pub pow_no_retargeting: bool,
+}
+
+impl ChainParams {
+    pub fn is_digishield(&self, last_height: u32) -> bool {
+        last_height >= self.digishield_height
+    }
+
+    /// Returns the retarget timespan for the block following `last_height`.
+    pub fn target_timespan(&self, last_height: u32) -> u32 {
+        if self.is_digishield(last_height) {
+            self.pow_target_spacing
+        } else {
+            self.pow_target_timespan
+        }
+    }
+
+    /// Returns the number of blocks between retargets for the block following `last_height`.
+    pub fn difficulty_adjustment_interval(&self, last_height: u32) -> u32 {
+        self.target_timespan(last_height) / self.pow_target_spacing
+    }
+
+    /// Returns whether the block following `last_height` may use the minimum difficulty.
+    pub fn allow_min_difficulty_blocks(&self, last_height: u32) -> bool {
+        self.pow_allow_min_difficulty_blocks
+            && !(self.is_digishield(last_height)
+                && last_height < self.digishield_min_difficulty_height)
+    }
+
+    /// Returns whether a block at `height` may be a legacy (non merge-mined) block.
+    pub fn allow_legacy_blocks(&self, height: u32) -> bool {
+        height < self.auxpow_height
+    }
 }
 
 pub static DOGE_MAIN_NET_CHAIN: ChainParams = ChainParams {
     chain_name: "main",
     genesis_block: "1a91e3dace36e2be3bf030a65679fe821aa1d6ef92e7c9902eb318182c355691",
+    magic: [0xc0, 0xc0, 0xc0, 0xc0],
+    default_port: 22556,
     p2pkh_address_prefix: 0x1e,       // D
     p2sh_address_prefix: 0x16,        // 9 or A
     pkey_prefix: 0x9e,                // Q or 6
@@ -23,11 +82,23 @@ pub static DOGE_MAIN_NET_CHAIN: ChainParams = ChainParams {
     bip32_pubkey_prefix: 0x02facafd,  // dgub
     bip32_wif_privkey_prefix: "dgpv",
     bip32_wif_pubkey_prefix: "dgub",
+    pow_limit_bits: 0x1e0fffff,
+    auxpow_chain_id: 0x0062,
+    strict_chain_id: true,
+    auxpow_height: 371337,
+    pow_target_spacing: 60,
+    pow_target_timespan: 4 * 60 * 60,
+    digishield_height: 145000,
+    pow_allow_min_difficulty_blocks: false,
+    digishield_min_difficulty_height: 0,
+    pow_no_retargeting: false,
 };
 
 pub static DOGE_TEST_NET_CHAIN: ChainParams = ChainParams {
     chain_name: "test",
     genesis_block: "bb0a78264637406b6360aad926284d544d7049f45189db5664f3c4d07350559e",
+    magic: [0xfc, 0xc1, 0xb7, 0xdc],
+    default_port: 44556,
     p2pkh_address_prefix: 0x71,       // n
     p2sh_address_prefix: 0xc4,        // 2
     pkey_prefix: 0xf1,                // 9 or c
@@ -35,11 +106,23 @@ pub static DOGE_TEST_NET_CHAIN: ChainParams = ChainParams {
     bip32_pubkey_prefix: 0x043587cf,  // tpub
     bip32_wif_privkey_prefix: "tprv",
     bip32_wif_pubkey_prefix: "tpub",
+    pow_limit_bits: 0x1e0fffff,
+    auxpow_chain_id: 0x0062,
+    strict_chain_id: false,
+    auxpow_height: 158100,
+    pow_target_spacing: 60,
+    pow_target_timespan: 4 * 60 * 60,
+    digishield_height: 145000,
+    pow_allow_min_difficulty_blocks: true,
+    digishield_min_difficulty_height: 157500,
+    pow_no_retargeting: false,
 };
 
 pub static DOGE_REG_TEST_CHAIN: ChainParams = ChainParams {
     chain_name: "regtest",
     genesis_block: "3d2160a3b5dc4a9d62e7e66a295f70313ac808440ef7400d6c0772171ce973a5",
+    magic: [0xfa, 0xbf, 0xb5, 0xda],
+    default_port: 18444,
     p2pkh_address_prefix: 0x6f,       // n
     p2sh_address_prefix: 0xc4,        // 2
     pkey_prefix: 0xef,                //
@@ -47,9 +130,19 @@ pub static DOGE_REG_TEST_CHAIN: ChainParams = ChainParams {
     bip32_pubkey_prefix: 0x043587cf,  // tpub
     bip32_wif_privkey_prefix: "tprv",
     bip32_wif_pubkey_prefix: "tpub",
+    pow_limit_bits: 0x207fffff,
+    auxpow_chain_id: 0x0062,
+    strict_chain_id: true,
+    auxpow_height: 20,
+    pow_target_spacing: 1,
+    pow_target_timespan: 4 * 60 * 60,
+    digishield_height: 10,
+    pow_allow_min_difficulty_blocks: true,
+    digishield_min_difficulty_height: 0,
+    pow_no_retargeting: true,
 };
 
-pub type KeyBits = u8; // keyECPriv,keyECPub,keyBip32Priv,keyBip32Pub,dogeMainNet,dogeTestNet
+pub type KeyBits = u8; // keyECPriv,keyECPub,keyBip32Priv,keyBip32Pub,dogeMainNet,dogeTestNet,btcMainNet,dogeRegTest
 
 pub const KEY_NONE: KeyBits = 0;
 pub const KEY_ECPRIV: KeyBits = 1;
@@ -59,13 +152,17 @@ pub const KEY_BIP32_PUB: KeyBits = 8;
 pub const MAIN_NET_DOGE: KeyBits = 16;
 pub const TEST_NET_DOGE: KeyBits = 32;
 pub const MAIN_NET_BTC: KeyBits = 64;
+pub const REG_TEST_DOGE: KeyBits = 128;
 
-pub fn chain_from_key_bits(key: KeyBits) -> &'static ChainParams {
-    if (key & MAIN_NET_DOGE) != 0 {
-        return &DOGE_MAIN_NET_CHAIN;
+/// Returns the Dogecoin chain selected by the key bits, exactly one of
+/// MAIN_NET_DOGE, TEST_NET_DOGE and REG_TEST_DOGE must be set.
+pub fn chain_from_key_bits(key: KeyBits) -> Result<&'static ChainParams, String> {
+    match key & (MAIN_NET_DOGE | TEST_NET_DOGE | REG_TEST_DOGE) {
+        MAIN_NET_DOGE => Ok(&DOGE_MAIN_NET_CHAIN),
+        TEST_NET_DOGE => Ok(&DOGE_TEST_NET_CHAIN),
+        REG_TEST_DOGE => Ok(&DOGE_REG_TEST_CHAIN),
+        _ => Err(format!("unknown chain key bits: {}", key)),
     }
-
-    &DOGE_TEST_NET_CHAIN // fallback
 }
 
 pub fn key_bits_for_chain(chain: &ChainParams) -> KeyBits {
@@ -74,17 +171,77 @@ pub fn key_bits_for_chain(chain: &ChainParams) -> KeyBits {
         bits |= MAIN_NET_DOGE
     } else if chain == &DOGE_TEST_NET_CHAIN {
         bits |= TEST_NET_DOGE
+    } else if chain == &DOGE_REG_TEST_CHAIN {
+        bits |= REG_TEST_DOGE
     }
     bits
 }
 
-pub fn chain_from_wif(wif: &str) -> &'static ChainParams {
-    let wif = wif.as_bytes();
-    if wif.is_empty() {
-        return &DOGE_TEST_NET_CHAIN; // fallback
+/// Returns the chain whose WIF private key prefix matches the first byte of the
+/// decoded WIF payload.
+pub fn chain_from_wif(wif: &str) -> Result<&'static ChainParams, String> {
+    let data = base58::decode_check(wif).map_err(|_| "invalid base58 secret key".to_string())?;
+    if data.is_empty() {
+        return Err("empty WIF payload".to_string());
     }
-    match wif[0] {
-        b'D' | b'9' | b'A' | b'Q' | b'6' | b'd' => &DOGE_MAIN_NET_CHAIN,
-        _ => &DOGE_TEST_NET_CHAIN,
+    chain_from_pkey_prefix(data[0]).ok_or_else(|| format!("unknown WIF prefix: {:#04x}", data[0]))
+}
+
+pub fn chain_from_pkey_prefix(prefix: u8) -> Option<&'static ChainParams> {
+    [
+        &DOGE_MAIN_NET_CHAIN,
+        &DOGE_TEST_NET_CHAIN,
+        &DOGE_REG_TEST_CHAIN,
+    ]
+    .into_iter()
+    .find(|chain| chain.pkey_prefix == prefix)
+}
+
+/// Returns the chain for a BIP32 version prefix and whether it is a private key prefix.
+/// Testnet and regtest share the tprv/tpub prefixes, so testnet is returned for both.
+pub fn chain_from_bip32_prefix(prefix: u32) -> Option<(&'static ChainParams, bool)> {
+    [&DOGE_MAIN_NET_CHAIN, &DOGE_TEST_NET_CHAIN]
+        .into_iter()
+        .find_map(|chain| {
+            if chain.bip32_privkey_prefix == prefix {
+                Some((chain, true))
+            } else if chain.bip32_pubkey_prefix == prefix {
+                Some((chain, false))
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_bits() {
+        for (chain, bits) in [
+            (&DOGE_MAIN_NET_CHAIN, MAIN_NET_DOGE),
+            (&DOGE_TEST_NET_CHAIN, TEST_NET_DOGE),
+            (&DOGE_REG_TEST_CHAIN, REG_TEST_DOGE),
+        ] {
+            assert_eq!(key_bits_for_chain(chain), bits);
+            assert_eq!(chain_from_key_bits(bits).unwrap(), chain);
+            // key type bits are ignored
+            assert_eq!(
+                chain_from_key_bits(bits | KEY_ECPRIV | KEY_BIP32_PUB).unwrap(),
+                chain
+            );
+        }
+
+        for bits in [
+            KEY_NONE,
+            KEY_ECPRIV,
+            MAIN_NET_BTC,
+            MAIN_NET_DOGE | TEST_NET_DOGE,
+            TEST_NET_DOGE | REG_TEST_DOGE,
+            MAIN_NET_DOGE | TEST_NET_DOGE | REG_TEST_DOGE,
+        ] {
+            assert!(chain_from_key_bits(bits).is_err());
+        }
     }
 }
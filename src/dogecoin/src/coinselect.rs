@@ -0,0 +1,512 @@
+// Coin selection strategies for building Dogecoin transactions.
+// Branch and bound follows Bitcoin Core's SelectCoinsBnB,
+// random-improve follows https://iohk.io/en/blog/posts/2018/07/03/self-organisation-in-coin-selection/
+
+use bitcoin::VarInt;
+
+use crate::amount::{fee_by_size, DUST_LIMIT, MIN_FEE_RATE};
+use crate::transaction::TxOut;
+
+/// Estimated size of a signed P2PKH input with a compressed public key:
+/// outpoint (36) + script length (1) + <sig:72> <pubkey:33> (107) + sequence (4).
+pub const P2PKH_INPUT_SIZE: u64 = 148;
+/// Size of a P2PKH output: value (8) + script length (1) + script (25).
+pub const P2PKH_OUTPUT_SIZE: u64 = 34;
+
+const BNB_MAX_TRIES: usize = 100_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CoinSelection {
+    /// Searches for an input set that needs no change output, see Bitcoin Core's SelectCoinsBnB.
+    BranchAndBound,
+    /// Spends the largest coins first, minimizing the number of inputs.
+    LargestFirst,
+    /// Spends the smallest coins first, consolidating dust.
+    SmallestFirst,
+    /// Selects coins at random and improves the selection towards twice the target,
+    /// so that change outputs look like payments. The seed must come from a secure source,
+    /// e.g. `raw_rand` on the IC.
+    RandomImprove { seed: u64 },
+}
+
+/// A spendable output.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Coin {
+    pub value: u64,
+    /// The estimated size of the input spending this coin, e.g. [`P2PKH_INPUT_SIZE`].
+    pub input_size: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SelectionParams {
+    /// Total value of the recipient outputs.
+    pub target: u64,
+    pub fee_rate: u64,
+    /// Size of the transaction without inputs and change output.
+    pub base_size: u64,
+    /// Size of the change output.
+    pub change_size: u64,
+    /// Maximum number of inputs to select.
+    pub max_inputs: usize,
+    /// The fee is deducted from the recipient outputs, so the inputs only cover the target.
+    pub subtract_fee: bool,
+}
+
+impl SelectionParams {
+    /// Creates params for the recipient outputs, with a P2PKH change output.
+    pub fn new(outputs: &[TxOut], fee_rate: u64, max_inputs: usize) -> Self {
+        let outputs_size: usize = outputs.iter().map(|o| o.size()).sum();
+        SelectionParams {
+            target: outputs.iter().map(|o| o.value).sum(),
+            fee_rate,
+            // version + inputs count (up to 0xffff inputs) + outputs count + outputs + lock_time
+            base_size: (4 + 3 + VarInt::from(outputs.len() + 1).size() + outputs_size + 4) as u64,
+            change_size: P2PKH_OUTPUT_SIZE,
+            max_inputs,
+            subtract_fee: false,
+        }
+    }
+
+    fn fee_rate(&self) -> u64 {
+        self.fee_rate.max(MIN_FEE_RATE)
+    }
+
+    // the value of a coin minus the fee to spend it
+    fn effective_value(&self, coin: &Coin) -> i64 {
+        coin.value as i64 - (coin.input_size * self.fee_rate()) as i64
+    }
+
+    // the value the inputs must cover with the given fee
+    fn required(&self, fee: u64) -> u64 {
+        if self.subtract_fee {
+            self.target
+        } else {
+            self.target + fee
+        }
+    }
+}
+
+/// The recipients receive `total - fee - change`, which is less than the target
+/// only with `subtract_fee`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Selection {
+    /// Indexes of the selected coins.
+    pub selected: Vec<usize>,
+    pub total: u64,
+    pub fee: u64,
+    /// Value of the change output, 0 if no change output is needed.
+    pub change: u64,
+}
+
+/// Selects coins to pay `params.target` plus fee with the given strategy.
+pub fn select_coins(
+    coins: &[Coin],
+    params: &SelectionParams,
+    strategy: CoinSelection,
+) -> Result<Selection, String> {
+    if params.max_inputs == 0 {
+        return Err("coin selection: max_inputs must be greater than 0".to_string());
+    }
+    // coins that cost more to spend than they are worth are never selected
+    let mut candidates: Vec<usize> = (0..coins.len())
+        .filter(|&i| params.effective_value(&coins[i]) > 0)
+        .collect();
+    let changeless = strategy == CoinSelection::BranchAndBound;
+    let selected = match strategy {
+        CoinSelection::BranchAndBound => {
+            candidates.sort_by_key(|&i| std::cmp::Reverse(params.effective_value(&coins[i])));
+            branch_and_bound(coins, &candidates, params)
+        }
+        CoinSelection::LargestFirst => {
+            candidates.sort_by_key(|&i| std::cmp::Reverse(coins[i].value));
+            accumulate(coins, &candidates, params)
+        }
+        CoinSelection::SmallestFirst => {
+            candidates.sort_by_key(|&i| coins[i].value);
+            accumulate(coins, &candidates, params)
+        }
+        CoinSelection::RandomImprove { seed } => {
+            random_improve(coins, candidates, params, &mut Rng(seed))
+        }
+    };
+    match selected {
+        Some(selected) => finish(coins, selected, params, changeless),
+        None => {
+            let available: u64 = coins.iter().map(|c| c.value).sum();
+            Err(format!(
+                "coin selection: insufficient funds, expected at least: {}, available: {} in {} coins, max inputs: {}",
+                params.target,
+                available,
+                coins.len(),
+                params.max_inputs
+            ))
+        }
+    }
+}
+
+// Returns the fee and change for the selected coins, dropping a change output below the dust limit.
+// A changeless selection never gets a change output, its excess goes to the fee.
+fn finish(
+    coins: &[Coin],
+    selected: Vec<usize>,
+    params: &SelectionParams,
+    changeless: bool,
+) -> Result<Selection, String> {
+    let total: u64 = selected.iter().map(|&i| coins[i].value).sum();
+    let size = params.base_size + selected.iter().map(|&i| coins[i].input_size).sum::<u64>();
+    let fee = fee_by_size(size, params.fee_rate);
+    if total < params.required(fee) {
+        return Err(format!(
+            "coin selection: insufficient funds, expected: {}, got: {}",
+            params.required(fee),
+            total
+        ));
+    }
+    let fee_with_change = fee_by_size(size + params.change_size, params.fee_rate);
+    let change = total.saturating_sub(params.required(fee_with_change));
+    if !changeless && change >= DUST_LIMIT {
+        return Ok(Selection {
+            selected,
+            total,
+            fee: fee_with_change,
+            change,
+        });
+    }
+    Ok(Selection {
+        selected,
+        total,
+        fee: fee.max(total - params.target),
+        change: 0,
+    })
+}
+
+fn is_enough(coins: &[Coin], selected: &[usize], params: &SelectionParams) -> bool {
+    let total: u64 = selected.iter().map(|&i| coins[i].value).sum();
+    let size = params.base_size + selected.iter().map(|&i| coins[i].input_size).sum::<u64>();
+    total >= params.required(fee_by_size(size, params.fee_rate))
+}
+
+// Accumulates the candidates in order until the target is met.
+// When max_inputs is reached, the earliest selected coins are dropped,
+// so smallest-first finds the smallest window of coins that covers the target.
+fn accumulate(
+    coins: &[Coin],
+    candidates: &[usize],
+    params: &SelectionParams,
+) -> Option<Vec<usize>> {
+    let mut selected: Vec<usize> = Vec::new();
+    for &i in candidates {
+        selected.push(i);
+        if selected.len() > params.max_inputs {
+            selected.remove(0);
+        }
+        if is_enough(coins, &selected, params) {
+            return Some(selected);
+        }
+    }
+    None
+}
+
+// Depth-first search over the candidates sorted by descending effective value,
+// for a selection whose effective value is in [target, target + cost_of_change].
+fn branch_and_bound(
+    coins: &[Coin],
+    candidates: &[usize],
+    params: &SelectionParams,
+) -> Option<Vec<usize>> {
+    let fee_rate = params.fee_rate();
+    // the recipients pay the fee with subtract_fee, the inputs only cover the target
+    let values: Vec<i64> = candidates
+        .iter()
+        .map(|&i| {
+            if params.subtract_fee {
+                coins[i].value as i64
+            } else {
+                params.effective_value(&coins[i])
+            }
+        })
+        .collect();
+    // MIN_FEE must be paid even by tiny transactions
+    let target = params.required(fee_by_size(params.base_size, params.fee_rate)) as i64;
+    // any excess below this can not create a change output, and will go to fee
+    let cost_of_change = if params.subtract_fee {
+        DUST_LIMIT as i64
+    } else {
+        (params.change_size * fee_rate + DUST_LIMIT) as i64
+    };
+
+    let mut available: i64 = values.iter().sum();
+    if available < target {
+        return None;
+    }
+
+    let mut current: Vec<usize> = Vec::new(); // positions in candidates
+    let mut current_value: i64 = 0;
+    let mut best: Option<(i64, Vec<usize>)> = None;
+    let mut pos = 0;
+    for _ in 0..BNB_MAX_TRIES {
+        let mut backtrack = false;
+        if current_value + available < target
+            || current_value > target + cost_of_change
+            || (current.len() >= params.max_inputs && current_value < target)
+        {
+            backtrack = true;
+        } else if current_value >= target {
+            let excess = current_value - target;
+            if best.as_ref().is_none_or(|(waste, _)| excess < *waste) {
+                best = Some((excess, current.clone()));
+                if excess == 0 {
+                    break;
+                }
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            // the whole tree has been searched
+            let last = match current.pop() {
+                Some(last) => last,
+                None => break,
+            };
+            // add the omitted coins back before traversing the omission branch of the last included coin
+            for v in &values[last + 1..pos] {
+                available += v;
+            }
+            current_value -= values[last];
+            pos = last + 1;
+        } else {
+            // moving forwards, continuing down this branch
+            available -= values[pos];
+            current.push(pos);
+            current_value += values[pos];
+            pos += 1;
+        }
+    }
+
+    best.map(|(_, positions)| positions.into_iter().map(|p| candidates[p]).collect())
+}
+
+// Selects random coins until the target is met, then keeps adding random coins
+// while they move the total closer to twice the target without exceeding three times it.
+fn random_improve(
+    coins: &[Coin],
+    mut candidates: Vec<usize>,
+    params: &SelectionParams,
+    rng: &mut Rng,
+) -> Option<Vec<usize>> {
+    let mut selected: Vec<usize> = Vec::new();
+    while !is_enough(coins, &selected, params) {
+        if candidates.is_empty() || selected.len() >= params.max_inputs {
+            // fall back to the largest coins
+            let mut all: Vec<usize> = (0..coins.len())
+                .filter(|&i| params.effective_value(&coins[i]) > 0)
+                .collect();
+            all.sort_by_key(|&i| std::cmp::Reverse(coins[i].value));
+            return accumulate(coins, &all, params);
+        }
+        let i = candidates.swap_remove(rng.below(candidates.len()));
+        selected.push(i);
+    }
+
+    let ideal = params.target.saturating_mul(2);
+    let max = params.target.saturating_mul(3);
+    let mut total: u64 = selected.iter().map(|&i| coins[i].value).sum();
+    while !candidates.is_empty() && selected.len() < params.max_inputs {
+        let i = candidates.swap_remove(rng.below(candidates.len()));
+        let next = total + coins[i].value;
+        if next > max || next.abs_diff(ideal) >= total.abs_diff(ideal) {
+            break;
+        }
+        selected.push(i);
+        total = next;
+    }
+    Some(selected)
+}
+
+// SplitMix64, enough to shuffle coins when seeded from a secure source.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::{DOGE, MIN_FEE};
+
+    fn coins(values: &[u64]) -> Vec<Coin> {
+        values
+            .iter()
+            .map(|&value| Coin {
+                value,
+                input_size: P2PKH_INPUT_SIZE,
+            })
+            .collect()
+    }
+
+    fn params(target: u64) -> SelectionParams {
+        SelectionParams {
+            target,
+            fee_rate: MIN_FEE_RATE,
+            base_size: 10 + P2PKH_OUTPUT_SIZE,
+            change_size: P2PKH_OUTPUT_SIZE,
+            max_inputs: 100,
+            subtract_fee: false,
+        }
+    }
+
+    fn values(coins: &[Coin], s: &Selection) -> Vec<u64> {
+        let mut v: Vec<u64> = s.selected.iter().map(|&i| coins[i].value).collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_select_coins() {
+        let pool = coins(&[DOGE, 5 * DOGE, 2 * DOGE, 10 * DOGE, 3 * DOGE, 1000]);
+
+        let s = select_coins(&pool, &params(4 * DOGE), CoinSelection::LargestFirst).unwrap();
+        assert_eq!(values(&pool, &s), vec![10 * DOGE]);
+        assert_eq!(s.fee, fee_by_size(10 + 34 + 148 + 34, MIN_FEE_RATE));
+        assert_eq!(s.total, s.fee + s.change + 4 * DOGE);
+
+        let s = select_coins(&pool, &params(4 * DOGE), CoinSelection::SmallestFirst).unwrap();
+        assert_eq!(values(&pool, &s), vec![DOGE, 2 * DOGE, 3 * DOGE]);
+        assert!(s.change > DUST_LIMIT);
+
+        // the window slides over the smallest coins when max_inputs is reached
+        let mut p = params(6 * DOGE);
+        p.max_inputs = 2;
+        let s = select_coins(&pool, &p, CoinSelection::SmallestFirst).unwrap();
+        assert_eq!(values(&pool, &s), vec![3 * DOGE, 5 * DOGE]);
+        p.max_inputs = 1;
+        p.target = 11 * DOGE;
+        assert!(select_coins(&pool, &p, CoinSelection::SmallestFirst).is_err());
+
+        // the coin of 1000 is not worth spending
+        let s = select_coins(
+            &pool,
+            &params(21 * DOGE - MIN_FEE + 500),
+            CoinSelection::LargestFirst,
+        );
+        assert!(s.is_err());
+        assert!(select_coins(&pool, &params(DOGE), CoinSelection::LargestFirst).is_ok());
+        assert!(select_coins(&[], &params(DOGE), CoinSelection::LargestFirst).is_err());
+    }
+
+    #[test]
+    fn test_branch_and_bound() {
+        let pool = coins(&[DOGE, 5 * DOGE, 2 * DOGE, 10 * DOGE, 3 * DOGE]);
+        let p = params(0);
+        let eff = |v: u64| v - P2PKH_INPUT_SIZE * MIN_FEE_RATE;
+        let base_fee = fee_by_size(p.base_size, p.fee_rate);
+
+        // exact match for 2 + 3 DOGE without change
+        let mut p = params(eff(2 * DOGE) + eff(3 * DOGE) - base_fee);
+        let s = select_coins(&pool, &p, CoinSelection::BranchAndBound).unwrap();
+        assert_eq!(values(&pool, &s), vec![2 * DOGE, 3 * DOGE]);
+        assert_eq!(s.change, 0);
+        assert_eq!(s.total, p.target + s.fee);
+
+        // small excess goes to fee
+        p.target -= DUST_LIMIT / 2;
+        let s = select_coins(&pool, &p, CoinSelection::BranchAndBound).unwrap();
+        assert_eq!(values(&pool, &s), vec![2 * DOGE, 3 * DOGE]);
+        assert_eq!(s.change, 0);
+
+        let s = select_coins(
+            &pool,
+            &params(eff(DOGE) + eff(5 * DOGE) + eff(10 * DOGE) - base_fee),
+            CoinSelection::BranchAndBound,
+        )
+        .unwrap();
+        assert_eq!(values(&pool, &s), vec![DOGE, 5 * DOGE, 10 * DOGE]);
+
+        let mut p = params(eff(DOGE) + eff(5 * DOGE) + eff(10 * DOGE) - base_fee);
+        p.max_inputs = 2;
+        assert!(select_coins(&pool, &p, CoinSelection::BranchAndBound).is_err());
+
+        // no changeless solution
+        assert!(select_coins(
+            &pool,
+            &params(4 * DOGE + DOGE / 2),
+            CoinSelection::BranchAndBound
+        )
+        .is_err());
+
+        // the excess covers a change output only because MIN_FEE was paid upfront,
+        // it still goes to the fee
+        let pool = coins(&[10 * DOGE]);
+        let p = params(10 * DOGE - MIN_FEE - DUST_LIMIT - 100_000);
+        let s = select_coins(&pool, &p, CoinSelection::BranchAndBound).unwrap();
+        assert_eq!(s.change, 0);
+        assert_eq!(s.fee, s.total - p.target);
+    }
+
+    #[test]
+    fn test_subtract_fee() {
+        let pool = coins(&[DOGE, 2 * DOGE, 3 * DOGE]);
+        let mut p = params(3 * DOGE);
+        let s = select_coins(&pool, &p, CoinSelection::SmallestFirst).unwrap();
+        assert_eq!(values(&pool, &s), vec![DOGE, 2 * DOGE, 3 * DOGE]);
+
+        p.subtract_fee = true;
+        let s = select_coins(&pool, &p, CoinSelection::SmallestFirst).unwrap();
+        assert_eq!(values(&pool, &s), vec![DOGE, 2 * DOGE]);
+        assert_eq!(s.change, 0);
+        assert_eq!(s.fee, MIN_FEE);
+        // the recipient pays the fee
+        assert_eq!(s.total - s.fee - s.change, 3 * DOGE - MIN_FEE);
+
+        let s = select_coins(&pool, &p, CoinSelection::BranchAndBound).unwrap();
+        assert_eq!(s.total, 3 * DOGE);
+        assert_eq!(s.change, 0);
+
+        p.target = 4 * DOGE;
+        let s = select_coins(&pool, &p, CoinSelection::LargestFirst).unwrap();
+        assert_eq!(values(&pool, &s), vec![2 * DOGE, 3 * DOGE]);
+        assert_eq!(s.change, DOGE);
+        assert_eq!(
+            s.fee,
+            fee_by_size(
+                p.base_size + 2 * P2PKH_INPUT_SIZE + p.change_size,
+                p.fee_rate
+            )
+        );
+    }
+
+    #[test]
+    fn test_random_improve() {
+        let pool = coins(&(1..=50).map(|i| i * DOGE).collect::<Vec<_>>());
+        let mut selections = Vec::new();
+        for seed in 0..10 {
+            let p = params(20 * DOGE);
+            let s = select_coins(&pool, &p, CoinSelection::RandomImprove { seed }).unwrap();
+            assert!(s.total >= p.target + s.fee);
+            assert!(s.total <= 3 * p.target || s.selected.len() == 1);
+            assert_eq!(s.total, p.target + s.fee + s.change);
+            selections.push(values(&pool, &s));
+        }
+        selections.dedup();
+        assert!(selections.len() > 1);
+
+        let mut p = params(50 * DOGE);
+        p.max_inputs = 1;
+        let s = select_coins(&pool, &p, CoinSelection::RandomImprove { seed: 1 });
+        assert!(s.is_err());
+        p.target = 49 * DOGE;
+        let s = select_coins(&pool, &p, CoinSelection::RandomImprove { seed: 1 }).unwrap();
+        assert_eq!(values(&pool, &s), vec![50 * DOGE]);
+    }
+}
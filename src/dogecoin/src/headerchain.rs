@@ -0,0 +1,468 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::block::{BlockHash, BlockHeader, MerkleTx};
+use crate::chainparams::ChainParams;
+use crate::pow::{next_work_required, U256};
+
+/// The number of blocks used to compute the median time past.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HeaderEntry {
+    pub header: BlockHeader,
+    pub hash: BlockHash,
+    pub height: u32,
+    /// Cumulative work of the chain up to and including this header.
+    pub chainwork: U256,
+}
+
+/// Changes to the best chain caused by accepting a header.
+/// `disconnected` runs from the old tip down to the fork point,
+/// `connected` runs from the fork point up to the new tip.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ChainUpdate {
+    pub disconnected: Vec<(u32, BlockHash)>,
+    pub connected: Vec<(u32, BlockHash)>,
+}
+
+impl ChainUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.disconnected.is_empty() && self.connected.is_empty()
+    }
+
+    pub fn is_reorg(&self) -> bool {
+        !self.disconnected.is_empty()
+    }
+}
+
+/// A header-only light client that follows the chain with the most cumulative work.
+/// It keeps a window of headers below the best tip, so competing branches that fork
+/// deeper than the window are forgotten.
+pub struct HeaderChain {
+    chain: &'static ChainParams,
+    window: u32,
+    headers: HashMap<BlockHash, HeaderEntry>,
+    tips: HashSet<BlockHash>,
+    best_chain: BTreeMap<u32, BlockHash>,
+    best: BlockHash,
+}
+
+impl HeaderChain {
+    /// Creates a header chain from trusted consecutive `headers`, the first one at `height`.
+    /// `chainwork` is the cumulative work up to and including the last header.
+    /// Enough headers must be given to cover the retarget window of the next header,
+    /// and `window` should be at least that large.
+    pub fn new(
+        chain: &'static ChainParams,
+        height: u32,
+        headers: Vec<BlockHeader>,
+        chainwork: U256,
+        window: u32,
+    ) -> Result<Self, String> {
+        if headers.is_empty() {
+            return Err("no trusted headers".to_string());
+        }
+        let mut hc = HeaderChain {
+            chain,
+            window,
+            headers: HashMap::new(),
+            tips: HashSet::new(),
+            best_chain: BTreeMap::new(),
+            best: BlockHash::default(),
+        };
+
+        let last = headers.len() - 1;
+        let (mut work, underflow) = chainwork.overflowing_sub(
+            &headers
+                .iter()
+                .skip(1)
+                .map(|h| h.work())
+                .fold(U256::ZERO, |a, b| a + b),
+        );
+        if underflow || work < headers[0].work() {
+            return Err("chainwork is less than the work of the trusted headers".to_string());
+        }
+        let mut prev_hash: Option<BlockHash> = None;
+        for (i, header) in headers.into_iter().enumerate() {
+            if i > 0 {
+                work += header.work();
+            }
+            let hash = header.block_hash();
+            if let Some(prev) = prev_hash {
+                if header.prev_blockhash != prev {
+                    return Err(format!("trusted header {} does not connect", hash));
+                }
+            }
+            let height = height + i as u32;
+            hc.best_chain.insert(height, hash);
+            hc.headers.insert(
+                hash,
+                HeaderEntry {
+                    header,
+                    hash,
+                    height,
+                    chainwork: work,
+                },
+            );
+            prev_hash = Some(hash);
+            if i == last {
+                hc.best = hash;
+                hc.tips.insert(hash);
+            }
+        }
+        Ok(hc)
+    }
+
+    pub fn chain(&self) -> &'static ChainParams {
+        self.chain
+    }
+
+    /// Returns the tip of the best chain.
+    pub fn tip(&self) -> &HeaderEntry {
+        &self.headers[&self.best]
+    }
+
+    /// Returns all known tips, the best one first, then by descending cumulative work.
+    pub fn tips(&self) -> Vec<&HeaderEntry> {
+        let mut tips: Vec<&HeaderEntry> = self.tips.iter().map(|h| &self.headers[h]).collect();
+        tips.sort_by(|a, b| {
+            (b.hash == self.best)
+                .cmp(&(a.hash == self.best))
+                .then_with(|| b.chainwork.cmp(&a.chainwork))
+        });
+        tips
+    }
+
+    pub fn get(&self, hash: &BlockHash) -> Option<&HeaderEntry> {
+        self.headers.get(hash)
+    }
+
+    /// Returns the header at `height` on the best chain.
+    pub fn get_by_height(&self, height: u32) -> Option<&HeaderEntry> {
+        self.best_chain
+            .get(&height)
+            .and_then(|hash| self.headers.get(hash))
+    }
+
+    pub fn contains(&self, hash: &BlockHash) -> bool {
+        self.headers.contains_key(hash)
+    }
+
+    pub fn is_in_best_chain(&self, hash: &BlockHash) -> bool {
+        match self.headers.get(hash) {
+            Some(entry) => self.best_chain.get(&entry.height) == Some(hash),
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// Returns up to `count` headers ending with `hash`, oldest first.
+    pub fn ancestors(&self, hash: &BlockHash, count: usize) -> Vec<BlockHeader> {
+        let mut res = Vec::with_capacity(count);
+        let mut cur = self.headers.get(hash);
+        while let Some(entry) = cur {
+            if res.len() >= count {
+                break;
+            }
+            res.push(entry.header.clone());
+            cur = self.headers.get(&entry.header.prev_blockhash);
+        }
+        res.reverse();
+        res
+    }
+
+    /// Returns the median time of the last `MEDIAN_TIME_SPAN` headers ending with `hash`.
+    pub fn median_time_past(&self, hash: &BlockHash) -> u32 {
+        let mut times: Vec<u32> = self
+            .ancestors(hash, MEDIAN_TIME_SPAN)
+            .iter()
+            .map(|h| h.time)
+            .collect();
+        times.sort_unstable();
+        times.get(times.len() / 2).copied().unwrap_or(0)
+    }
+
+    /// Validates and stores a header, with its AuxPoW proof for merge-mined headers,
+    /// and returns the resulting changes to the best chain.
+    /// Accepting a known header is a no-op.
+    pub fn accept_header(
+        &mut self,
+        header: BlockHeader,
+        auxpow: Option<&MerkleTx>,
+    ) -> Result<ChainUpdate, String> {
+        let hash = header.block_hash();
+        if self.headers.contains_key(&hash) {
+            return Ok(ChainUpdate::default());
+        }
+        let parent = self.headers.get(&header.prev_blockhash).ok_or_else(|| {
+            format!(
+                "header {} has unknown parent {}",
+                hash, header.prev_blockhash
+            )
+        })?;
+        let height = parent.height + 1;
+
+        header.validate_pow(auxpow, self.chain)?;
+
+        if header.is_legacy() && !self.chain.allow_legacy_blocks(height) {
+            return Err(format!("legacy block {} after auxpow start height", hash));
+        }
+
+        let mtp = self.median_time_past(&parent.hash);
+        if header.time <= mtp {
+            return Err(format!(
+                "header {} time {} is not after median time past {}",
+                hash, header.time, mtp
+            ));
+        }
+
+        let interval = self.chain.difficulty_adjustment_interval(parent.height) as usize;
+        let ancestors = self.ancestors(&parent.hash, interval + 1);
+        let bits = next_work_required(self.chain, parent.height, &ancestors, header.time)?;
+        if header.bits != bits {
+            return Err(format!(
+                "header {} has incorrect bits {:#010x}, expected {:#010x}",
+                hash, header.bits, bits
+            ));
+        }
+
+        let entry = HeaderEntry {
+            chainwork: parent.chainwork + header.work(),
+            header,
+            hash,
+            height,
+        };
+        self.tips.remove(&entry.header.prev_blockhash);
+        self.tips.insert(hash);
+        let better = entry.chainwork > self.tip().chainwork;
+        self.headers.insert(hash, entry);
+
+        if !better {
+            return Ok(ChainUpdate::default());
+        }
+        let update = self.set_best(hash);
+        self.prune();
+        Ok(update)
+    }
+
+    fn set_best(&mut self, hash: BlockHash) -> ChainUpdate {
+        let mut update = ChainUpdate::default();
+        let mut connected = self.headers.get(&hash);
+        let mut disconnected = self.headers.get(&self.best);
+        while let (Some(c), Some(d)) = (connected, disconnected) {
+            if c.hash == d.hash {
+                break;
+            }
+            if c.height >= d.height {
+                update.connected.push((c.height, c.hash));
+                connected = self.headers.get(&c.header.prev_blockhash);
+            }
+            if d.height > c.height {
+                update.disconnected.push((d.height, d.hash));
+                disconnected = self.headers.get(&d.header.prev_blockhash);
+            }
+        }
+        update.connected.reverse();
+
+        for (height, _) in &update.disconnected {
+            self.best_chain.remove(height);
+        }
+        for (height, hash) in &update.connected {
+            self.best_chain.insert(*height, *hash);
+        }
+        self.best = hash;
+        update
+    }
+
+    // drops headers that fall out of the window below the best tip
+    fn prune(&mut self) {
+        let min_height = self.tip().height.saturating_sub(self.window);
+        self.headers.retain(|_, entry| entry.height >= min_height);
+        self.best_chain = self.best_chain.split_off(&min_height);
+        let headers = &self.headers;
+        self.tips.retain(|hash| headers.contains_key(hash));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::TxMerkleNode;
+    use crate::chainparams::DOGE_REG_TEST_CHAIN;
+    use bitcoin::hashes::Hash;
+    use std::str::FromStr;
+
+    fn regtest_genesis() -> BlockHeader {
+        BlockHeader {
+            version: 1,
+            prev_blockhash: BlockHash::default(),
+            merkle_root: TxMerkleNode::from_str(
+                "5b2a3f53f605d62c53e62932dac6925e3d74afa5a4b459745c36d42d0ed26a69",
+            )
+            .unwrap(),
+            time: 1296688602,
+            bits: 0x207fffff,
+            nonce: 2,
+        }
+    }
+
+    fn mine(prev: &BlockHeader, time: u32, bits: u32, tag: u8) -> BlockHeader {
+        let mut header = BlockHeader {
+            version: 0x00620004,
+            prev_blockhash: prev.block_hash(),
+            merkle_root: TxMerkleNode::from_byte_array([tag; 32]),
+            time,
+            bits,
+            nonce: 0,
+        };
+        while header.validate_pow(None, &DOGE_REG_TEST_CHAIN).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    #[test]
+    fn test_header_chain() {
+        let genesis = regtest_genesis();
+        assert_eq!(
+            genesis.block_hash().to_string(),
+            DOGE_REG_TEST_CHAIN.genesis_block
+        );
+        let mut hc = HeaderChain::new(
+            &DOGE_REG_TEST_CHAIN,
+            0,
+            vec![genesis.clone()],
+            genesis.work(),
+            100,
+        )
+        .unwrap();
+        assert_eq!(hc.tip().height, 0);
+        let child = mine(&genesis, genesis.time + 1, 0x207fffff, 1);
+        assert!(HeaderChain::new(
+            &DOGE_REG_TEST_CHAIN,
+            0,
+            vec![genesis.clone(), child],
+            genesis.work(),
+            100,
+        )
+        .is_err());
+
+        let mut a = vec![genesis.clone()];
+        for i in 1..=5 {
+            let h = mine(a.last().unwrap(), genesis.time + i, 0x207fffff, 1);
+            let update = hc.accept_header(h.clone(), None).unwrap();
+            assert_eq!(update.connected, vec![(i, h.block_hash())]);
+            assert!(!update.is_reorg());
+            a.push(h);
+        }
+        assert_eq!(hc.tip().hash, a[5].block_hash());
+        assert_eq!(hc.tip().chainwork, genesis.work().mul_u64(6));
+        assert!(hc.accept_header(a[3].clone(), None).unwrap().is_empty());
+
+        // competing branch from a[2]
+        let mut b = vec![a[2].clone()];
+        for i in 3..=5 {
+            let h = mine(b.last().unwrap(), genesis.time + i, 0x207fffff, 2);
+            assert!(hc.accept_header(h.clone(), None).unwrap().is_empty());
+            b.push(h);
+        }
+        assert_eq!(hc.tips().len(), 2);
+        assert_eq!(hc.tip().hash, a[5].block_hash());
+
+        let h = mine(b.last().unwrap(), genesis.time + 6, 0x207fffff, 2);
+        let update = hc.accept_header(h.clone(), None).unwrap();
+        b.push(h);
+        assert!(update.is_reorg());
+        assert_eq!(
+            update.disconnected,
+            vec![
+                (5, a[5].block_hash()),
+                (4, a[4].block_hash()),
+                (3, a[3].block_hash())
+            ]
+        );
+        assert_eq!(
+            update.connected,
+            (3..=6)
+                .map(|i| (i, b[i as usize - 2].block_hash()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(hc.tip().hash, b[4].block_hash());
+        assert_eq!(hc.get_by_height(4).unwrap().hash, b[2].block_hash());
+        assert!(hc.is_in_best_chain(&a[2].block_hash()));
+        assert!(!hc.is_in_best_chain(&a[4].block_hash()));
+        let tips = hc.tips();
+        assert_eq!(tips[0].hash, b[4].block_hash());
+        assert_eq!(tips[1].hash, a[5].block_hash());
+    }
+
+    #[test]
+    fn test_header_chain_rejects() {
+        let genesis = regtest_genesis();
+        let mut hc = HeaderChain::new(
+            &DOGE_REG_TEST_CHAIN,
+            0,
+            vec![genesis.clone()],
+            genesis.work(),
+            100,
+        )
+        .unwrap();
+
+        let orphan = mine(&genesis, genesis.time + 1, 0x207fffff, 1);
+        let orphan = mine(&orphan, genesis.time + 2, 0x207fffff, 1);
+        assert!(hc
+            .accept_header(orphan, None)
+            .unwrap_err()
+            .contains("unknown parent"));
+
+        let h = mine(&genesis, genesis.time + 1, 0x207ffffe, 1);
+        assert!(hc
+            .accept_header(h, None)
+            .unwrap_err()
+            .contains("incorrect bits"));
+
+        let h = mine(&genesis, genesis.time, 0x207fffff, 1);
+        assert!(hc
+            .accept_header(h, None)
+            .unwrap_err()
+            .contains("median time past"));
+
+        let mut h = mine(&genesis, genesis.time + 1, 0x207fffff, 1);
+        while h.validate_pow(None, &DOGE_REG_TEST_CHAIN).is_ok() {
+            h.nonce += 1;
+        }
+        assert!(hc.accept_header(h, None).is_err());
+        assert_eq!(hc.len(), 1);
+    }
+
+    #[test]
+    fn test_header_chain_prune() {
+        let genesis = regtest_genesis();
+        let mut hc = HeaderChain::new(
+            &DOGE_REG_TEST_CHAIN,
+            0,
+            vec![genesis.clone()],
+            genesis.work(),
+            3,
+        )
+        .unwrap();
+        let mut prev = genesis.clone();
+        // blocks more than 2 * spacing apart are min-difficulty blocks, which
+        // don't need the pruned ancestors to compute the next bits
+        for i in 1..=6 {
+            prev = mine(&prev, genesis.time + 3 * i, 0x207fffff, 1);
+            hc.accept_header(prev.clone(), None).unwrap();
+        }
+        assert_eq!(hc.len(), 4);
+        assert!(hc.get_by_height(2).is_none());
+        assert_eq!(hc.get_by_height(3).unwrap().height, 3);
+        assert_eq!(hc.tip().height, 6);
+    }
+}
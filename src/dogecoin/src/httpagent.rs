@@ -0,0 +1,327 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as base64_std, Engine};
+use reqwest::{header, Client, ClientBuilder, StatusCode, Url};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::err_string;
+use crate::jsonrpc::{JsonRPCAgent, APP_AGENT};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RPCAuth {
+    #[default]
+    None,
+    /// rpcuser/rpcpassword
+    Basic { user: String, password: String },
+    /// Path of the node's `.cookie` file, re-read on every request because the
+    /// node writes a new one when it restarts.
+    Cookie(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpRPCConfig {
+    pub url: String,
+    pub auth: RPCAuth,
+    /// Sent as `proxy-authorization: Bearer <token>` to the Idempotent Proxy.
+    pub proxy_token: Option<String>,
+    pub connect_timeout: Duration,
+    pub timeout: Duration,
+    /// Retries after connection errors, timeouts and 401/429/502/503/504 responses.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every following one.
+    pub retry_backoff: Duration,
+}
+
+impl Default for HttpRPCConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:22555".to_string(),
+            auth: RPCAuth::None,
+            proxy_token: None,
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Native JSON-RPC agent for dogecoind or an Idempotent Proxy in front of it.
+/// The idempotency key of each call is sent as the `idempotency-key` header.
+pub struct HttpRPCAgent {
+    client: Client,
+    url: Url,
+    auth: RPCAuth,
+    max_retries: u32,
+    retry_backoff: Duration,
+}
+
+impl HttpRPCAgent {
+    pub fn new(config: HttpRPCConfig) -> Result<Self, String> {
+        let url = Url::parse(&config.url).map_err(err_string)?;
+        let mut common_headers = header::HeaderMap::with_capacity(3);
+        common_headers.insert(
+            header::ACCEPT,
+            header::HeaderValue::from_static("application/json"),
+        );
+        common_headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        if let Some(token) = &config.proxy_token {
+            common_headers.insert(
+                header::PROXY_AUTHORIZATION,
+                format!("Bearer {}", token).parse().map_err(err_string)?,
+            );
+        }
+
+        let client = ClientBuilder::new()
+            .use_rustls_tls()
+            .no_proxy()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout)
+            .user_agent(APP_AGENT)
+            .default_headers(common_headers)
+            .gzip(true)
+            .build()
+            .map_err(err_string)?;
+
+        Ok(Self {
+            client,
+            url,
+            auth: config.auth,
+            max_retries: config.max_retries,
+            retry_backoff: config.retry_backoff,
+        })
+    }
+
+    /// Builds an agent from the RPC_URL, RPC_USER, RPC_PASSWORD and RPC_COOKIE_FILE
+    /// environment variables, the cookie file is used when no user is set.
+    pub fn from_env() -> Result<Self, String> {
+        let url = std::env::var("RPC_URL").map_err(|_| "RPC_URL not set".to_string())?;
+        let user = std::env::var("RPC_USER").unwrap_or_default();
+        let password = std::env::var("RPC_PASSWORD").unwrap_or_default();
+        let cookie = std::env::var("RPC_COOKIE_FILE").unwrap_or_default();
+        let auth = if !user.is_empty() {
+            RPCAuth::Basic { user, password }
+        } else if !cookie.is_empty() {
+            RPCAuth::Cookie(cookie.into())
+        } else {
+            RPCAuth::None
+        };
+        Self::new(HttpRPCConfig {
+            url,
+            auth,
+            ..Default::default()
+        })
+    }
+
+    fn authorization(&self) -> Result<Option<String>, String> {
+        let credentials = match &self.auth {
+            RPCAuth::None => return Ok(None),
+            RPCAuth::Basic { user, password } => format!("{}:{}", user, password),
+            RPCAuth::Cookie(path) => std::fs::read_to_string(path)
+                .map_err(|err| format!("failed to read cookie file {:?}: {}", path, err))?
+                .trim()
+                .to_string(),
+        };
+        Ok(Some(format!("Basic {}", base64_std.encode(credentials))))
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.retry_backoff.saturating_mul(1 << attempt.min(16))
+    }
+
+    // returns the error and whether the request can be retried
+    async fn send(
+        &self,
+        idempotency_key: &str,
+        body: Vec<u8>,
+    ) -> Result<bytes::Bytes, (String, bool)> {
+        let mut req = self.client.post(self.url.clone()).body(body);
+        if !idempotency_key.is_empty() {
+            req = req.header("idempotency-key", idempotency_key);
+        }
+        if let Some(auth) = self.authorization().map_err(|err| (err, false))? {
+            req = req.header(header::AUTHORIZATION, auth);
+        }
+
+        let res = req.send().await.map_err(|err| {
+            let retryable = err.is_timeout() || err.is_connect();
+            (err.to_string(), retryable)
+        })?;
+        let status = res.status();
+        let is_json = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json"));
+        // dogecoind answers RPC errors with 500 or 404 and a JSON-RPC error body,
+        // which DogecoinRPC::call turns into RpcError::Node.
+        if status.is_success()
+            || (is_json
+                && (status == StatusCode::INTERNAL_SERVER_ERROR || status == StatusCode::NOT_FOUND))
+        {
+            return res.bytes().await.map_err(|err| (err.to_string(), true));
+        }
+
+        let retryable = is_retryable_status(status, &self.auth);
+        Err((
+            format!(
+                "failed to request url: {}, idempotency-key: {}, status: {}, body: {}",
+                self.url,
+                idempotency_key,
+                status,
+                res.text().await.unwrap_or_default(),
+            ),
+            retryable,
+        ))
+    }
+}
+
+fn is_retryable_status(status: StatusCode, auth: &RPCAuth) -> bool {
+    match status {
+        // the node may have restarted and written a new cookie
+        StatusCode::UNAUTHORIZED => matches!(auth, RPCAuth::Cookie(_)),
+        StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => true,
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl JsonRPCAgent for &HttpRPCAgent {
+    async fn post(&self, idempotency_key: String, body: Vec<u8>) -> Result<bytes::Bytes, String> {
+        let mut attempt = 0;
+        loop {
+            match self.send(&idempotency_key, body.clone()).await {
+                Ok(data) => return Ok(data),
+                Err((_, true)) if attempt < self.max_retries => {
+                    tokio::time::sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err((err, _)) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::{DogecoinRPC, RpcError, RPC_INVALID_PARAMETER};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Serves the given responses in order, one per connection, and returns the
+    // received requests.
+    async fn mock_server(
+        responses: Vec<(u16, &'static str)>,
+    ) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 8192];
+                let n = stream.read(&mut buf).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+                let res = format!(
+                    "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(res.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_retries_and_headers() {
+        let (url, server) = mock_server(vec![
+            (503, "work queue depth exceeded"),
+            (200, r#"{"result":null,"error":null,"id":1}"#),
+        ])
+        .await;
+        let agent = HttpRPCAgent::new(HttpRPCConfig {
+            url,
+            auth: RPCAuth::Basic {
+                user: "user".to_string(),
+                password: "pass".to_string(),
+            },
+            retry_backoff: Duration::from_millis(10),
+            ..Default::default()
+        })
+        .unwrap();
+        DogecoinRPC::ping(&agent, "ping-1".to_string())
+            .await
+            .unwrap();
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        for req in requests {
+            assert!(req.contains("idempotency-key: ping-1"));
+            assert!(req.contains("authorization: basic dxnlcjpwyxnz"));
+            assert!(req.contains("accept-encoding: gzip"));
+        }
+
+        // RPC errors are returned to DogecoinRPC without retrying
+        let (url, server) = mock_server(vec![(
+            500,
+            r#"{"result":null,"error":{"code":-8,"message":"Block height out of range"},"id":1}"#,
+        )])
+        .await;
+        let agent = HttpRPCAgent::new(HttpRPCConfig {
+            url,
+            ..Default::default()
+        })
+        .unwrap();
+        let err = DogecoinRPC::get_blockhash(&agent, "".to_string(), u64::MAX)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            RpcError::Node {
+                code: RPC_INVALID_PARAMETER,
+                message: "Block height out of range".to_string()
+            }
+        );
+        let requests = server.await.unwrap();
+        assert!(!requests[0].contains("idempotency-key"));
+        assert!(!requests[0].contains("authorization"));
+    }
+
+    #[test]
+    fn test_cookie_auth() {
+        let path = std::env::temp_dir().join(format!("dogecoin-{}.cookie", std::process::id()));
+        std::fs::write(&path, "__cookie__:abc\n").unwrap();
+        let agent = HttpRPCAgent::new(HttpRPCConfig {
+            auth: RPCAuth::Cookie(path.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            agent.authorization().unwrap().unwrap(),
+            format!("Basic {}", base64_std.encode("__cookie__:abc"))
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(agent.authorization().is_err());
+
+        assert!(is_retryable_status(StatusCode::UNAUTHORIZED, &agent.auth));
+        assert!(!is_retryable_status(
+            StatusCode::UNAUTHORIZED,
+            &RPCAuth::None
+        ));
+        assert!(!is_retryable_status(
+            StatusCode::BAD_REQUEST,
+            &RPCAuth::None
+        ));
+        assert_eq!(agent.backoff(0), Duration::from_millis(500));
+        assert_eq!(agent.backoff(2), Duration::from_millis(2000));
+    }
+}
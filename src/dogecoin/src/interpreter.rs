@@ -0,0 +1,1203 @@
+// https://github.com/dogecoin/dogecoin/blob/master/src/script/interpreter.cpp
+
+use bitcoin::hashes::{hash160, ripemd160, sha1, sha256, sha256d, Hash};
+use bitcoin::secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, VerifyOnly};
+
+use crate::opcodes::*;
+use crate::script::{get_op, Script};
+use crate::sighash::SighashCache;
+use crate::transaction::{Transaction, TxIn};
+
+/// Script verification flags, mirroring Dogecoin Core's SCRIPT_VERIFY_*.
+pub type ScriptFlags = u32;
+
+pub const SCRIPT_VERIFY_NONE: ScriptFlags = 0;
+/// Evaluate P2SH subscripts (BIP16).
+pub const SCRIPT_VERIFY_P2SH: ScriptFlags = 1 << 0;
+/// Enforce strict signature and public key encodings, and defined sighash types.
+pub const SCRIPT_VERIFY_STRICTENC: ScriptFlags = 1 << 1;
+/// Enforce strict DER signatures (BIP66).
+pub const SCRIPT_VERIFY_DERSIG: ScriptFlags = 1 << 2;
+/// Enforce low S values in signatures.
+pub const SCRIPT_VERIFY_LOW_S: ScriptFlags = 1 << 3;
+/// Require the dummy element of OP_CHECKMULTISIG to be empty.
+pub const SCRIPT_VERIFY_NULLDUMMY: ScriptFlags = 1 << 4;
+/// Verify OP_CHECKLOCKTIMEVERIFY (BIP65).
+pub const SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY: ScriptFlags = 1 << 9;
+/// Verify OP_CHECKSEQUENCEVERIFY (BIP112).
+pub const SCRIPT_VERIFY_CHECKSEQUENCEVERIFY: ScriptFlags = 1 << 10;
+
+/// Flags that blocks must satisfy.
+pub const MANDATORY_SCRIPT_VERIFY_FLAGS: ScriptFlags = SCRIPT_VERIFY_P2SH;
+/// The standard script verification flags of Dogecoin Core that this interpreter
+/// implements, checked on relay but not in blocks. The other standardness rules are
+/// in [`crate::policy::check_standard`].
+pub const STANDARD_SCRIPT_VERIFY_FLAGS: ScriptFlags = MANDATORY_SCRIPT_VERIFY_FLAGS
+    | SCRIPT_VERIFY_STRICTENC
+    | SCRIPT_VERIFY_DERSIG
+    | SCRIPT_VERIFY_LOW_S
+    | SCRIPT_VERIFY_NULLDUMMY
+    | SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY
+    | SCRIPT_VERIFY_CHECKSEQUENCEVERIFY;
+
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+pub const MAX_SCRIPT_SIZE: usize = 10000;
+pub const MAX_STACK_SIZE: usize = 1000;
+/// Threshold for nLockTime: below this value it is interpreted as block number,
+/// otherwise as UNIX timestamp.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+const SIGHASH_ALL: u8 = 1;
+const SIGHASH_SINGLE: u8 = 3;
+const SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+/// Checks signatures and timelocks against the spending transaction.
+pub trait SignatureChecker {
+    /// Checks a signature (with its trailing sighash type byte) for `pubkey` over `script_code`.
+    fn check_sig(&self, _sig: &[u8], _pubkey: &[u8], _script_code: &Script) -> bool {
+        false
+    }
+
+    fn check_lock_time(&self, _lock_time: i64) -> bool {
+        false
+    }
+
+    fn check_sequence(&self, _sequence: i64) -> bool {
+        false
+    }
+}
+
+/// Signature checker that fails every check, for evaluating scripts without a transaction.
+pub struct BaseSignatureChecker;
+
+impl SignatureChecker for BaseSignatureChecker {}
+
+pub struct TransactionSignatureChecker<'a> {
+    sighasher: SighashCache<&'a Transaction>,
+    input_index: usize,
+    secp: Secp256k1<VerifyOnly>,
+}
+
+impl<'a> TransactionSignatureChecker<'a> {
+    pub fn new(tx: &'a Transaction, input_index: usize) -> Self {
+        TransactionSignatureChecker {
+            sighasher: SighashCache::new(tx),
+            input_index,
+            secp: Secp256k1::verification_only(),
+        }
+    }
+
+    fn input(&self) -> &TxIn {
+        &self.sighasher.transaction().input[self.input_index]
+    }
+}
+
+impl SignatureChecker for TransactionSignatureChecker<'_> {
+    fn check_sig(&self, sig: &[u8], pubkey: &[u8], script_code: &Script) -> bool {
+        let (sighash_type, der) = match sig.split_last() {
+            Some(v) => v,
+            None => return false,
+        };
+        let pubkey = match PublicKey::from_slice(pubkey) {
+            Ok(pk) => pk,
+            Err(_) => return false,
+        };
+        let mut sig = match Signature::from_der_lax(der) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        };
+        // libsecp256k1's ECDSA verification requires lower-S signatures
+        sig.normalize_s();
+        let sighash = match self.sighasher.legacy_signature_hash(
+            self.input_index,
+            script_code,
+            *sighash_type as u32,
+        ) {
+            Ok(h) => h,
+            Err(_) => return false,
+        };
+        self.secp
+            .verify_ecdsa(&Message::from(sighash), &sig, &pubkey)
+            .is_ok()
+    }
+
+    fn check_lock_time(&self, lock_time: i64) -> bool {
+        let tx_lock_time = self.sighasher.transaction().lock_time as i64;
+        let threshold = LOCKTIME_THRESHOLD as i64;
+        // There are two kinds of nLockTime: lock-by-blockheight and lock-by-blocktime,
+        // the script and the transaction must use the same kind.
+        if (tx_lock_time < threshold) != (lock_time < threshold) {
+            return false;
+        }
+        if lock_time > tx_lock_time {
+            return false;
+        }
+        // A final input would make nLockTime ineffective, so it must not be final.
+        self.input().sequence != u32::MAX
+    }
+
+    fn check_sequence(&self, sequence: i64) -> bool {
+        let tx_sequence = self.input().sequence as i64;
+        // Relative lock times are supported from transaction version 2 (BIP68).
+        if self.sighasher.transaction().version < 2 {
+            return false;
+        }
+        if tx_sequence & TxIn::SEQUENCE_LOCKTIME_DISABLE_FLAG as i64 != 0 {
+            return false;
+        }
+        let mask = (TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG | TxIn::SEQUENCE_LOCKTIME_MASK) as i64;
+        let type_flag = TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG as i64;
+        let tx_sequence = tx_sequence & mask;
+        let sequence = sequence & mask;
+        if (tx_sequence < type_flag) != (sequence < type_flag) {
+            return false;
+        }
+        sequence <= tx_sequence
+    }
+}
+
+/// Verifies that `script_sig` satisfies `script_pubkey`, mirroring VerifyScript.
+pub fn verify_script(
+    script_sig: &Script,
+    script_pubkey: &Script,
+    flags: ScriptFlags,
+    checker: &impl SignatureChecker,
+) -> Result<(), String> {
+    let mut stack: Vec<Vec<u8>> = Vec::new();
+    eval_script(&mut stack, script_sig, flags, checker)?;
+    let stack_copy = if flags & SCRIPT_VERIFY_P2SH != 0 {
+        stack.clone()
+    } else {
+        vec![]
+    };
+    eval_script(&mut stack, script_pubkey, flags, checker)?;
+    if !stack.last().map(|v| cast_to_bool(v)).unwrap_or(false) {
+        return Err(
+            "script evaluated without error but finished with a false/empty top stack element"
+                .to_string(),
+        );
+    }
+
+    // Additional validation for spend-to-script-hash transactions:
+    if flags & SCRIPT_VERIFY_P2SH != 0 && script_pubkey.is_p2sh() {
+        // scriptSig must be literals-only or validation fails
+        if !script_sig.is_push_only() {
+            return Err("only non-push operators allowed in signatures".to_string());
+        }
+        stack = stack_copy;
+        // stack cannot be empty here, because if it was the
+        // P2SH  HASH <> EQUAL  scriptPubKey would be evaluated with
+        // an empty stack and the EvalScript above would return false.
+        let redeem_script = stack.pop().ok_or("invalid stack operation")?;
+        eval_script(
+            &mut stack,
+            Script::from_bytes(&redeem_script),
+            flags,
+            checker,
+        )?;
+        if !stack.last().map(|v| cast_to_bool(v)).unwrap_or(false) {
+            return Err("P2SH script evaluated without error but finished with a false/empty top stack element".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Verifies the input `input_index` of a signed transaction against the script_pubkey
+/// of the output it spends.
+pub fn verify_input(
+    tx: &Transaction,
+    input_index: usize,
+    script_pubkey: &Script,
+    flags: ScriptFlags,
+) -> Result<(), String> {
+    let input = tx
+        .input
+        .get(input_index)
+        .ok_or_else(|| format!("input index {} out of range", input_index))?;
+    let checker = TransactionSignatureChecker::new(tx, input_index);
+    verify_script(&input.script, script_pubkey, flags, &checker)
+        .map_err(|err| format!("input {} script verification failed: {}", input_index, err))
+}
+
+/// Evaluates a script on the given stack, mirroring EvalScript.
+pub fn eval_script(
+    stack: &mut Vec<Vec<u8>>,
+    script: &Script,
+    flags: ScriptFlags,
+    checker: &impl SignatureChecker,
+) -> Result<(), String> {
+    let script = script.as_bytes();
+    if script.len() > MAX_SCRIPT_SIZE {
+        return Err("script is too big".to_string());
+    }
+
+    let mut pc = 0;
+    let mut begin_code_hash = 0;
+    let mut exec_stack: Vec<bool> = Vec::new();
+    let mut alt_stack: Vec<Vec<u8>> = Vec::new();
+    let mut op_count = 0;
+
+    while pc < script.len() {
+        let executing = !exec_stack.contains(&false);
+
+        // Read instruction
+        let (opcode, push) = get_op(script, &mut pc).ok_or("bad opcode")?;
+        if push.len() > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err("push value size limit exceeded".to_string());
+        }
+
+        // Note how OP_RESERVED does not count towards the opcode limit.
+        if opcode > OP_16 {
+            op_count += 1;
+            if op_count > MAX_OPS_PER_SCRIPT {
+                return Err("operation limit exceeded".to_string());
+            }
+        }
+
+        if matches!(
+            opcode,
+            OP_CAT
+                | OP_SUBSTR
+                | OP_LEFT
+                | OP_RIGHT
+                | OP_INVERT
+                | OP_AND
+                | OP_OR
+                | OP_XOR
+                | OP_2MUL
+                | OP_2DIV
+                | OP_MUL
+                | OP_DIV
+                | OP_MOD
+                | OP_LSHIFT
+                | OP_RSHIFT
+        ) {
+            // Disabled opcodes.
+            return Err("attempted to use a disabled opcode".to_string());
+        }
+
+        if executing && opcode <= OP_PUSHDATA4 {
+            stack.push(push.to_vec());
+        } else if executing || (OP_IF..=OP_ENDIF).contains(&opcode) {
+            match opcode {
+                // Push value
+                OP_1NEGATE | OP_1..=OP_16 => {
+                    stack.push(scriptnum_encode(opcode as i64 - (OP_1 - 1) as i64));
+                }
+
+                // Control
+                OP_NOP => {}
+
+                OP_CHECKLOCKTIMEVERIFY => {
+                    if flags & SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY != 0 {
+                        // Note that elsewhere numeric opcodes are limited to operands in the range
+                        // -2**31+1 to 2**31-1, however it is legal for opcodes to produce results
+                        // exceeding that range. Thus as a special case we tell CScriptNum to accept
+                        // up to 5-byte bignums, which are good until 2**39-1, well beyond the
+                        // 2**32-1 limit of the nLockTime field itself.
+                        let lock_time = scriptnum_decode(top(stack, 1)?, 5)?;
+                        if lock_time < 0 {
+                            return Err("negative locktime".to_string());
+                        }
+                        if !checker.check_lock_time(lock_time) {
+                            return Err("locktime requirement not satisfied".to_string());
+                        }
+                    }
+                }
+
+                OP_CHECKSEQUENCEVERIFY => {
+                    if flags & SCRIPT_VERIFY_CHECKSEQUENCEVERIFY != 0 {
+                        let sequence = scriptnum_decode(top(stack, 1)?, 5)?;
+                        if sequence < 0 {
+                            return Err("negative locktime".to_string());
+                        }
+                        // To provide for future soft-fork extensibility, if the
+                        // operand has the disabled lock-time flag set,
+                        // CHECKSEQUENCEVERIFY behaves as a NOP.
+                        if sequence & TxIn::SEQUENCE_LOCKTIME_DISABLE_FLAG as i64 == 0
+                            && !checker.check_sequence(sequence)
+                        {
+                            return Err("locktime requirement not satisfied".to_string());
+                        }
+                    }
+                }
+
+                OP_NOP1 | OP_NOP4..=OP_NOP10 => {}
+
+                OP_IF | OP_NOTIF => {
+                    // <expression> if [statements] [else [statements]] endif
+                    let mut value = false;
+                    if executing {
+                        let v = stack.pop().ok_or("unbalanced conditional")?;
+                        value = cast_to_bool(&v);
+                        if opcode == OP_NOTIF {
+                            value = !value;
+                        }
+                    }
+                    exec_stack.push(value);
+                }
+
+                OP_ELSE => {
+                    let v = exec_stack.last_mut().ok_or("unbalanced conditional")?;
+                    *v = !*v;
+                }
+
+                OP_ENDIF => {
+                    exec_stack.pop().ok_or("unbalanced conditional")?;
+                }
+
+                OP_VERIFY => {
+                    if !cast_to_bool(top(stack, 1)?) {
+                        return Err("script failed an OP_VERIFY operation".to_string());
+                    }
+                    stack.pop();
+                }
+
+                OP_RETURN => {
+                    return Err("OP_RETURN was encountered".to_string());
+                }
+
+                // Stack ops
+                OP_TOALTSTACK => {
+                    let v = stack.pop().ok_or("invalid stack operation")?;
+                    alt_stack.push(v);
+                }
+
+                OP_FROMALTSTACK => {
+                    let v = alt_stack.pop().ok_or("invalid altstack operation")?;
+                    stack.push(v);
+                }
+
+                OP_2DROP => {
+                    need(stack, 2)?;
+                    stack.truncate(stack.len() - 2);
+                }
+
+                OP_2DUP => {
+                    need(stack, 2)?;
+                    let n = stack.len();
+                    stack.extend_from_within(n - 2..);
+                }
+
+                OP_3DUP => {
+                    need(stack, 3)?;
+                    let n = stack.len();
+                    stack.extend_from_within(n - 3..);
+                }
+
+                OP_2OVER => {
+                    need(stack, 4)?;
+                    let n = stack.len();
+                    stack.extend_from_within(n - 4..n - 2);
+                }
+
+                OP_2ROT => {
+                    need(stack, 6)?;
+                    let n = stack.len();
+                    let v: Vec<Vec<u8>> = stack.drain(n - 6..n - 4).collect();
+                    stack.extend(v);
+                }
+
+                OP_2SWAP => {
+                    need(stack, 4)?;
+                    let n = stack.len();
+                    stack.swap(n - 4, n - 2);
+                    stack.swap(n - 3, n - 1);
+                }
+
+                OP_IFDUP => {
+                    let v = top(stack, 1)?;
+                    if cast_to_bool(v) {
+                        stack.push(v.clone());
+                    }
+                }
+
+                OP_DEPTH => {
+                    stack.push(scriptnum_encode(stack.len() as i64));
+                }
+
+                OP_DROP => {
+                    stack.pop().ok_or("invalid stack operation")?;
+                }
+
+                OP_DUP => {
+                    let v = top(stack, 1)?.clone();
+                    stack.push(v);
+                }
+
+                OP_NIP => {
+                    need(stack, 2)?;
+                    stack.remove(stack.len() - 2);
+                }
+
+                OP_OVER => {
+                    let v = top(stack, 2)?.clone();
+                    stack.push(v);
+                }
+
+                OP_PICK | OP_ROLL => {
+                    // (xn ... x2 x1 x0 n - xn ... x2 x1 x0 xn)
+                    // (xn ... x2 x1 x0 n - ... x2 x1 x0 xn)
+                    need(stack, 2)?;
+                    let n = scriptnum_decode(top(stack, 1)?, 4)?;
+                    stack.pop();
+                    if n < 0 || n >= stack.len() as i64 {
+                        return Err("invalid stack operation".to_string());
+                    }
+                    let idx = stack.len() - 1 - n as usize;
+                    let v = if opcode == OP_ROLL {
+                        stack.remove(idx)
+                    } else {
+                        stack[idx].clone()
+                    };
+                    stack.push(v);
+                }
+
+                OP_ROT => {
+                    // (x1 x2 x3 -- x2 x3 x1)
+                    need(stack, 3)?;
+                    let n = stack.len();
+                    stack[n - 3..].rotate_left(1);
+                }
+
+                OP_SWAP => {
+                    need(stack, 2)?;
+                    let n = stack.len();
+                    stack.swap(n - 2, n - 1);
+                }
+
+                OP_TUCK => {
+                    // (x1 x2 -- x2 x1 x2)
+                    let v = top(stack, 1)?.clone();
+                    need(stack, 2)?;
+                    stack.insert(stack.len() - 2, v);
+                }
+
+                OP_SIZE => {
+                    let n = top(stack, 1)?.len();
+                    stack.push(scriptnum_encode(n as i64));
+                }
+
+                // Bitwise logic
+                OP_EQUAL | OP_EQUALVERIFY => {
+                    need(stack, 2)?;
+                    let v2 = stack.pop().unwrap();
+                    let v1 = stack.pop().unwrap();
+                    let equal = v1 == v2;
+                    if opcode == OP_EQUALVERIFY {
+                        if !equal {
+                            return Err("script failed an OP_EQUALVERIFY operation".to_string());
+                        }
+                    } else {
+                        stack.push(bool_to_vec(equal));
+                    }
+                }
+
+                // Numeric
+                OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                    let n = scriptnum_decode(top(stack, 1)?, 4)?;
+                    let res = match opcode {
+                        OP_1ADD => n + 1,
+                        OP_1SUB => n - 1,
+                        OP_NEGATE => -n,
+                        OP_ABS => n.abs(),
+                        OP_NOT => (n == 0) as i64,
+                        _ => (n != 0) as i64,
+                    };
+                    stack.pop();
+                    stack.push(scriptnum_encode(res));
+                }
+
+                OP_ADD
+                | OP_SUB
+                | OP_BOOLAND
+                | OP_BOOLOR
+                | OP_NUMEQUAL
+                | OP_NUMEQUALVERIFY
+                | OP_NUMNOTEQUAL
+                | OP_LESSTHAN
+                | OP_GREATERTHAN
+                | OP_LESSTHANOREQUAL
+                | OP_GREATERTHANOREQUAL
+                | OP_MIN
+                | OP_MAX => {
+                    need(stack, 2)?;
+                    let n1 = scriptnum_decode(top(stack, 2)?, 4)?;
+                    let n2 = scriptnum_decode(top(stack, 1)?, 4)?;
+                    let res = match opcode {
+                        OP_ADD => n1 + n2,
+                        OP_SUB => n1 - n2,
+                        OP_BOOLAND => (n1 != 0 && n2 != 0) as i64,
+                        OP_BOOLOR => (n1 != 0 || n2 != 0) as i64,
+                        OP_NUMEQUAL | OP_NUMEQUALVERIFY => (n1 == n2) as i64,
+                        OP_NUMNOTEQUAL => (n1 != n2) as i64,
+                        OP_LESSTHAN => (n1 < n2) as i64,
+                        OP_GREATERTHAN => (n1 > n2) as i64,
+                        OP_LESSTHANOREQUAL => (n1 <= n2) as i64,
+                        OP_GREATERTHANOREQUAL => (n1 >= n2) as i64,
+                        OP_MIN => n1.min(n2),
+                        _ => n1.max(n2),
+                    };
+                    stack.truncate(stack.len() - 2);
+                    if opcode == OP_NUMEQUALVERIFY {
+                        if res == 0 {
+                            return Err("script failed an OP_NUMEQUALVERIFY operation".to_string());
+                        }
+                    } else {
+                        stack.push(scriptnum_encode(res));
+                    }
+                }
+
+                OP_WITHIN => {
+                    // (x min max -- out)
+                    need(stack, 3)?;
+                    let x = scriptnum_decode(top(stack, 3)?, 4)?;
+                    let min = scriptnum_decode(top(stack, 2)?, 4)?;
+                    let max = scriptnum_decode(top(stack, 1)?, 4)?;
+                    stack.truncate(stack.len() - 3);
+                    stack.push(bool_to_vec(min <= x && x < max));
+                }
+
+                // Crypto
+                OP_RIPEMD160 | OP_SHA1 | OP_SHA256 | OP_HASH160 | OP_HASH256 => {
+                    let v = stack.pop().ok_or("invalid stack operation")?;
+                    let hash = match opcode {
+                        OP_RIPEMD160 => ripemd160::Hash::hash(&v).to_byte_array().to_vec(),
+                        OP_SHA1 => sha1::Hash::hash(&v).to_byte_array().to_vec(),
+                        OP_SHA256 => sha256::Hash::hash(&v).to_byte_array().to_vec(),
+                        OP_HASH160 => hash160::Hash::hash(&v).to_byte_array().to_vec(),
+                        _ => sha256d::Hash::hash(&v).to_byte_array().to_vec(),
+                    };
+                    stack.push(hash);
+                }
+
+                OP_CODESEPARATOR => {
+                    // Hash starts after the code separator
+                    begin_code_hash = pc;
+                }
+
+                OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                    // (sig pubkey -- bool)
+                    need(stack, 2)?;
+                    let sig = top(stack, 2)?;
+                    let pubkey = top(stack, 1)?;
+
+                    // Drop the signature, since there's no way for a signature to sign itself
+                    let script_code = find_and_delete(&script[begin_code_hash..], sig);
+                    check_signature_encoding(sig, flags)?;
+                    check_pubkey_encoding(pubkey, flags)?;
+                    let success = checker.check_sig(sig, pubkey, Script::from_bytes(&script_code));
+
+                    stack.truncate(stack.len() - 2);
+                    if opcode == OP_CHECKSIGVERIFY {
+                        if !success {
+                            return Err("script failed an OP_CHECKSIGVERIFY operation".to_string());
+                        }
+                    } else {
+                        stack.push(bool_to_vec(success));
+                    }
+                }
+
+                OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                    // ([sig ...] num_of_signatures [pubkey ...] num_of_pubkeys -- bool)
+                    let mut i = 1;
+                    let mut keys_count = scriptnum_decode(top(stack, i)?, 4)?;
+                    if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&keys_count) {
+                        return Err("pubkey count is negative or too big".to_string());
+                    }
+                    op_count += keys_count as usize;
+                    if op_count > MAX_OPS_PER_SCRIPT {
+                        return Err("operation limit exceeded".to_string());
+                    }
+                    i += 1;
+                    let mut ikey = i;
+                    i += keys_count as usize;
+                    let mut sigs_count = scriptnum_decode(top(stack, i)?, 4)?;
+                    if sigs_count < 0 || sigs_count > keys_count {
+                        return Err(
+                            "signature count is negative or greater than pubkey count".to_string()
+                        );
+                    }
+                    i += 1;
+                    let mut isig = i;
+                    i += sigs_count as usize;
+                    need(stack, i)?;
+
+                    // Drop the signatures, since there's no way for a signature to sign itself
+                    let mut script_code = script[begin_code_hash..].to_vec();
+                    for k in 0..sigs_count as usize {
+                        script_code = find_and_delete(&script_code, top(stack, isig + k)?);
+                    }
+
+                    let mut success = true;
+                    while success && sigs_count > 0 {
+                        let sig = top(stack, isig)?;
+                        let pubkey = top(stack, ikey)?;
+                        // Note how this makes the exact order of pubkey/signature evaluation
+                        // distinguishable by CHECKMULTISIG NOT if the STRICTENC flag is set.
+                        check_signature_encoding(sig, flags)?;
+                        check_pubkey_encoding(pubkey, flags)?;
+                        if checker.check_sig(sig, pubkey, Script::from_bytes(&script_code)) {
+                            isig += 1;
+                            sigs_count -= 1;
+                        }
+                        ikey += 1;
+                        keys_count -= 1;
+                        // If there are more signatures left than keys left,
+                        // then too many signatures have failed. Exit early,
+                        // without checking any further signatures.
+                        if sigs_count > keys_count {
+                            success = false;
+                        }
+                    }
+
+                    // Clean up stack of actual arguments
+                    stack.truncate(stack.len() - (i - 1));
+                    // A bug causes CHECKMULTISIG to consume one extra argument
+                    // whose contents were not checked in any way.
+                    let dummy = stack.pop().ok_or("invalid stack operation")?;
+                    if flags & SCRIPT_VERIFY_NULLDUMMY != 0 && !dummy.is_empty() {
+                        return Err("dummy CHECKMULTISIG argument must be zero".to_string());
+                    }
+
+                    if opcode == OP_CHECKMULTISIGVERIFY {
+                        if !success {
+                            return Err(
+                                "script failed an OP_CHECKMULTISIGVERIFY operation".to_string()
+                            );
+                        }
+                    } else {
+                        stack.push(bool_to_vec(success));
+                    }
+                }
+
+                _ => return Err("bad opcode".to_string()),
+            }
+        }
+
+        // Size limits
+        if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+            return Err("stack size limit exceeded".to_string());
+        }
+    }
+
+    if !exec_stack.is_empty() {
+        return Err("unbalanced conditional".to_string());
+    }
+    Ok(())
+}
+
+// returns the i-th element from the top of the stack, 1-based
+fn top(stack: &[Vec<u8>], i: usize) -> Result<&Vec<u8>, String> {
+    if i == 0 || i > stack.len() {
+        return Err("invalid stack operation".to_string());
+    }
+    Ok(&stack[stack.len() - i])
+}
+
+fn need(stack: &[Vec<u8>], n: usize) -> Result<(), String> {
+    if stack.len() < n {
+        return Err("invalid stack operation".to_string());
+    }
+    Ok(())
+}
+
+pub fn cast_to_bool(v: &[u8]) -> bool {
+    for (i, b) in v.iter().enumerate() {
+        if *b != 0 {
+            // Can be negative zero
+            return !(i == v.len() - 1 && *b == 0x80);
+        }
+    }
+    false
+}
+
+fn bool_to_vec(v: bool) -> Vec<u8> {
+    if v {
+        vec![1]
+    } else {
+        vec![]
+    }
+}
+
+/// Decodes a CScriptNum of at most `max_size` bytes.
+pub fn scriptnum_decode(v: &[u8], max_size: usize) -> Result<i64, String> {
+    if v.len() > max_size {
+        return Err("script number overflow".to_string());
+    }
+    if v.is_empty() {
+        return Ok(0);
+    }
+    let mut res: i64 = 0;
+    for (i, b) in v.iter().enumerate() {
+        res |= (*b as i64) << (8 * i);
+    }
+    // If the input vector's most significant byte is 0x80, remove it from
+    // the result's msb and return a negative.
+    let last = v[v.len() - 1];
+    if last & 0x80 != 0 {
+        return Ok(-(res & !(0x80i64 << (8 * (v.len() - 1)))));
+    }
+    Ok(res)
+}
+
+/// Encodes a number as a minimal CScriptNum.
+pub fn scriptnum_encode(n: i64) -> Vec<u8> {
+    if n == 0 {
+        return vec![];
+    }
+    let neg = n < 0;
+    let mut abs = n.unsigned_abs();
+    let mut res = Vec::new();
+    while abs > 0 {
+        res.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    // If the most significant byte is >= 0x80 and the value is positive, push a
+    // new zero-byte to make the significant byte < 0x80 again. If negative, push
+    // 0x80 or set the sign bit of the most significant byte.
+    let last = res.len() - 1;
+    if res[last] & 0x80 != 0 {
+        res.push(if neg { 0x80 } else { 0 });
+    } else if neg {
+        res[last] |= 0x80;
+    }
+    res
+}
+
+// Removes all occurrences of the serialized push of `data` at operation boundaries,
+// mirroring CScript::FindAndDelete.
+fn find_and_delete(script: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut needle = Vec::with_capacity(data.len() + 5);
+    match data.len() {
+        n if n < OP_PUSHDATA1 as usize => needle.push(n as u8),
+        n if n <= 0xff => needle.extend_from_slice(&[OP_PUSHDATA1, n as u8]),
+        n if n <= 0xffff => {
+            needle.push(OP_PUSHDATA2);
+            needle.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        n => {
+            needle.push(OP_PUSHDATA4);
+            needle.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+    }
+    needle.extend_from_slice(data);
+
+    let mut res = Vec::with_capacity(script.len());
+    let mut found = false;
+    let mut pc = 0;
+    let mut pc2 = 0;
+    loop {
+        res.extend_from_slice(&script[pc2..pc]);
+        while script.len() - pc >= needle.len() && script[pc..].starts_with(&needle) {
+            pc += needle.len();
+            found = true;
+        }
+        pc2 = pc;
+        if get_op(script, &mut pc).is_none() {
+            break;
+        }
+    }
+    if !found {
+        return script.to_vec();
+    }
+    res.extend_from_slice(&script[pc2..]);
+    res
+}
+
+/// A canonical signature exists of: <30> <total len> <02> <len R> <R> <02> <len S> <S> <hashtype>,
+/// see BIP66.
+pub fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    // Minimum and maximum size constraints.
+    if sig.len() < 9 || sig.len() > 73 {
+        return false;
+    }
+    // A signature is of type 0x30 (compound).
+    if sig[0] != 0x30 {
+        return false;
+    }
+    // Make sure the length covers the entire signature.
+    if sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    // Extract the length of the R element.
+    let len_r = sig[3] as usize;
+    // Make sure the length of the S element is still inside the signature.
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    // Extract the length of the S element.
+    let len_s = sig[5 + len_r] as usize;
+    // Verify that the length of the signature matches the sum of the length
+    // of the elements.
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+    // Check whether the R element is an integer.
+    if sig[2] != 0x02 {
+        return false;
+    }
+    // Zero-length integers are not allowed for R.
+    if len_r == 0 {
+        return false;
+    }
+    // Negative numbers are not allowed for R.
+    if sig[4] & 0x80 != 0 {
+        return false;
+    }
+    // Null bytes at the start of R are not allowed, unless R would
+    // otherwise be interpreted as a negative number.
+    if len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+    // Check whether the S element is an integer.
+    if sig[len_r + 4] != 0x02 {
+        return false;
+    }
+    // Zero-length integers are not allowed for S.
+    if len_s == 0 {
+        return false;
+    }
+    // Negative numbers are not allowed for S.
+    if sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    // Null bytes at the start of S are not allowed, unless S would otherwise be
+    // interpreted as a negative number.
+    if len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0 {
+        return false;
+    }
+    true
+}
+
+fn is_low_der_signature(sig: &[u8]) -> Result<(), String> {
+    if !is_valid_signature_encoding(sig) {
+        return Err("non-canonical DER signature".to_string());
+    }
+    let sig = Signature::from_der_lax(&sig[..sig.len() - 1])
+        .map_err(|_| "non-canonical signature: S value is unnecessarily high".to_string())?;
+    let mut normalized = sig;
+    normalized.normalize_s();
+    if normalized != sig {
+        return Err("non-canonical signature: S value is unnecessarily high".to_string());
+    }
+    Ok(())
+}
+
+fn is_defined_hashtype_signature(sig: &[u8]) -> bool {
+    match sig.last() {
+        Some(v) => {
+            let t = v & !SIGHASH_ANYONECANPAY;
+            (SIGHASH_ALL..=SIGHASH_SINGLE).contains(&t)
+        }
+        None => false,
+    }
+}
+
+fn check_signature_encoding(sig: &[u8], flags: ScriptFlags) -> Result<(), String> {
+    // Empty signature. Not strictly DER encoded, but allowed to provide a
+    // compact way to provide an invalid signature for use with CHECK(MULTI)SIG
+    if sig.is_empty() {
+        return Ok(());
+    }
+    if flags & (SCRIPT_VERIFY_DERSIG | SCRIPT_VERIFY_LOW_S | SCRIPT_VERIFY_STRICTENC) != 0
+        && !is_valid_signature_encoding(sig)
+    {
+        return Err("non-canonical DER signature".to_string());
+    }
+    if flags & SCRIPT_VERIFY_LOW_S != 0 {
+        is_low_der_signature(sig)?;
+    }
+    if flags & SCRIPT_VERIFY_STRICTENC != 0 && !is_defined_hashtype_signature(sig) {
+        return Err("signature hash type missing or not understood".to_string());
+    }
+    Ok(())
+}
+
+fn check_pubkey_encoding(pubkey: &[u8], flags: ScriptFlags) -> Result<(), String> {
+    let valid = match pubkey.first() {
+        Some(0x04) => pubkey.len() == 65,
+        Some(0x02) | Some(0x03) => pubkey.len() == 33,
+        _ => false,
+    };
+    if flags & SCRIPT_VERIFY_STRICTENC != 0 && !valid {
+        return Err("public key is neither compressed or uncompressed".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pow::U256;
+    use crate::script::{PushBytes, ScriptBuf};
+    use crate::transaction::OutPoint;
+    use bitcoin::PubkeyHash;
+    use hex::test_hex_unwrap as hex;
+
+    fn run(script: &[u8], flags: ScriptFlags) -> Result<(), String> {
+        verify_script(
+            Script::new(),
+            Script::from_bytes(script),
+            flags,
+            &BaseSignatureChecker,
+        )
+    }
+
+    #[test]
+    fn test_scriptnum() {
+        let nums = [0, 1, -1, 127, 128, -128, 255, 256, 32767, -32768];
+        for n in nums.into_iter().chain([i32::MAX as i64, -i32::MAX as i64]) {
+            assert_eq!(scriptnum_decode(&scriptnum_encode(n), 4).unwrap(), n);
+        }
+        assert_eq!(scriptnum_encode(128), vec![0x80, 0x00]);
+        assert_eq!(scriptnum_encode(-1), vec![0x81]);
+        assert!(scriptnum_decode(&[1, 2, 3, 4, 5], 4).is_err());
+        assert!(cast_to_bool(&[0, 1]));
+        assert!(!cast_to_bool(&[0, 0x80]));
+        assert!(!cast_to_bool(&[]));
+    }
+
+    #[test]
+    fn test_eval_script() {
+        for (asm, script) in [
+            ("2 3 ADD 5 EQUAL", "5253935587"),
+            ("1 IF 2 ELSE 3 ENDIF 2 EQUAL", "5163526753685287"),
+            ("0 IF 2 ELSE 3 ENDIF 3 EQUAL", "0063526753685387"),
+            (
+                "1 2 3 ROT 1 EQUALVERIFY 3 EQUALVERIFY 2 EQUAL",
+                "5152537b518853885287",
+            ),
+            ("5 1 7 WITHIN", "555157a5"),
+            ("7 8 9 2 PICK 7 EQUAL", "57585952795787"),
+            (
+                "7 8 9 2 ROLL 7 EQUALVERIFY DEPTH 2 EQUAL",
+                "575859527a5788745287",
+            ),
+            ("1 0 IF RESERVED ENDIF", "5100635068"),
+            (
+                "'abc' SHA256 <sha256('abc')> EQUAL",
+                "03616263a820ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad87",
+            ),
+        ] {
+            assert!(run(&hex!(script), 0).is_ok(), "{}", asm);
+        }
+
+        for (asm, script) in [
+            ("1 RETURN", "516a"),
+            ("1 IF", "5163"),
+            ("ENDIF", "68"),
+            ("0", "00"),
+            ("DROP", "75"),
+            ("1 VERIF", "5165"),
+            // disabled opcodes fail even in an unexecuted branch
+            ("1 0 IF CAT ENDIF", "5100637e68"),
+            // truncated push
+            ("1 <5 bytes>", "510501"),
+            ("1 1 1 1 1 CHECKMULTISIG", "5151515151ae"),
+        ] {
+            assert!(run(&hex!(script), 0).is_err(), "{}", asm);
+        }
+    }
+
+    #[test]
+    fn test_timelocks() {
+        let mut tx = Transaction {
+            version: 1,
+            lock_time: 1000,
+            input: vec![TxIn::with_outpoint(OutPoint::default())],
+            output: vec![],
+        };
+        tx.input[0].sequence = 0xfffffffe;
+        let cltv = |n: i64, tx: &Transaction, flags| {
+            let mut script = ScriptBuf::new();
+            script.push_slice(<&PushBytes>::try_from(&scriptnum_encode(n)[..]).unwrap());
+            script.push_opcode(OP_CHECKLOCKTIMEVERIFY.into());
+            script.push_opcode(OP_DROP.into());
+            script.push_opcode(OP_TRUE.into());
+            verify_script(
+                Script::new(),
+                &script,
+                flags,
+                &TransactionSignatureChecker::new(tx, 0),
+            )
+        };
+        cltv(999, &tx, SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY).unwrap();
+        cltv(1000, &tx, SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY).unwrap();
+        assert!(cltv(1001, &tx, SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY).is_err());
+        assert!(cltv(-1, &tx, SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY).is_err());
+        assert!(cltv(500_000_001, &tx, SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY).is_err());
+        // treated as NOP2 without the flag
+        cltv(1001, &tx, SCRIPT_VERIFY_NONE).unwrap();
+        let mut final_tx = tx.clone();
+        final_tx.input[0].sequence = u32::MAX;
+        assert!(cltv(999, &final_tx, SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY).is_err());
+
+        let csv = |n: i64, tx: &Transaction| {
+            let mut script = ScriptBuf::new();
+            script.push_slice(<&PushBytes>::try_from(&scriptnum_encode(n)[..]).unwrap());
+            script.push_opcode(OP_CHECKSEQUENCEVERIFY.into());
+            script.push_opcode(OP_DROP.into());
+            script.push_opcode(OP_TRUE.into());
+            verify_script(
+                Script::new(),
+                &script,
+                SCRIPT_VERIFY_CHECKSEQUENCEVERIFY,
+                &TransactionSignatureChecker::new(tx, 0),
+            )
+        };
+        tx.version = 2;
+        tx.input[0].sequence = 10;
+        csv(5, &tx).unwrap();
+        csv(10, &tx).unwrap();
+        assert!(csv(11, &tx).is_err());
+        assert!(csv(5 | TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG as i64, &tx).is_err());
+        csv(11 | TxIn::SEQUENCE_LOCKTIME_DISABLE_FLAG as i64, &tx).unwrap();
+        tx.version = 1;
+        assert!(csv(5, &tx).is_err());
+    }
+
+    #[test]
+    fn test_verify_p2pkh() {
+        // https://dogechain.info/tx/f875c9959d2013caedf7b3acce278d1bed7be0e7d45aa266db1c262e6743cb99
+        let tx_data = hex!("0100000003607ee3f9c2c8eb4db9297d38c3ed1493fbdd257283eabf5a89999ebd5676f13f000000006a473044022045228179cc4fed581b5d9e6411402fd34c40866397094f0d97843ee257b30abc0220310c33c4452d47113c51257883c780d40928ba6b9e2bf14c234f1bd5991607e6012102ca3e8f77965b91cbe15203817cce0170bc066d5d7a9acb2070e9b3e4b2077bb8feffffff538cde51d37d84ec955410476c18c7dddaf5531a7aa07e60b75dd106ca50b447000000006b483045022100b58824fe1e036320a5cc267fe65fec100988df2a0d0ea594b945088c4945765b0220091e3dead2cec11ce5c4139f126452437b8ba8d957ae7dcee0fba60d854d46df0121026079f574275f68fd88fbd01c0af3364524b3071419dc24bddbf60003be974288feffffff7e24f4f31a3a6eaf109c3368352975c1e0ef10d175905e991553e4040b049e8c010000006b483045022100f3d369af38220916afb0c805581cd14b08ff0ee83b093c71fdeccb9b8fea197f022053e648c6a0ed5e1b42042d0e4654be8e039082c8106f147726e420cf1c986d03012103a0e805a231331c414b0423adef1ddedb23cc801acfd90ba7bd95907048a3b908feffffff02a4700106000000001976a91467f5672ce989470f4dcba16c1e930f80c03c887488acf23b03c3360000001976a91489248eee4e9d99729ebebbca00efb75ceb1ed01888acf9ed4900");
+        let tx = Transaction::try_from(&tx_data[..]).unwrap();
+        let script_pubkey = ScriptBuf::new_p2pkh(
+            &PubkeyHash::from_slice(&hex!("e1209b366bebd881861c9aaeed0b780f531c6435")).unwrap(),
+        );
+        verify_input(&tx, 0, &script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS).unwrap();
+        // signed for a different input
+        assert!(verify_input(&tx, 1, &script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS).is_err());
+        assert!(verify_input(&tx, 3, &script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS).is_err());
+
+        let wrong = ScriptBuf::new_p2pkh(&PubkeyHash::from_slice(&[0u8; 20]).unwrap());
+        assert!(verify_input(&tx, 0, &wrong, STANDARD_SCRIPT_VERIFY_FLAGS).is_err());
+
+        let mut bad = tx.clone();
+        bad.output[0].value += 1;
+        assert!(verify_input(&bad, 0, &script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS).is_err());
+
+        // the same signature with a high S value
+        let mut ins = tx.input[0].script.instructions();
+        let sig = ins
+            .next()
+            .unwrap()
+            .unwrap()
+            .push_bytes()
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        let pubkey = ins
+            .next()
+            .unwrap()
+            .unwrap()
+            .push_bytes()
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        let compact = Signature::from_der(&sig[..sig.len() - 1])
+            .unwrap()
+            .serialize_compact();
+        let order = hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141");
+        let be_to_u256 = |data: &[u8]| {
+            let mut le: [u8; 32] = data.try_into().unwrap();
+            le.reverse();
+            U256::from_le_bytes(le)
+        };
+        let high_s = be_to_u256(&order) - be_to_u256(&compact[32..]);
+        let mut high_s = high_s.to_le_bytes();
+        high_s.reverse();
+        let mut compact_high = compact;
+        compact_high[32..].copy_from_slice(&high_s);
+        let mut high_sig = Signature::from_compact(&compact_high)
+            .unwrap()
+            .serialize_der()
+            .to_vec();
+        high_sig.push(SIGHASH_ALL);
+        let mut script_sig = ScriptBuf::new();
+        script_sig.push_slice(<&PushBytes>::try_from(&high_sig[..]).unwrap());
+        script_sig.push_slice(<&PushBytes>::try_from(&pubkey[..]).unwrap());
+        let mut high_tx = tx.clone();
+        high_tx.input[0].script = script_sig;
+        verify_input(
+            &high_tx,
+            0,
+            &script_pubkey,
+            SCRIPT_VERIFY_P2SH | SCRIPT_VERIFY_DERSIG,
+        )
+        .unwrap();
+        assert!(
+            verify_input(&high_tx, 0, &script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS)
+                .unwrap_err()
+                .contains("S value is unnecessarily high")
+        );
+    }
+
+    #[test]
+    fn test_verify_p2sh_multisig() {
+        // 2-of-2 P2SH multisig spend, from the block in block::tests::test_block
+        let tx_data = hex!("0100000001892bc2522115e7bbf227a93aa5708e22b621616a398ab4c7119237b1da0b37d84f000000da00483045022100c5163a753aa37f9dfed708ed9167474f2b10a2b57238b00a95b3edb4f302d3fe022073fdc9c7a2147c79fb04e121a0aa64ce82901e31d15f65fa9c7a0633f2923112014730440220368808f54756249843c30cbf9ff964d1eddf4473f13352084dc1ab2229209e3302202578414b8fa035ffb58c861e965f0bcb9111636eedd173a936a4a326c138259d0147522102e60e9ac8a490768b3cd74fa55acf36d92491012c46dda806367f6dd567ac7b012103e5ed64e1c73f6d341d2a36d3f987a3b325117843f65afc24a616879047c7971652ae000000000200ca9a3b000000001976a914c7935d8f471d3472d31b6c5682f4bc2aaf2b806488acc54b563e0000000017a914f033fcfbdeafc8d9547c9015e021375694c316008700000000");
+        let tx = Transaction::try_from(&tx_data[..]).unwrap();
+        let redeem_script = tx.input[0]
+            .script
+            .instructions()
+            .last()
+            .unwrap()
+            .unwrap()
+            .push_bytes()
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        let script_pubkey = ScriptBuf::new_p2sh(&Script::from_bytes(&redeem_script).script_hash());
+        verify_input(&tx, 0, &script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS).unwrap();
+
+        // the redeem script alone is not evaluated without P2SH
+        let mut bad = tx.clone();
+        let mut script_sig = ScriptBuf::new();
+        script_sig.push_slice(<&PushBytes>::try_from(&redeem_script[..]).unwrap());
+        bad.input[0].script = script_sig;
+        verify_input(&bad, 0, &script_pubkey, SCRIPT_VERIFY_NONE).unwrap();
+        assert!(verify_input(&bad, 0, &script_pubkey, SCRIPT_VERIFY_P2SH).is_err());
+
+        // non-null dummy
+        let mut bad = tx.clone();
+        let mut script = bad.input[0].script.to_bytes();
+        script[0] = OP_1;
+        bad.input[0].script = ScriptBuf::from_bytes(script);
+        verify_input(&bad, 0, &script_pubkey, SCRIPT_VERIFY_P2SH).unwrap();
+        assert!(
+            verify_input(&bad, 0, &script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS)
+                .unwrap_err()
+                .contains("dummy")
+        );
+
+        // signatures out of order
+        let mut bad = tx.clone();
+        let pushes: Vec<Vec<u8>> = tx.input[0]
+            .script
+            .instructions()
+            .map(|ins| ins.unwrap().push_bytes().unwrap().as_bytes().to_vec())
+            .collect();
+        let mut script_sig = ScriptBuf::new();
+        script_sig.push_opcode(OP_0.into());
+        for i in [2, 1, 3] {
+            script_sig.push_slice(<&PushBytes>::try_from(&pushes[i][..]).unwrap());
+        }
+        bad.input[0].script = script_sig;
+        assert!(verify_input(&bad, 0, &script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS).is_err());
+    }
+
+    #[test]
+    fn test_find_and_delete() {
+        assert_eq!(
+            find_and_delete(&[0x02, 0xff, 0x03, OP_1], &[0xff, 0x03]),
+            vec![OP_1]
+        );
+        // only matches at operation boundaries
+        let script = vec![0x03, 0x02, 0xff, 0x03];
+        assert_eq!(find_and_delete(&script, &[0xff, 0x03]), script);
+        assert_eq!(find_and_delete(&[OP_1, OP_1], &[]), vec![0x51, 0x51]);
+    }
+}
@@ -5,8 +5,10 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{to_vec, Value};
 use std::str::FromStr;
 
-use crate::block::{Block, BlockHash};
+use crate::block::{Block, BlockHash, BlockHeader, MerkleTx};
 use crate::err_string;
+use crate::merkleblock::MerkleBlock;
+use crate::p2p::AuxPowHeader;
 use crate::transaction::{Transaction, Txid};
 
 pub static APP_AGENT: &str = concat!(
@@ -35,7 +37,113 @@ pub struct RPCRequest<'a> {
 pub struct RPCResponse<T> {
     result: Option<T>,
     error: Option<Value>,
-    // id: u64,
+    #[serde(default)]
+    id: Value,
+}
+
+impl<T: DeserializeOwned> RPCResponse<T> {
+    fn into_result(self) -> Result<T, RpcError> {
+        if let Some(error) = self.error {
+            return Err(match serde_json::from_value::<ErrorObject>(error.clone()) {
+                Ok(err) => RpcError::Node {
+                    code: err.code,
+                    message: err.message,
+                },
+                Err(_) => RpcError::Transport(format!("unexpected RPC error: {}", error)),
+            });
+        }
+
+        match self.result {
+            Some(result) => Ok(result),
+            None => serde_json::from_value(Value::Null).map_err(RpcError::transport),
+        }
+    }
+}
+
+// https://github.com/dogecoin/dogecoin/blob/master/src/rpc/protocol.h
+pub const RPC_INVALID_REQUEST: i64 = -32600;
+pub const RPC_METHOD_NOT_FOUND: i64 = -32601;
+pub const RPC_INVALID_PARAMS: i64 = -32602;
+pub const RPC_INTERNAL_ERROR: i64 = -32603;
+pub const RPC_PARSE_ERROR: i64 = -32700;
+pub const RPC_MISC_ERROR: i64 = -1;
+pub const RPC_TYPE_ERROR: i64 = -3;
+pub const RPC_INVALID_ADDRESS_OR_KEY: i64 = -5;
+pub const RPC_OUT_OF_MEMORY: i64 = -7;
+pub const RPC_INVALID_PARAMETER: i64 = -8;
+pub const RPC_DATABASE_ERROR: i64 = -20;
+pub const RPC_DESERIALIZATION_ERROR: i64 = -22;
+pub const RPC_VERIFY_ERROR: i64 = -25;
+pub const RPC_VERIFY_REJECTED: i64 = -26;
+pub const RPC_VERIFY_ALREADY_IN_CHAIN: i64 = -27;
+pub const RPC_IN_WARMUP: i64 = -28;
+pub const RPC_CLIENT_NOT_CONNECTED: i64 = -9;
+pub const RPC_CLIENT_IN_INITIAL_DOWNLOAD: i64 = -10;
+
+/// Error of a DogecoinRPC call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpcError {
+    /// JSON-RPC error returned by the node.
+    Node { code: i64, message: String },
+    /// The request didn't reach the node or its response can't be decoded.
+    Transport(String),
+}
+
+#[derive(Deserialize)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn transport(err: impl std::fmt::Display) -> Self {
+        RpcError::Transport(err.to_string())
+    }
+
+    /// The node's error code, None for transport errors.
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            RpcError::Node { code, .. } => Some(*code),
+            RpcError::Transport(_) => None,
+        }
+    }
+
+    /// Missing or already spent inputs.
+    pub fn is_missing_inputs(&self) -> bool {
+        self.code() == Some(RPC_VERIFY_ERROR)
+    }
+
+    /// Rejected by policy or consensus rules, e.g. insufficient fee.
+    pub fn is_rejected(&self) -> bool {
+        self.code() == Some(RPC_VERIFY_REJECTED)
+    }
+
+    pub fn is_already_in_chain(&self) -> bool {
+        self.code() == Some(RPC_VERIFY_ALREADY_IN_CHAIN)
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Node { code, message } => write!(f, "RPC error {}: {}", code, message),
+            RpcError::Transport(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<RpcError> for String {
+    fn from(err: RpcError) -> Self {
+        err.to_string()
+    }
+}
+
+/// dogecoind answers RPC errors with HTTP 500 or 404 and a JSON-RPC error body,
+/// agents should return such a body so that DogecoinRPC can decode the error.
+pub fn is_rpc_error_body(body: &[u8]) -> bool {
+    serde_json::from_slice::<RPCResponse<Value>>(body).is_ok_and(|res| res.error.is_some())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,6 +152,99 @@ pub struct BlockRef {
     pub height: u64,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChainTip {
+    pub height: u64,
+    pub hash: String,
+    pub branchlen: u64,
+    /// "active", "valid-fork", "valid-headers", "headers-only" or "invalid"
+    pub status: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockchainInfo {
+    pub chain: String,
+    pub blocks: u64,
+    pub headers: u64,
+    pub bestblockhash: String,
+    pub difficulty: f64,
+    pub mediantime: u64,
+    pub verificationprogress: f64,
+    #[serde(default)]
+    pub initialblockdownload: bool,
+    pub chainwork: String,
+    #[serde(default)]
+    pub size_on_disk: u64,
+    pub pruned: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxOutScriptPubKey {
+    pub asm: String,
+    pub hex: String,
+    #[serde(rename = "reqSigs", default)]
+    pub req_sigs: Option<u32>,
+    #[serde(rename = "type")]
+    pub script_type: String,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+/// `gettxout`, values are in DOGE.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxOutInfo {
+    pub bestblock: String,
+    pub confirmations: u64,
+    pub value: f64,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: TxOutScriptPubKey,
+    pub coinbase: bool,
+}
+
+/// `getmempoolentry`, fees are in DOGE.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MempoolEntry {
+    pub size: u64,
+    pub fee: f64,
+    pub modifiedfee: f64,
+    pub time: u64,
+    pub height: u64,
+    pub descendantcount: u64,
+    pub descendantsize: u64,
+    pub descendantfees: u64,
+    pub ancestorcount: u64,
+    pub ancestorsize: u64,
+    pub ancestorfees: u64,
+    #[serde(default)]
+    pub depends: Vec<String>,
+}
+
+/// `getmempoolinfo`, fee rates are in DOGE/kB.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MempoolInfo {
+    pub size: u64,
+    pub bytes: u64,
+    pub usage: u64,
+    pub maxmempool: u64,
+    pub mempoolminfee: f64,
+}
+
+/// `estimatesmartfee`, `feerate` is in DOGE/kB and negative when no estimate is
+/// available, `blocks` is the target the estimate is for.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SmartFeeEstimate {
+    pub feerate: f64,
+    pub blocks: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MempoolAcceptResult {
+    pub txid: String,
+    pub allowed: bool,
+    #[serde(rename = "reject-reason", default)]
+    pub reject_reason: Option<String>,
+}
+
 impl From<&BlockRef> for BlockHash {
     fn from(block: &BlockRef) -> Self {
         BlockHash::from_str(&block.hash).expect("invalid block hash")
@@ -51,33 +252,33 @@ impl From<&BlockRef> for BlockHash {
 }
 
 impl DogecoinRPC {
-    pub async fn ping(agent: impl JsonRPCAgent, idempotency_key: String) -> Result<(), String> {
+    pub async fn ping(agent: impl JsonRPCAgent, idempotency_key: String) -> Result<(), RpcError> {
         Self::call(agent, idempotency_key, "ping", &[]).await
     }
 
     pub async fn get_best_blockhash(
         agent: impl JsonRPCAgent,
         idempotency_key: String,
-    ) -> Result<BlockHash, String> {
+    ) -> Result<BlockHash, RpcError> {
         let hex: String = Self::call(agent, idempotency_key, "getbestblockhash", &[]).await?;
-        BlockHash::from_str(&hex).map_err(err_string)
+        BlockHash::from_str(&hex).map_err(RpcError::transport)
     }
 
     pub async fn get_blockhash(
         agent: impl JsonRPCAgent,
         idempotency_key: String,
         height: u64,
-    ) -> Result<BlockHash, String> {
+    ) -> Result<BlockHash, RpcError> {
         let hex: String =
             Self::call(agent, idempotency_key, "getblockhash", &[height.into()]).await?;
-        BlockHash::from_str(&hex).map_err(err_string)
+        BlockHash::from_str(&hex).map_err(RpcError::transport)
     }
 
     pub async fn get_block(
         agent: impl JsonRPCAgent,
         idempotency_key: String,
         hash: &BlockHash,
-    ) -> Result<Block, String> {
+    ) -> Result<Block, RpcError> {
         let hex: String = Self::call(
             agent,
             idempotency_key,
@@ -85,14 +286,182 @@ impl DogecoinRPC {
             &[hash.to_string().into(), 0.into()],
         )
         .await?;
-        deserialize_hex(&hex)
+        deserialize_hex(&hex).map_err(RpcError::Transport)
+    }
+
+    /// The header with its AuxPoW proof if the block is merge-mined.
+    pub async fn get_block_header(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        hash: &BlockHash,
+    ) -> Result<AuxPowHeader, RpcError> {
+        let hex: String = Self::call(
+            agent,
+            idempotency_key,
+            "getblockheader",
+            &[hash.to_string().into(), false.into()],
+        )
+        .await?;
+        let data = Vec::from_hex(&hex).map_err(RpcError::transport)?;
+        let mut reader = &data[..];
+        let header = BlockHeader::consensus_decode_from_finite_reader(&mut reader)
+            .map_err(RpcError::transport)?;
+        let auxpow = if header.is_auxpow() {
+            Some(
+                MerkleTx::consensus_decode_from_finite_reader(&mut reader)
+                    .map_err(RpcError::transport)?,
+            )
+        } else {
+            None
+        };
+        if reader.read_u8().is_ok() {
+            return Err(RpcError::transport(
+                "get_block_header: data not consumed entirely",
+            ));
+        }
+        Ok(AuxPowHeader { header, auxpow })
+    }
+
+    pub async fn get_block_count(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+    ) -> Result<u64, RpcError> {
+        Self::call(agent, idempotency_key, "getblockcount", &[]).await
+    }
+
+    pub async fn get_chain_tips(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+    ) -> Result<Vec<ChainTip>, RpcError> {
+        Self::call(agent, idempotency_key, "getchaintips", &[]).await
+    }
+
+    pub async fn get_blockchain_info(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+    ) -> Result<BlockchainInfo, RpcError> {
+        Self::call(agent, idempotency_key, "getblockchaininfo", &[]).await
+    }
+
+    /// Returns None if the output is spent or doesn't exist.
+    pub async fn get_txout(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        txid: &Txid,
+        vout: u32,
+        include_mempool: bool,
+    ) -> Result<Option<TxOutInfo>, RpcError> {
+        Self::call(
+            agent,
+            idempotency_key,
+            "gettxout",
+            &[txid.to_string().into(), vout.into(), include_mempool.into()],
+        )
+        .await
+    }
+
+    pub async fn get_raw_mempool(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+    ) -> Result<Vec<Txid>, RpcError> {
+        let txids: Vec<String> =
+            Self::call(agent, idempotency_key, "getrawmempool", &[false.into()]).await?;
+        txids
+            .iter()
+            .map(|txid| Txid::from_str(txid).map_err(RpcError::transport))
+            .collect()
+    }
+
+    pub async fn get_mempool_entry(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        txid: &Txid,
+    ) -> Result<MempoolEntry, RpcError> {
+        Self::call(
+            agent,
+            idempotency_key,
+            "getmempoolentry",
+            &[txid.to_string().into()],
+        )
+        .await
+    }
+
+    pub async fn get_mempool_info(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+    ) -> Result<MempoolInfo, RpcError> {
+        Self::call(agent, idempotency_key, "getmempoolinfo", &[]).await
+    }
+
+    /// Fee rate in DOGE/kB for confirmation within `nblocks`, -1 if no estimate is available.
+    pub async fn estimate_fee(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        nblocks: u32,
+    ) -> Result<f64, RpcError> {
+        Self::call(agent, idempotency_key, "estimatefee", &[nblocks.into()]).await
+    }
+
+    pub async fn estimate_smart_fee(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        nblocks: u32,
+    ) -> Result<SmartFeeEstimate, RpcError> {
+        Self::call(
+            agent,
+            idempotency_key,
+            "estimatesmartfee",
+            &[nblocks.into()],
+        )
+        .await
+    }
+
+    pub async fn test_mempool_accept(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        txs: &[Transaction],
+    ) -> Result<Vec<MempoolAcceptResult>, RpcError> {
+        let raw: Vec<Value> = txs.iter().map(|tx| serialize_hex(tx).into()).collect();
+        Self::call(agent, idempotency_key, "testmempoolaccept", &[raw.into()]).await
+    }
+
+    /// Decodes into `Value`, or `verbose::TransactionJson` with the `serde` feature.
+    pub async fn decode_raw_transaction<T: DeserializeOwned>(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        tx: &Transaction,
+    ) -> Result<T, RpcError> {
+        Self::call(
+            agent,
+            idempotency_key,
+            "decoderawtransaction",
+            &[serialize_hex(tx).into()],
+        )
+        .await
+    }
+
+    /// Proof that the transactions are included in a block, found through the txindex
+    /// or the UTXO set unless `blockhash` is given.
+    pub async fn get_txout_proof(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        txids: &[Txid],
+        blockhash: Option<&BlockHash>,
+    ) -> Result<MerkleBlock, RpcError> {
+        let txids: Vec<Value> = txids.iter().map(|txid| txid.to_string().into()).collect();
+        let mut params = vec![txids.into()];
+        if let Some(hash) = blockhash {
+            params.push(hash.to_string().into());
+        }
+        let hex: String = Self::call(agent, idempotency_key, "gettxoutproof", &params).await?;
+        deserialize_hex(&hex).map_err(RpcError::Transport)
     }
 
     pub async fn wait_for_new_block(
         agent: impl JsonRPCAgent,
         idempotency_key: String,
         timeout_ms: u64,
-    ) -> Result<BlockRef, String> {
+    ) -> Result<BlockRef, RpcError> {
         Self::call(
             agent,
             idempotency_key,
@@ -106,7 +475,7 @@ impl DogecoinRPC {
         agent: impl JsonRPCAgent,
         idempotency_key: String,
         hash: &Txid,
-    ) -> Result<Transaction, String> {
+    ) -> Result<Transaction, RpcError> {
         let hex: String = Self::call(
             agent,
             idempotency_key,
@@ -114,14 +483,14 @@ impl DogecoinRPC {
             &[hash.to_string().into(), 0.into()],
         )
         .await?;
-        deserialize_hex(&hex)
+        deserialize_hex(&hex).map_err(RpcError::Transport)
     }
 
     pub async fn send_transaction(
         agent: impl JsonRPCAgent,
         idempotency_key: String,
         tx: &Transaction,
-    ) -> Result<Txid, String> {
+    ) -> Result<Txid, RpcError> {
         let hex: String = Self::call(
             agent,
             idempotency_key,
@@ -129,14 +498,14 @@ impl DogecoinRPC {
             &[serialize_hex(tx).into()],
         )
         .await?;
-        Txid::from_str(&hex).map_err(err_string)
+        Txid::from_str(&hex).map_err(RpcError::transport)
     }
 
     pub async fn send_rawtransaction(
         agent: impl JsonRPCAgent,
         idempotency_key: String,
         raw: &[u8],
-    ) -> Result<Txid, String> {
+    ) -> Result<Txid, RpcError> {
         let hex: String = Self::call(
             agent,
             idempotency_key,
@@ -144,7 +513,7 @@ impl DogecoinRPC {
             &[raw.to_lower_hex_string().into()],
         )
         .await?;
-        Txid::from_str(&hex).map_err(err_string)
+        Txid::from_str(&hex).map_err(RpcError::transport)
     }
 
     pub async fn call<T: DeserializeOwned>(
@@ -152,26 +521,120 @@ impl DogecoinRPC {
         idempotency_key: String,
         method: &str,
         params: &[Value],
-    ) -> Result<T, String> {
+    ) -> Result<T, RpcError> {
         let input = RPCRequest {
             jsonrpc: "2.0",
             method,
             params,
             id: 1,
         };
-        let input = to_vec(&input).map_err(err_string)?;
-        let data = agent.post(idempotency_key, input).await?;
+        let input = to_vec(&input).map_err(RpcError::transport)?;
+        let data = agent
+            .post(idempotency_key, input)
+            .await
+            .map_err(RpcError::Transport)?;
 
-        let output: RPCResponse<T> = serde_json::from_slice(&data).map_err(err_string)?;
+        let output: RPCResponse<T> = serde_json::from_slice(&data).map_err(RpcError::transport)?;
+        output.into_result()
+    }
 
-        if let Some(error) = output.error {
-            return Err(serde_json::to_string(&error).map_err(err_string)?);
+    /// Sends the calls as one JSON-RPC batch and returns their results in the
+    /// order of `calls`, matched by request id. Use `T = Value` for mixed methods.
+    pub async fn batch_call<T: DeserializeOwned>(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        calls: &[(&str, Vec<Value>)],
+    ) -> Result<Vec<Result<T, RpcError>>, RpcError> {
+        if calls.is_empty() {
+            return Ok(Vec::new());
         }
 
-        match output.result {
-            Some(result) => Ok(result),
-            None => serde_json::from_value(Value::Null).map_err(err_string),
+        let input: Vec<RPCRequest> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, (method, params))| RPCRequest {
+                jsonrpc: "2.0",
+                method,
+                params,
+                id: i as u64,
+            })
+            .collect();
+        let input = to_vec(&input).map_err(RpcError::transport)?;
+        let data = agent
+            .post(idempotency_key, input)
+            .await
+            .map_err(RpcError::Transport)?;
+
+        // the server replies with a single error object if it rejects the whole batch
+        let output: Vec<RPCResponse<Value>> = match serde_json::from_slice(&data) {
+            Ok(output) => output,
+            Err(_) => {
+                let output: RPCResponse<Value> =
+                    serde_json::from_slice(&data).map_err(RpcError::transport)?;
+                output.into_result()?;
+                return Err(RpcError::transport("batch response is not an array"));
+            }
+        };
+
+        let mut results: Vec<Option<Result<T, RpcError>>> = calls.iter().map(|_| None).collect();
+        for res in output {
+            let slot = res
+                .id
+                .as_u64()
+                .and_then(|id| results.get_mut(id as usize))
+                .ok_or_else(|| {
+                    RpcError::Transport(format!("batch response with unknown id {}", res.id))
+                })?;
+            *slot = Some(
+                res.into_result()
+                    .and_then(|v| serde_json::from_value(v).map_err(RpcError::transport)),
+            );
         }
+        Ok(results
+            .into_iter()
+            .enumerate()
+            .map(|(i, res)| {
+                res.unwrap_or_else(|| {
+                    Err(RpcError::Transport(format!(
+                        "missing response for id {}",
+                        i
+                    )))
+                })
+            })
+            .collect())
+    }
+
+    /// Hashes of the blocks in `heights`, fetched in one batch.
+    pub async fn get_blockhashes(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        heights: std::ops::Range<u64>,
+    ) -> Result<Vec<BlockHash>, RpcError> {
+        let calls: Vec<(&str, Vec<Value>)> = heights
+            .map(|height| ("getblockhash", vec![height.into()]))
+            .collect();
+        let res: Vec<Result<String, RpcError>> =
+            Self::batch_call(agent, idempotency_key, &calls).await?;
+        res.into_iter()
+            .map(|hex| BlockHash::from_str(&hex?).map_err(RpcError::transport))
+            .collect()
+    }
+
+    /// Blocks with the given hashes, fetched in one batch.
+    pub async fn get_blocks(
+        agent: impl JsonRPCAgent,
+        idempotency_key: String,
+        hashes: &[BlockHash],
+    ) -> Result<Vec<Block>, RpcError> {
+        let calls: Vec<(&str, Vec<Value>)> = hashes
+            .iter()
+            .map(|hash| ("getblock", vec![hash.to_string().into(), 0.into()]))
+            .collect();
+        let res: Vec<Result<String, RpcError>> =
+            Self::batch_call(agent, idempotency_key, &calls).await?;
+        res.into_iter()
+            .map(|hex| deserialize_hex(&hex?).map_err(RpcError::Transport))
+            .collect()
     }
 }
 
@@ -196,79 +659,241 @@ pub fn deserialize_hex<T: Decodable>(hex: &str) -> Result<T, String> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use base64::Engine;
-    use dotenvy::dotenv;
-    use reqwest::{header, Client, ClientBuilder, Url};
-    use std::time::Duration;
+    use crate::chainparams::DOGE_MAIN_NET_CHAIN;
+    #[cfg(feature = "http")]
+    use {crate::httpagent::HttpRPCAgent, dotenvy::dotenv};
 
-    pub struct RPCAgent {
-        client: Client,
-        url: Url,
+    // answers getblockhash with the height as hash, in reverse order
+    struct MockAgent;
+
+    #[async_trait]
+    impl JsonRPCAgent for &MockAgent {
+        async fn post(
+            &self,
+            _idempotency_key: String,
+            body: Vec<u8>,
+        ) -> Result<bytes::Bytes, String> {
+            let reqs: Vec<Value> = serde_json::from_slice(&body).unwrap();
+            let res: Vec<Value> = reqs
+                .iter()
+                .rev()
+                .map(|req| {
+                    let height = req["params"][0].as_u64().unwrap();
+                    if height > 100 {
+                        serde_json::json!({"result": null, "error": {"code": -8, "message": "Block height out of range"}, "id": req["id"]})
+                    } else {
+                        let mut hash = [0u8; 32];
+                        hash[0] = height as u8;
+                        serde_json::json!({"result": BlockHash::from(hash).to_string(), "error": null, "id": req["id"]})
+                    }
+                })
+                .collect();
+            Ok(serde_json::to_vec(&res).unwrap().into())
+        }
     }
 
-    impl Default for RPCAgent {
-        fn default() -> Self {
-            Self::new()
+    #[tokio::test]
+    async fn test_batch_call() {
+        let hashes = DogecoinRPC::get_blockhashes(&MockAgent, "".to_string(), 5..9)
+            .await
+            .unwrap();
+        assert_eq!(hashes.len(), 4);
+        for (i, hash) in hashes.iter().enumerate() {
+            assert_eq!(hash[0], 5 + i as u8);
         }
+        assert!(
+            DogecoinRPC::get_blockhashes(&MockAgent, "".to_string(), 0..0)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let res: Vec<Result<String, RpcError>> = DogecoinRPC::batch_call(
+            &MockAgent,
+            "".to_string(),
+            &[
+                ("getblockhash", vec![1.into()]),
+                ("getblockhash", vec![101.into()]),
+                ("getblockhash", vec![2.into()]),
+            ],
+        )
+        .await
+        .unwrap();
+        assert!(res[0].is_ok());
+        assert_eq!(
+            res[1],
+            Err(RpcError::Node {
+                code: RPC_INVALID_PARAMETER,
+                message: "Block height out of range".to_string()
+            })
+        );
+        assert!(res[2].is_ok());
+        assert!(
+            DogecoinRPC::get_blockhashes(&MockAgent, "".to_string(), 99..102)
+                .await
+                .is_err()
+        );
     }
 
-    impl RPCAgent {
-        pub fn new() -> Self {
-            let rpcurl = std::env::var("RPC_URL").unwrap();
-            let rpcuser = std::env::var("RPC_USER").unwrap_or_default();
-            let rpcpassword = std::env::var("RPC_PASSWORD").unwrap_or_default();
-
-            let mut common_headers = header::HeaderMap::with_capacity(4);
-            common_headers.insert(header::ACCEPT, "application/json".parse().unwrap());
-            common_headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
-            common_headers.insert(header::ACCEPT_ENCODING, "gzip".parse().unwrap());
-
-            let url = reqwest::Url::parse(&rpcurl).unwrap();
-            if !rpcuser.is_empty() && !rpcpassword.is_empty() {
-                let auth = format!("{}:{}", rpcuser, rpcpassword);
-                let auth = format!(
-                    "Basic {}",
-                    base64::engine::general_purpose::STANDARD.encode(auth)
-                );
-                common_headers.insert(header::AUTHORIZATION, auth.parse().unwrap());
-            }
+    // answers every request with the same response
+    struct FixedAgent(String);
 
-            let client = ClientBuilder::new()
-                .use_rustls_tls()
-                .no_proxy()
-                .connect_timeout(Duration::from_secs(10))
-                .timeout(Duration::from_secs(30))
-                .user_agent(APP_AGENT)
-                .default_headers(common_headers)
-                .gzip(true)
-                .build()
-                .unwrap();
+    #[async_trait]
+    impl JsonRPCAgent for &FixedAgent {
+        async fn post(
+            &self,
+            _idempotency_key: String,
+            _body: Vec<u8>,
+        ) -> Result<bytes::Bytes, String> {
+            Ok(self.0.clone().into())
+        }
+    }
 
-            Self { client, url }
+    #[tokio::test]
+    async fn test_rpc_errors_and_results() {
+        let tx: Transaction = deserialize_hex("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff060340420f0103ffffffff0136bf775ee9000000232102a1b03b741b2f25549de39ee08df766395857b2459edf28675bddb119784c7db7ac00000000").unwrap();
+        for (code, check) in [
+            (-25, RpcError::is_missing_inputs as fn(&RpcError) -> bool),
+            (-26, RpcError::is_rejected),
+            (-27, RpcError::is_already_in_chain),
+        ] {
+            let res = format!(
+                r#"{{"result":null,"error":{{"code":{},"message":"tx error"}},"id":1}}"#,
+                code
+            );
+            let agent = FixedAgent(res);
+            let err = DogecoinRPC::send_transaction(&agent, "".to_string(), &tx)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err,
+                RpcError::Node {
+                    code,
+                    message: "tx error".to_string()
+                }
+            );
+            assert!(check(&err));
+            assert_eq!(err.to_string(), format!("RPC error {}: tx error", code));
         }
+
+        let agent = FixedAgent(r#"{"result":null,"error":null,"id":1}"#.to_string());
+        let txid = tx.compute_txid();
+        assert!(
+            DogecoinRPC::get_txout(&agent, "".to_string(), &txid, 0, true)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let agent = FixedAgent(
+            r#"{"result":{"bestblock":"9679d40a4a78d0570cb010b50bf4b801532dc3e286b7e99af5289a4553e6b315","confirmations":3,"value":1.5,"scriptPubKey":{"asm":"OP_DUP OP_HASH160 3224fd0571314c5959a075b9946d9f7218c01667 OP_EQUALVERIFY OP_CHECKSIG","hex":"76a9143224fd0571314c5959a075b9946d9f7218c0166788ac","reqSigs":1,"type":"pubkeyhash","addresses":["nYmJMro1rtZvHWm5a4WxTE77bGYtRYrfao"]},"coinbase":false},"error":null,"id":1}"#.to_string(),
+        );
+        let out = DogecoinRPC::get_txout(&agent, "".to_string(), &txid, 0, true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(out.confirmations, 3);
+        assert_eq!(out.script_pubkey.script_type, "pubkeyhash");
+        assert_eq!(out.script_pubkey.req_sigs, Some(1));
+
+        let agent = FixedAgent(
+            r#"{"result":[{"height":5240740,"hash":"62bba02e84a5b437fcccfacbec309623bfc7479f033e250f93339f981b4ca6c4","branchlen":0,"status":"active"}],"error":null,"id":1}"#.to_string(),
+        );
+        let tips = DogecoinRPC::get_chain_tips(&agent, "".to_string())
+            .await
+            .unwrap();
+        assert_eq!(tips[0].status, "active");
+
+        let agent =
+            FixedAgent(r#"{"result":{"feerate":0.01,"blocks":2},"error":null,"id":1}"#.to_string());
+        let fee = DogecoinRPC::estimate_smart_fee(&agent, "".to_string(), 2)
+            .await
+            .unwrap();
+        assert_eq!(fee.blocks, 2);
     }
 
+    // fails every request, like an agent that got an HTTP error without a JSON-RPC body
+    struct FailingAgent;
+
     #[async_trait]
-    impl JsonRPCAgent for &RPCAgent {
+    impl JsonRPCAgent for &FailingAgent {
         async fn post(
             &self,
             _idempotency_key: String,
-            body: Vec<u8>,
+            _body: Vec<u8>,
         ) -> Result<bytes::Bytes, String> {
-            let req = self.client.post(self.url.clone()).body(body);
-            let res = req.send().await.map_err(err_string)?;
-            if res.status().is_success() {
-                res.bytes().await.map_err(err_string)
-            } else {
-                Err(format!(
-                    "HTTP error: {}, {}",
-                    res.status(),
-                    res.text().await.unwrap()
-                ))
-            }
+            Err("status: 503, body: work queue depth exceeded".to_string())
         }
     }
 
+    #[tokio::test]
+    async fn test_rpc_error_bodies() {
+        // the body dogecoind sends with HTTP 500
+        let body =
+            r#"{"result":null,"error":{"code":-8,"message":"Block height out of range"},"id":1}"#;
+        assert!(is_rpc_error_body(body.as_bytes()));
+        assert!(!is_rpc_error_body(b"work queue depth exceeded"));
+        assert!(!is_rpc_error_body(
+            br#"{"result":"ok","error":null,"id":1}"#
+        ));
+
+        let agent = FixedAgent(body.to_string());
+        let err = DogecoinRPC::get_blockhash(&agent, "".to_string(), u64::MAX)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(RPC_INVALID_PARAMETER));
+
+        let err = DogecoinRPC::get_blockhash(&FailingAgent, "".to_string(), 1)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), None);
+        assert!(!err.is_missing_inputs());
+        assert!(String::from(err).contains("work queue depth exceeded"));
+    }
+
+    #[tokio::test]
+    async fn test_get_block_header() {
+        // mainnet block merge-mined after height 371337, with AuxPoW
+        let agent = FixedAgent(format!(
+            r#"{{"result":"{}","error":null,"id":1}}"#,
+            "040162000da1809fa62c133c8550bfd8b9cd10d4fe092bfde21e2878b1cb4e58af6a89bc832687bd8628066e1f0438bbb024fa0d887f8628481aa188235a13d5988b3bab2fb6dd641b3c011a0000000001000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4f03639426082f5669614254432f2cfabe6d6d2c1f86704524b309442ddaf53a0b12befc612d768b4061ef16f8c756d0ac8eae10000000000000001042e8fb01b6dd8dec3dc58ab82b00000000000000ffffffff0270084e25000000001976a914e16c28146ed4869c190b3f0bdc18d80d45f9213488ac0000000000000000266a24aa21a9edb157e30de8b33073d3306bc698a3a195af1ad403fd60039dce6949fd5c07f75400000000f8e994042c682997e6cbc8896cafc1272b0f7463e15de79c5c6dac28efc1399e07d9e698cf2defb464daad1ae9eafb4f8b322c4173aa42ebc416a266c04af1d1eac77feb6d1ecd018947547f09be57c71b5a124f8c92bb2e68f51ac188bdcc7dcd0b37b84bf014510523a0d947743663738cd9751725374a564ad84d8c2439a80297100afdaec455ff3bfd03dc6dbb2020392cffe6ad0c8aaf9df6ce6ac4fb00e81465b6291762e2637e44e6fee4da05e3f3daa1e0479795f8570389c61153ee4d2affa7b1efa60214706e02e0e2b6efb6e4a421dff1c1b3a1ce50b4cd153365c2a71619dc93c90d7ad10ee5c58343218020c497c82c43c8f1ca3a3e474eb31bea00000000040000000000000000000000000000000000000000000000000000000000000000e2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf97d24db2bfa41474bfb2f877d688fac5faa5e10a2808cf9de307370b93352e54894857d3e08918f70395d9206410fbfa942f1a889aa5ab8188ec33c2f6e207dc70800000000000020cb69a881eb9f5d7fd4bc222efd87cb2a9f5e6ffc415e53dd0e73366ab83c746d92bd765c621aba82551149c76600200f289f96e2994271ab9145d43648310d902fb6dd643ea1001a849aa3bf"
+        ));
+        let hash =
+            BlockHash::from_str("fb5f5b5b7d70e660c2c67bca8d3328afae32ae8bb4c8d6cbc42d96ff876b0859")
+                .unwrap();
+        let header = DogecoinRPC::get_block_header(&agent, "".to_string(), &hash)
+            .await
+            .unwrap();
+        assert_eq!(header.block_hash(), hash);
+        assert!(header.auxpow.is_some());
+        header
+            .header
+            .validate_pow(header.auxpow.as_ref(), &DOGE_MAIN_NET_CHAIN)
+            .unwrap();
+
+        // genesis block, without AuxPoW
+        let agent = FixedAgent(format!(
+            r#"{{"result":"{}","error":null,"id":1}}"#,
+            "010000000000000000000000000000000000000000000000000000000000000000000000696ad20e2dd4365c7459b4a4a5af743d5e92c6da3229e6532cd605f6533f2a5b24a6a152f0ff0f1e67860100"
+        ));
+        let header = DogecoinRPC::get_block_header(&agent, "".to_string(), &hash)
+            .await
+            .unwrap();
+        assert_eq!(
+            header.block_hash().to_string(),
+            "1a91e3dace36e2be3bf030a65679fe821aa1d6ef92e7c9902eb318182c355691"
+        );
+        assert!(header.auxpow.is_none());
+
+        let agent = FixedAgent(format!(
+            r#"{{"result":"{}00","error":null,"id":1}}"#,
+            "010000000000000000000000000000000000000000000000000000000000000000000000696ad20e2dd4365c7459b4a4a5af743d5e92c6da3229e6532cd605f6533f2a5b24a6a152f0ff0f1e67860100"
+        ));
+        assert!(DogecoinRPC::get_block_header(&agent, "".to_string(), &hash)
+            .await
+            .is_err());
+    }
+
     #[test]
     fn consensus_decode_hex_works() {
         let tx: Transaction = deserialize_hex(
@@ -287,12 +912,13 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[cfg(feature = "http")]
     #[tokio::test(flavor = "current_thread")]
     #[ignore]
     async fn rpc_works() {
         dotenv().expect(".env file not found");
 
-        let agent = RPCAgent::new();
+        let agent = HttpRPCAgent::from_env().unwrap();
         assert!(DogecoinRPC::ping(&agent, "".to_string()).await.is_ok());
 
         let best_blockhash = DogecoinRPC::get_best_blockhash(&agent, "".to_string())
@@ -306,12 +932,13 @@ mod tests {
         println!("block: {:?}", block);
     }
 
+    #[cfg(feature = "http")]
     #[tokio::test(flavor = "current_thread")]
     #[ignore]
     async fn check_some_blocks() {
         dotenv().expect(".env file not found");
 
-        let agent = RPCAgent::new();
+        let agent = HttpRPCAgent::from_env().unwrap();
         assert!(DogecoinRPC::ping(&agent, "".to_string()).await.is_ok());
 
         let mut height = 5240740u64;
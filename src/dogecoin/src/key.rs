@@ -0,0 +1,145 @@
+use bitcoin::base58;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey, Signing};
+use std::str::FromStr;
+
+use crate::chainparams::{chain_from_pkey_prefix, ChainParams};
+use crate::err_string;
+use crate::script::{p2pkh_address, Address};
+
+/// A secp256k1 private key with the network and public key encoding it is used with,
+/// encoded as WIF (Wallet Import Format).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PrivateKey {
+    pub chain: &'static ChainParams,
+    pub compressed: bool,
+    pub inner: SecretKey,
+}
+
+impl PrivateKey {
+    /// Creates a private key whose public key is used in compressed form.
+    pub fn new(inner: SecretKey, chain: &'static ChainParams) -> Self {
+        PrivateKey {
+            chain,
+            compressed: true,
+            inner,
+        }
+    }
+
+    pub fn new_uncompressed(inner: SecretKey, chain: &'static ChainParams) -> Self {
+        PrivateKey {
+            chain,
+            compressed: false,
+            inner,
+        }
+    }
+
+    /// Decodes a WIF key: <prefix> <32-byte key> [0x01 if compressed].
+    /// The network is detected from the prefix byte.
+    pub fn from_wif(wif: &str) -> Result<Self, String> {
+        let data =
+            base58::decode_check(wif).map_err(|_| "invalid base58 secret key".to_string())?;
+        let compressed = match data.len() {
+            33 => false,
+            34 if data[33] == 0x01 => true,
+            34 => return Err("invalid compression flag".to_string()),
+            n => return Err(format!("invalid WIF length: {}", n)),
+        };
+        let chain = chain_from_pkey_prefix(data[0])
+            .ok_or_else(|| format!("unknown WIF prefix: {:#04x}", data[0]))?;
+        let inner = SecretKey::from_slice(&data[1..33]).map_err(err_string)?;
+        Ok(PrivateKey {
+            chain,
+            compressed,
+            inner,
+        })
+    }
+
+    pub fn to_wif(&self) -> String {
+        let mut data = Vec::with_capacity(34);
+        data.push(self.chain.pkey_prefix);
+        data.extend_from_slice(&self.inner.secret_bytes());
+        if self.compressed {
+            data.push(0x01);
+        }
+        base58::encode_check(&data)
+    }
+
+    pub fn public_key<C: Signing>(&self, secp: &Secp256k1<C>) -> PublicKey {
+        self.inner.public_key(secp)
+    }
+
+    /// Public key serialized in the form selected by the compressed flag.
+    pub fn public_key_bytes<C: Signing>(&self, secp: &Secp256k1<C>) -> Vec<u8> {
+        let pk = self.public_key(secp);
+        if self.compressed {
+            pk.serialize().to_vec()
+        } else {
+            pk.serialize_uncompressed().to_vec()
+        }
+    }
+
+    pub fn p2pkh_address<C: Signing>(&self, secp: &Secp256k1<C>) -> Address {
+        p2pkh_address(&self.public_key_bytes(secp), self.chain).expect("valid public key")
+    }
+}
+
+impl std::fmt::Display for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_wif())
+    }
+}
+
+impl FromStr for PrivateKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PrivateKey::from_wif(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::*;
+
+    #[test]
+    fn test_wif_roundtrip() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let cases: [(&'static ChainParams, bool, &str); 6] = [
+            (&DOGE_MAIN_NET_CHAIN, true, "Q"),
+            (&DOGE_MAIN_NET_CHAIN, false, "6"),
+            (&DOGE_TEST_NET_CHAIN, true, "c"),
+            (&DOGE_TEST_NET_CHAIN, false, "9"),
+            (&DOGE_REG_TEST_CHAIN, true, "c"),
+            (&DOGE_REG_TEST_CHAIN, false, "9"),
+        ];
+        for (chain, compressed, first) in cases {
+            let key = PrivateKey {
+                chain,
+                compressed,
+                inner: sk,
+            };
+            let wif = key.to_wif();
+            assert!(wif.starts_with(first), "{} {}", chain.chain_name, wif);
+            let decoded: PrivateKey = wif.parse().unwrap();
+            assert_eq!(decoded, key);
+            assert_eq!(chain_from_wif(&wif).unwrap(), chain);
+            assert_eq!(
+                decoded.public_key_bytes(&secp).len(),
+                if compressed { 33 } else { 65 }
+            );
+            assert_eq!(
+                decoded.p2pkh_address(&secp).0[0],
+                chain.p2pkh_address_prefix
+            );
+        }
+
+        // bitcoin mainnet WIF
+        let btc = base58::encode_check(&[&[0x80][..], &[0x11; 32], &[0x01]].concat());
+        assert!(PrivateKey::from_wif(&btc).is_err());
+        assert!(chain_from_wif(&btc).is_err());
+        let bad_flag = base58::encode_check(&[&[0x9e][..], &[0x11; 32], &[0x02]].concat());
+        assert!(PrivateKey::from_wif(&bad_flag).is_err());
+    }
+}
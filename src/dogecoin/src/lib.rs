@@ -4,15 +4,39 @@ use bitcoin::{
 };
 use bitcoin_io::{Error, Read, Write};
 
+pub mod address;
 pub mod amount;
+pub mod asm;
+pub mod bip32;
 pub mod block;
+pub mod blockfile;
+pub mod bloom;
+pub mod builder;
+#[cfg(feature = "canister")]
 pub mod canister;
 pub mod chainparams;
+pub mod coinselect;
+pub mod headerchain;
+#[cfg(feature = "http")]
+pub mod httpagent;
+pub mod interpreter;
 pub mod jsonrpc;
+pub mod key;
+pub mod merkleblock;
+pub mod message;
+pub mod multisig;
 pub mod opcodes;
+pub mod p2p;
+pub mod policy;
+pub mod pow;
+pub mod psbt;
 pub mod script;
 pub mod sighash;
+pub mod timelock;
 pub mod transaction;
+#[cfg(feature = "serde")]
+pub mod verbose;
+pub mod wallet;
 
 pub extern crate hex;
 
@@ -36,7 +60,8 @@ where
 {
     let cap: VarInt = Decodable::consensus_decode(r)?;
     let cap = cap.0 as usize;
-    let mut vv = Vec::with_capacity(cap);
+    // the count is untrusted, don't preallocate more than a few items
+    let mut vv = Vec::with_capacity(cap.min(1024));
     for _ in 0..cap {
         vv.push(Decodable::consensus_decode_from_finite_reader(r)?);
     }
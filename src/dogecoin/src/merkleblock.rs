@@ -0,0 +1,442 @@
+// https://github.com/dogecoin/dogecoin/blob/master/src/merkleblock.cpp
+
+use bitcoin::consensus::{encode, Decodable, Encodable};
+use bitcoin::hashes::{sha256d, Hash, HashEngine};
+use bitcoin_io::{Error, Read, Write};
+
+use crate::block::{Block, BlockHeader, MerkleTx, TxMerkleNode};
+use crate::bloom::BloomFilter;
+use crate::transaction::Txid;
+
+/// Upper bound of transactions in a block: MAX_BLOCK_SIZE / minimum transaction size.
+pub const MAX_BLOCK_TRANSACTIONS: u32 = 1_000_000 / 60;
+
+/// Data structure that represents a partial merkle tree, as used by `gettxoutproof`
+/// and the `merkleblock` message.
+///
+/// It represents a subset of the txids of a known block, in a way that
+/// allows recovery of the list of txids, the merkle root and their positions.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct PartialMerkleTree {
+    /// The total number of transactions in the block
+    pub num_transactions: u32,
+    /// Node-is-parent-of-matched-txid bits
+    pub bits: Vec<bool>,
+    /// Transaction ids and internal hashes
+    pub hashes: Vec<TxMerkleNode>,
+}
+
+impl PartialMerkleTree {
+    /// Constructs a partial merkle tree from the block's `txids`,
+    /// where `matches[i]` marks whether `txids[i]` should be proven.
+    pub fn from_txids(txids: &[Txid], matches: &[bool]) -> Result<Self, String> {
+        if txids.is_empty() {
+            return Err("partial merkle tree has no transactions".to_string());
+        }
+        if txids.len() != matches.len() {
+            return Err(format!(
+                "partial merkle tree has {} txids but {} matches",
+                txids.len(),
+                matches.len()
+            ));
+        }
+
+        let mut pmt = PartialMerkleTree {
+            num_transactions: txids.len() as u32,
+            bits: Vec::with_capacity(txids.len()),
+            hashes: vec![],
+        };
+        let height = pmt.calc_tree_height();
+        pmt.traverse_and_build(height, 0, txids, matches);
+        Ok(pmt)
+    }
+
+    /// Extracts the matching txids and their positions in the block, returning the merkle root.
+    pub fn extract_matches(
+        &self,
+        matches: &mut Vec<Txid>,
+        indexes: &mut Vec<u32>,
+    ) -> Result<TxMerkleNode, String> {
+        matches.clear();
+        indexes.clear();
+        if self.num_transactions == 0 {
+            return Err("partial merkle tree has no transactions".to_string());
+        }
+        if self.num_transactions > MAX_BLOCK_TRANSACTIONS {
+            return Err("partial merkle tree has too many transactions".to_string());
+        }
+        if self.hashes.len() as u32 > self.num_transactions {
+            return Err("partial merkle tree has too many hashes".to_string());
+        }
+        // there must be at least one bit per node in the partial tree, and at least one node per hash
+        if self.bits.len() < self.hashes.len() {
+            return Err("partial merkle tree has not enough bits".to_string());
+        }
+
+        let height = self.calc_tree_height();
+        let mut bits_used = 0u32;
+        let mut hash_used = 0u32;
+        let root =
+            self.traverse_and_extract(height, 0, &mut bits_used, &mut hash_used, matches, indexes)?;
+        // Verify that all bits were consumed (except for the padding caused by serializing it as a byte sequence)
+        if (bits_used as usize).div_ceil(8) != self.bits.len().div_ceil(8) {
+            return Err("partial merkle tree has unused bits".to_string());
+        }
+        if hash_used as usize != self.hashes.len() {
+            return Err("partial merkle tree has unused hashes".to_string());
+        }
+        Ok(root)
+    }
+
+    fn calc_tree_height(&self) -> u32 {
+        let mut height = 0;
+        while self.calc_tree_width(height) > 1 {
+            height += 1;
+        }
+        height
+    }
+
+    fn calc_tree_width(&self, height: u32) -> u32 {
+        (self.num_transactions + (1 << height) - 1) >> height
+    }
+
+    fn calc_hash(&self, height: u32, pos: u32, txids: &[Txid]) -> TxMerkleNode {
+        if height == 0 {
+            return TxMerkleNode::from(txids[pos as usize].to_raw_hash());
+        }
+        let left = self.calc_hash(height - 1, pos * 2, txids);
+        let right = if pos * 2 + 1 < self.calc_tree_width(height - 1) {
+            self.calc_hash(height - 1, pos * 2 + 1, txids)
+        } else {
+            left
+        };
+        parent_hash(&left, &right)
+    }
+
+    fn traverse_and_build(&mut self, height: u32, pos: u32, txids: &[Txid], matches: &[bool]) {
+        let start = (pos << height) as usize;
+        let end = (((pos + 1) << height) as usize).min(self.num_transactions as usize);
+        let parent_of_match = matches[start..end].iter().any(|m| *m);
+        self.bits.push(parent_of_match);
+
+        if height == 0 || !parent_of_match {
+            let hash = self.calc_hash(height, pos, txids);
+            self.hashes.push(hash);
+        } else {
+            self.traverse_and_build(height - 1, pos * 2, txids, matches);
+            if pos * 2 + 1 < self.calc_tree_width(height - 1) {
+                self.traverse_and_build(height - 1, pos * 2 + 1, txids, matches);
+            }
+        }
+    }
+
+    fn traverse_and_extract(
+        &self,
+        height: u32,
+        pos: u32,
+        bits_used: &mut u32,
+        hash_used: &mut u32,
+        matches: &mut Vec<Txid>,
+        indexes: &mut Vec<u32>,
+    ) -> Result<TxMerkleNode, String> {
+        if *bits_used as usize >= self.bits.len() {
+            return Err("partial merkle tree overflowed the bits array".to_string());
+        }
+        let parent_of_match = self.bits[*bits_used as usize];
+        *bits_used += 1;
+
+        if height == 0 || !parent_of_match {
+            if *hash_used as usize >= self.hashes.len() {
+                return Err("partial merkle tree overflowed the hashes array".to_string());
+            }
+            let hash = self.hashes[*hash_used as usize];
+            *hash_used += 1;
+            if height == 0 && parent_of_match {
+                matches.push(Txid::from_raw_hash(hash.to_raw_hash()));
+                indexes.push(pos);
+            }
+            return Ok(hash);
+        }
+
+        let left =
+            self.traverse_and_extract(height - 1, pos * 2, bits_used, hash_used, matches, indexes)?;
+        let right = if pos * 2 + 1 < self.calc_tree_width(height - 1) {
+            let right = self.traverse_and_extract(
+                height - 1,
+                pos * 2 + 1,
+                bits_used,
+                hash_used,
+                matches,
+                indexes,
+            )?;
+            // The left and right branches should never be identical, as the transaction
+            // hashes covered by them must each be unique (CVE-2012-2459).
+            if right == left {
+                return Err("partial merkle tree has identical left and right hashes".to_string());
+            }
+            right
+        } else {
+            left
+        };
+        Ok(parent_hash(&left, &right))
+    }
+}
+
+fn parent_hash(left: &TxMerkleNode, right: &TxMerkleNode) -> TxMerkleNode {
+    let mut engine = sha256d::Hash::engine();
+    engine.input(left.as_ref());
+    engine.input(right.as_ref());
+    TxMerkleNode::from(sha256d::Hash::from_engine(engine))
+}
+
+impl Encodable for PartialMerkleTree {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.num_transactions.consensus_encode(w)?;
+        len += crate::consensus_encode_vec(&self.hashes, w)?;
+
+        let mut bytes: Vec<u8> = vec![0; self.bits.len().div_ceil(8)];
+        for (i, bit) in self.bits.iter().enumerate() {
+            bytes[i / 8] |= (*bit as u8) << (i % 8);
+        }
+        len += bytes.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for PartialMerkleTree {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let num_transactions: u32 = Decodable::consensus_decode_from_finite_reader(r)?;
+        let hashes: Vec<TxMerkleNode> = crate::consensus_decode_from_vec(r)?;
+        let bytes: Vec<u8> = Decodable::consensus_decode_from_finite_reader(r)?;
+        let mut bits: Vec<bool> = vec![false; bytes.len() * 8];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = (bytes[i / 8] & (1 << (i % 8))) != 0;
+        }
+        Ok(PartialMerkleTree {
+            num_transactions,
+            bits,
+            hashes,
+        })
+    }
+}
+
+/// Data structure that represents a `merkleblock` message or a `gettxoutproof` result:
+/// a block header (with its auxpow for merge-mined blocks) and a partial merkle tree.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MerkleBlock {
+    pub header: BlockHeader,
+    pub auxpow: Option<MerkleTx>,
+    pub txn: PartialMerkleTree,
+}
+
+impl MerkleBlock {
+    /// Creates a merkle block proving the transactions whose txids satisfy `match_txids`.
+    pub fn from_block_with_predicate<F>(block: &Block, match_txids: F) -> Result<Self, String>
+    where
+        F: Fn(&Txid) -> bool,
+    {
+        let txids: Vec<Txid> = block.txdata.iter().map(|tx| tx.compute_txid()).collect();
+        let matches: Vec<bool> = txids.iter().map(&match_txids).collect();
+        Ok(MerkleBlock {
+            header: block.header.clone(),
+            auxpow: block.auxpow.clone(),
+            txn: PartialMerkleTree::from_txids(&txids, &matches)?,
+        })
+    }
+
+    /// Creates a merkle block proving the transactions relevant to the bloom `filter`,
+    /// updating the filter as BIP37 requires. Also returns the matched (index, txid) pairs.
+    pub fn from_block_with_filter(
+        block: &Block,
+        filter: &mut BloomFilter,
+    ) -> Result<(Self, Vec<(u32, Txid)>), String> {
+        let mut txids = Vec::with_capacity(block.txdata.len());
+        let mut matches = Vec::with_capacity(block.txdata.len());
+        let mut matched = Vec::new();
+        for (i, tx) in block.txdata.iter().enumerate() {
+            let txid = tx.compute_txid();
+            let relevant = filter.is_relevant_and_update(tx);
+            if relevant {
+                matched.push((i as u32, txid));
+            }
+            txids.push(txid);
+            matches.push(relevant);
+        }
+        let mb = MerkleBlock {
+            header: block.header.clone(),
+            auxpow: block.auxpow.clone(),
+            txn: PartialMerkleTree::from_txids(&txids, &matches)?,
+        };
+        Ok((mb, matched))
+    }
+
+    /// Extracts the matching txids and their positions, checking that the partial merkle tree
+    /// commits to the header's merkle root, like `verifytxoutproof`.
+    pub fn extract_matches(
+        &self,
+        matches: &mut Vec<Txid>,
+        indexes: &mut Vec<u32>,
+    ) -> Result<(), String> {
+        let root = self.txn.extract_matches(matches, indexes)?;
+        if root != self.header.merkle_root {
+            return Err(format!(
+                "merkle root mismatch, expected {}, got {}",
+                self.header.merkle_root, root
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Encodable for MerkleBlock {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.header.consensus_encode(w)?;
+        if let Some(ref auxpow) = self.auxpow {
+            len += auxpow.consensus_encode(w)?;
+        }
+        len += self.txn.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for MerkleBlock {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let header: BlockHeader = Decodable::consensus_decode_from_finite_reader(r)?;
+        let auxpow = if header.is_auxpow() {
+            Some(Decodable::consensus_decode_from_finite_reader(r)?)
+        } else {
+            None
+        };
+        Ok(MerkleBlock {
+            header,
+            auxpow,
+            txn: Decodable::consensus_decode_from_finite_reader(r)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHash;
+    use crate::bloom::BLOOM_UPDATE_ALL;
+    use crate::transaction::{Transaction, TxIn, TxOut};
+    use bitcoin::ScriptBuf;
+    use hex::test_hex_unwrap as hex;
+
+    fn make_block(n: u32) -> Block {
+        let txdata: Vec<Transaction> = (0..n)
+            .map(|i| Transaction {
+                version: 1,
+                lock_time: i,
+                input: vec![TxIn::default()],
+                output: vec![TxOut {
+                    value: i as u64,
+                    script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+                }],
+            })
+            .collect();
+        let mut block = Block {
+            header: BlockHeader {
+                version: 1,
+                prev_blockhash: BlockHash::default(),
+                merkle_root: TxMerkleNode::default(),
+                time: 0,
+                bits: 0x207fffff,
+                nonce: 0,
+            },
+            auxpow: None,
+            txdata,
+        };
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        block
+    }
+
+    #[test]
+    fn test_partial_merkle_tree() {
+        for n in [1, 4, 7, 17, 56, 100] {
+            let block = make_block(n);
+            let txids: Vec<Txid> = block.txdata.iter().map(|tx| tx.compute_txid()).collect();
+            for step in [1, 2, 3, 7, 100] {
+                let matches: Vec<bool> = (0..n).map(|i| i % step == 0).collect();
+                let pmt = PartialMerkleTree::from_txids(&txids, &matches).unwrap();
+
+                let data = bitcoin::consensus::encode::serialize(&pmt);
+                let pmt2: PartialMerkleTree =
+                    bitcoin::consensus::encode::deserialize(&data).unwrap();
+
+                let mut matched = vec![];
+                let mut indexes = vec![];
+                let root = pmt2.extract_matches(&mut matched, &mut indexes).unwrap();
+                assert_eq!(root, block.header.merkle_root);
+                let expected: Vec<u32> = (0..n).filter(|i| i % step == 0).collect();
+                assert_eq!(indexes, expected);
+                let expected: Vec<Txid> = expected.iter().map(|i| txids[*i as usize]).collect();
+                assert_eq!(matched, expected);
+
+                // tampering with a hash breaks the root
+                let mut bad = pmt2.clone();
+                bad.hashes[0] = TxMerkleNode::from_byte_array([1; 32]);
+                if let Ok(root) = bad.extract_matches(&mut matched, &mut indexes) {
+                    assert_ne!(root, block.header.merkle_root);
+                }
+            }
+        }
+
+        let pmt = PartialMerkleTree::default();
+        assert!(pmt.extract_matches(&mut vec![], &mut vec![]).is_err());
+        assert!(PartialMerkleTree::from_txids(&[], &[]).is_err());
+        let txids: Vec<Txid> = make_block(2)
+            .txdata
+            .iter()
+            .map(|tx| tx.compute_txid())
+            .collect();
+        assert!(PartialMerkleTree::from_txids(&txids, &[true]).is_err());
+    }
+
+    #[test]
+    fn test_merkle_block() {
+        // https://github.com/rust-bitcoin/rust-bitcoin/blob/master/bitcoin/src/merkle_tree/block.rs
+        let data = hex!("0100000079cda856b143d9db2c1caff01d1aecc8630d30625d10e8b4b8b0000000000000b50cc069d6a3e33e3ff84a5c41d9d3febe7c770fdcc96b2c3ff60abe184f196367291b4d4c86041b8fa45d630100000001b50cc069d6a3e33e3ff84a5c41d9d3febe7c770fdcc96b2c3ff60abe184f19630101");
+        let mb: MerkleBlock = bitcoin::consensus::encode::deserialize(&data).unwrap();
+        assert!(mb.auxpow.is_none());
+        let mut matched = vec![];
+        let mut indexes = vec![];
+        mb.extract_matches(&mut matched, &mut indexes).unwrap();
+        assert_eq!(indexes, vec![0]);
+        assert_eq!(
+            matched[0].to_string(),
+            "63194f18be0af63f2c6bc9dc0f777cbefed3d9415c4af83f3ee3a3d669c00cb5"
+        );
+        assert_eq!(bitcoin::consensus::encode::serialize(&mb), data);
+
+        let mut bad = mb.clone();
+        bad.header.merkle_root = TxMerkleNode::default();
+        assert!(bad.extract_matches(&mut matched, &mut indexes).is_err());
+    }
+
+    #[test]
+    fn test_merkle_block_from_block() {
+        let block = make_block(9);
+        let target = block.txdata[5].compute_txid();
+        let mb = MerkleBlock::from_block_with_predicate(&block, |txid| *txid == target).unwrap();
+        let mut matched = vec![];
+        let mut indexes = vec![];
+        mb.extract_matches(&mut matched, &mut indexes).unwrap();
+        assert_eq!(matched, vec![target]);
+        assert_eq!(indexes, vec![5]);
+
+        let mut filter = BloomFilter::new(10, 0.000001, 0, BLOOM_UPDATE_ALL);
+        filter.insert(target.as_ref());
+        let (mb, matched_txs) = MerkleBlock::from_block_with_filter(&block, &mut filter).unwrap();
+        assert_eq!(matched_txs, vec![(5, target)]);
+        mb.extract_matches(&mut matched, &mut indexes).unwrap();
+        assert_eq!(matched, vec![target]);
+    }
+}
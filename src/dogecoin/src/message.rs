@@ -0,0 +1,155 @@
+use base64::{engine::general_purpose::STANDARD as base64_std, Engine};
+use bitcoin::consensus::Encodable;
+use bitcoin::hashes::{sha256d, Hash, HashEngine};
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, Signing, Verification};
+use bitcoin::sign_message::MessageSignature;
+use bitcoin::VarInt;
+
+use crate::chainparams::ChainParams;
+use crate::err_string;
+use crate::key::PrivateKey;
+use crate::script::{p2pkh_address, Address};
+
+// https://github.com/dogecoin/dogecoin/blob/master/src/validation.cpp#L101
+pub const MESSAGE_MAGIC: &str = "Dogecoin Signed Message:\n";
+
+/// Hash signed by `signmessage`: sha256d(varstr(magic) || varstr(message)).
+pub fn message_hash(message: &str) -> sha256d::Hash {
+    let mut engine = sha256d::Hash::engine();
+    for data in [MESSAGE_MAGIC.as_bytes(), message.as_bytes()] {
+        VarInt::from(data.len())
+            .consensus_encode(&mut engine)
+            .expect("engines don't error");
+        engine.input(data);
+    }
+    sha256d::Hash::from_engine(engine)
+}
+
+/// Signs a message like Dogecoin Core's `signmessage`, returning the base64 encoded
+/// 65-byte compact recoverable signature.
+pub fn sign_message<C: Signing>(secp: &Secp256k1<C>, key: &PrivateKey, message: &str) -> String {
+    let msg = Message::from_digest(message_hash(message).to_byte_array());
+    let sig = secp.sign_ecdsa_recoverable(&msg, &key.inner);
+    base64_std.encode(MessageSignature::new(sig, key.compressed).serialize())
+}
+
+/// Recovers the signer's public key and whether it was compressed from a base64 signature.
+pub fn recover_pubkey<C: Verification>(
+    secp: &Secp256k1<C>,
+    signature: &str,
+    message: &str,
+) -> Result<(PublicKey, bool), String> {
+    let data = base64_std
+        .decode(signature)
+        .map_err(|_| "malformed base64 encoding".to_string())?;
+    let sig = MessageSignature::from_slice(&data).map_err(err_string)?;
+    let pk = sig
+        .recover_pubkey(secp, message_hash(message))
+        .map_err(err_string)?;
+    Ok((pk.inner, pk.compressed))
+}
+
+/// Verifies a signature like Dogecoin Core's `verifymessage`.
+/// Only P2PKH addresses of the given chain can sign messages.
+pub fn verify_message<C: Verification>(
+    secp: &Secp256k1<C>,
+    chain: &ChainParams,
+    address: &Address,
+    signature: &str,
+    message: &str,
+) -> Result<bool, String> {
+    if !address.is_p2pkh(chain) {
+        return Err("address does not refer to a key".to_string());
+    }
+    let (pk, compressed) = recover_pubkey(secp, signature, message)?;
+    let recovered = if compressed {
+        p2pkh_address(&pk.serialize(), chain)?
+    } else {
+        p2pkh_address(&pk.serialize_uncompressed(), chain)?
+    };
+    Ok(&recovered == address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::*;
+    use bitcoin::secp256k1::SecretKey;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0x42; 32]).unwrap();
+        for chain in [&DOGE_MAIN_NET_CHAIN, &DOGE_TEST_NET_CHAIN] {
+            for key in [
+                PrivateKey::new(sk, chain),
+                PrivateKey::new_uncompressed(sk, chain),
+            ] {
+                let addr = key.p2pkh_address(&secp);
+                let sig = sign_message(&secp, &key, "hello dogecoin");
+                assert_eq!(base64_std.decode(&sig).unwrap().len(), 65);
+                assert!(verify_message(&secp, chain, &addr, &sig, "hello dogecoin").unwrap());
+                assert!(!verify_message(&secp, chain, &addr, &sig, "hello dogecoin!").unwrap());
+                let (pk, compressed) = recover_pubkey(&secp, &sig, "hello dogecoin").unwrap();
+                assert_eq!(pk, key.public_key(&secp));
+                assert_eq!(compressed, key.compressed);
+
+                // the compressed and uncompressed keys have different addresses
+                let other = PrivateKey {
+                    compressed: !key.compressed,
+                    ..key.clone()
+                };
+                assert!(!verify_message(
+                    &secp,
+                    chain,
+                    &other.p2pkh_address(&secp),
+                    &sig,
+                    "hello dogecoin"
+                )
+                .unwrap());
+            }
+        }
+
+        let key = PrivateKey::new(sk, &DOGE_MAIN_NET_CHAIN);
+        let sig = sign_message(&secp, &key, "");
+        let p2sh =
+            crate::script::hash160_to_address(&[0u8; 20], DOGE_MAIN_NET_CHAIN.p2sh_address_prefix);
+        assert!(verify_message(&secp, &DOGE_MAIN_NET_CHAIN, &p2sh, &sig, "").is_err());
+        assert!(recover_pubkey(&secp, "not base64!", "").is_err());
+        assert!(recover_pubkey(&secp, &base64_std.encode([0u8; 64]), "").is_err());
+    }
+
+    #[test]
+    fn test_known_answer() {
+        // Dogecoin Core signs with RFC 6979 nonces and low S values, so signmessage is
+        // deterministic. These vectors were computed with an independent Python
+        // implementation of CMessageSigner::SignMessage, not taken from dogecoin-cli.
+        let secp = Secp256k1::new();
+        let message = "Much wow, very signed";
+        for (wif, addr, pubkey, sig) in [
+            (
+                "QW6ct2xeTzjkjZLs6zvSnvR8QWBwD5c1Ukj9r3vK5PzDtfvyapjp",
+                "DHxsQ5ahmiuvzFdpkSLn1VUPRjWRJttMsz",
+                "022d210b88aaf5b8478654e41fe527a7cf105d7b9487f7eac6502b78785c8ffcce",
+                "IOoE3g9EzANtFml/ShCuZUGJO+LI1At1WVvbmE5KmpW/B5RfXFDu8DMZa1aPtN1gSgq/PVnGImCN2Q5LP8TIk1Y=",
+            ),
+            (
+                "6KpwoTGnC6qCipCkXMpuWtXPXFKLXqAkA1Ph4s41vZPw8HS62Dk",
+                "DMLHVPMfUQQZf6WqCvwTvN1eNNt2k9N5jR",
+                "042d210b88aaf5b8478654e41fe527a7cf105d7b9487f7eac6502b78785c8ffccea5def292aae08c2a7dce0e666de70ce8af282a6fdd8c89893af5f6c2a8533734",
+                "HOoE3g9EzANtFml/ShCuZUGJO+LI1At1WVvbmE5KmpW/B5RfXFDu8DMZa1aPtN1gSgq/PVnGImCN2Q5LP8TIk1Y=",
+            ),
+        ] {
+            let key = PrivateKey::from_wif(wif).unwrap();
+            let address = Address::parse(addr, &DOGE_MAIN_NET_CHAIN).unwrap();
+            assert_eq!(key.p2pkh_address(&secp), address);
+            assert_eq!(sign_message(&secp, &key, message), sig);
+            assert!(verify_message(&secp, &DOGE_MAIN_NET_CHAIN, &address, sig, message).unwrap());
+
+            let (pk, compressed) = recover_pubkey(&secp, sig, message).unwrap();
+            assert_eq!(pk, PublicKey::from_str(pubkey).unwrap());
+            assert_eq!(compressed, key.compressed);
+        }
+    }
+}
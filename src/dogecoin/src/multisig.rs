@@ -0,0 +1,305 @@
+use bitcoin::secp256k1::{PublicKey, Secp256k1};
+use std::collections::BTreeMap;
+
+use crate::chainparams::ChainParams;
+use crate::interpreter::MAX_SCRIPT_ELEMENT_SIZE;
+use crate::opcodes::*;
+use crate::script::{get_op, p2sh_address, Address, PushBytes, Script, ScriptBuf};
+use crate::sighash::{SighashCache, SighashSignature};
+use crate::transaction::Transaction;
+
+/// Maximum number of public keys that can be encoded with OP_1..OP_16.
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// An m-of-n multisig redeem script: OP_m <pubkey*n> OP_n OP_CHECKMULTISIG
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MultiSig {
+    pub required: usize,
+    pub pubkeys: Vec<PublicKey>,
+}
+
+impl MultiSig {
+    pub fn new(required: usize, pubkeys: Vec<PublicKey>) -> Result<Self, String> {
+        if pubkeys.is_empty() || pubkeys.len() > MAX_MULTISIG_KEYS {
+            return Err(format!(
+                "multisig: invalid number of pubkeys {}, expected 1..={}",
+                pubkeys.len(),
+                MAX_MULTISIG_KEYS
+            ));
+        }
+        if required == 0 || required > pubkeys.len() {
+            return Err(format!(
+                "multisig: invalid required signatures {}, expected 1..={}",
+                required,
+                pubkeys.len()
+            ));
+        }
+        let ms = MultiSig { required, pubkeys };
+        // compressed keys are 34 bytes in the script, so at most 15 of them fit in a P2SH redeem script
+        if ms.redeem_script().len() > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err("multisig: redeem script exceeds 520 bytes".to_string());
+        }
+        Ok(ms)
+    }
+
+    /// Creates a multisig with the public keys sorted lexicographically (BIP67),
+    /// so that co-signers derive the same address regardless of key order.
+    pub fn new_sorted(required: usize, mut pubkeys: Vec<PublicKey>) -> Result<Self, String> {
+        pubkeys.sort_by_key(|pk| pk.serialize());
+        Self::new(required, pubkeys)
+    }
+
+    pub fn from_redeem_script(script: &Script) -> Result<Self, String> {
+        let data = script.as_bytes();
+        let mut pc = 0;
+        let mut ops = Vec::new();
+        while pc < data.len() {
+            ops.push(get_op(data, &mut pc).ok_or("multisig: bad opcode")?);
+        }
+        if ops.len() < 4 || ops[ops.len() - 1].0 != OP_CHECKMULTISIG {
+            return Err("multisig: not a multisig script".to_string());
+        }
+        let required = decode_small_int(ops[0].0)?;
+        let n = decode_small_int(ops[ops.len() - 2].0)?;
+        let keys = &ops[1..ops.len() - 2];
+        if keys.len() != n {
+            return Err("multisig: pubkey count mismatch".to_string());
+        }
+        let pubkeys = keys
+            .iter()
+            .map(|(_, key)| PublicKey::from_slice(key).map_err(|err| format!("multisig: {}", err)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ms = Self::new(required, pubkeys)?;
+        // reject scripts that re-encode differently, e.g. uncompressed vs compressed keys
+        if ms.redeem_script().as_script() != script {
+            return Err("multisig: non-canonical redeem script".to_string());
+        }
+        Ok(ms)
+    }
+
+    pub fn redeem_script(&self) -> ScriptBuf {
+        let mut script = ScriptBuf::new();
+        script.push_opcode(encode_small_int(self.required).into());
+        for pk in &self.pubkeys {
+            script.push_slice(pk.serialize());
+        }
+        script.push_opcode(encode_small_int(self.pubkeys.len()).into());
+        script.push_opcode(OP_CHECKMULTISIG.into());
+        script
+    }
+
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        ScriptBuf::new_p2sh(&self.redeem_script().script_hash())
+    }
+
+    pub fn address(&self, chain: &ChainParams) -> Address {
+        p2sh_address(self.redeem_script().as_bytes(), chain).unwrap()
+    }
+
+    /// Verifies a signature for the input against each public key,
+    /// returns the index of the matching key.
+    pub fn match_signature(
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+        sig: &SighashSignature,
+    ) -> Result<Option<usize>, String> {
+        let sighasher = SighashCache::new(tx);
+        let msg = sighasher.signature_hash(input_index, &self.redeem_script(), sig.sighash_type)?;
+        let secp = Secp256k1::verification_only();
+        Ok(self
+            .pubkeys
+            .iter()
+            .position(|pk| secp.verify_ecdsa(&msg.into(), &sig.signature, pk).is_ok()))
+    }
+
+    /// Extracts the valid signatures from a (partial) multisig scriptSig of the input,
+    /// keyed by the index of the public key that signed.
+    pub fn extract_signatures(
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+        script_sig: &Script,
+    ) -> Result<BTreeMap<usize, SighashSignature>, String> {
+        let data = script_sig.as_bytes();
+        let mut pc = 0;
+        let mut sigs = BTreeMap::new();
+        while pc < data.len() {
+            let (opcode, push) = get_op(data, &mut pc).ok_or("multisig: bad opcode")?;
+            if opcode > OP_PUSHDATA4 {
+                return Err("multisig: scriptSig is not push only".to_string());
+            }
+            // skip the dummy, placeholders and the redeem script
+            if let Ok(sig) = SighashSignature::from_slice(push) {
+                if let Some(i) = self.match_signature(tx, input_index, &sig)? {
+                    sigs.insert(i, sig);
+                }
+            }
+        }
+        Ok(sigs)
+    }
+
+    /// Builds the P2SH scriptSig: OP_0 <sig*m> <redeem script>.
+    /// Signatures must be keyed by public key index, the first m are used in key order.
+    /// Missing signatures are filled with OP_0 placeholders, as Dogecoin Core does for partial scripts.
+    pub fn script_sig(&self, sigs: &BTreeMap<usize, SighashSignature>) -> ScriptBuf {
+        let mut script = ScriptBuf::new();
+        // A bug in OP_CHECKMULTISIG consumes one extra stack element.
+        script.push_opcode(OP_0.into());
+        for sig in sigs.values().take(self.required) {
+            script.push_slice(sig.serialize());
+        }
+        for _ in sigs.len()..self.required {
+            script.push_opcode(OP_0.into());
+        }
+        script.push_slice(<&PushBytes>::try_from(self.redeem_script().as_bytes()).unwrap());
+        script
+    }
+
+    /// Combines partial scriptSigs of the input from several co-signers into one,
+    /// returns the scriptSig and whether it has enough signatures.
+    pub fn combine_script_sigs(
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+        script_sigs: &[&Script],
+    ) -> Result<(ScriptBuf, bool), String> {
+        let mut sigs = BTreeMap::new();
+        for script_sig in script_sigs {
+            sigs.extend(self.extract_signatures(tx, input_index, script_sig)?);
+        }
+        let complete = sigs.len() >= self.required;
+        Ok((self.script_sig(&sigs), complete))
+    }
+}
+
+fn encode_small_int(n: usize) -> u8 {
+    debug_assert!((1..=16).contains(&n));
+    OP_1 + (n as u8) - 1
+}
+
+fn decode_small_int(opcode: u8) -> Result<usize, String> {
+    if (OP_1..=OP_16).contains(&opcode) {
+        Ok((opcode - OP_1 + 1) as usize)
+    } else {
+        Err("multisig: expected OP_1..OP_16".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::DOGE_MAIN_NET_CHAIN;
+    use crate::interpreter::{verify_input, STANDARD_SCRIPT_VERIFY_FLAGS};
+    use crate::sighash::{EcdsaSighashType, SecretKey};
+    use crate::transaction::{OutPoint, TxIn, TxOut};
+    use hex::test_hex_unwrap as hex;
+
+    #[test]
+    fn test_multisig_redeem_script() {
+        // 2-of-2 P2SH multisig spend, from the block in block::tests::test_block
+        let tx_data = hex!("0100000001892bc2522115e7bbf227a93aa5708e22b621616a398ab4c7119237b1da0b37d84f000000da00483045022100c5163a753aa37f9dfed708ed9167474f2b10a2b57238b00a95b3edb4f302d3fe022073fdc9c7a2147c79fb04e121a0aa64ce82901e31d15f65fa9c7a0633f2923112014730440220368808f54756249843c30cbf9ff964d1eddf4473f13352084dc1ab2229209e3302202578414b8fa035ffb58c861e965f0bcb9111636eedd173a936a4a326c138259d0147522102e60e9ac8a490768b3cd74fa55acf36d92491012c46dda806367f6dd567ac7b012103e5ed64e1c73f6d341d2a36d3f987a3b325117843f65afc24a616879047c7971652ae000000000200ca9a3b000000001976a914c7935d8f471d3472d31b6c5682f4bc2aaf2b806488acc54b563e0000000017a914f033fcfbdeafc8d9547c9015e021375694c316008700000000");
+        let tx = Transaction::try_from(&tx_data[..]).unwrap();
+        let redeem_script = hex!("522102e60e9ac8a490768b3cd74fa55acf36d92491012c46dda806367f6dd567ac7b012103e5ed64e1c73f6d341d2a36d3f987a3b325117843f65afc24a616879047c7971652ae");
+        let ms = MultiSig::from_redeem_script(Script::from_bytes(&redeem_script)).unwrap();
+        assert_eq!(ms.required, 2);
+        assert_eq!(ms.pubkeys.len(), 2);
+        assert_eq!(ms.redeem_script().as_bytes(), &redeem_script[..]);
+        assert_eq!(
+            ms.address(&DOGE_MAIN_NET_CHAIN)
+                .try_to_script(&DOGE_MAIN_NET_CHAIN)
+                .unwrap(),
+            ms.script_pubkey()
+        );
+
+        let sigs = ms.extract_signatures(&tx, 0, &tx.input[0].script).unwrap();
+        assert_eq!(sigs.keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(ms.script_sig(&sigs), tx.input[0].script);
+
+        // split into partial scriptSigs and combine them back
+        let partial0 = ms.script_sig(&BTreeMap::from([(0, sigs[&0])]));
+        let partial1 = ms.script_sig(&BTreeMap::from([(1, sigs[&1])]));
+        let (script, complete) = ms.combine_script_sigs(&tx, 0, &[&partial0]).unwrap();
+        assert!(!complete);
+        assert_eq!(script, partial0);
+        let (script, complete) = ms
+            .combine_script_sigs(&tx, 0, &[&partial1, &partial0])
+            .unwrap();
+        assert!(complete);
+        assert_eq!(script, tx.input[0].script);
+
+        assert!(MultiSig::from_redeem_script(Script::from_bytes(&redeem_script[1..])).is_err());
+        assert!(MultiSig::new(3, ms.pubkeys.clone()).is_err());
+        assert!(MultiSig::new(0, ms.pubkeys.clone()).is_err());
+    }
+
+    #[test]
+    fn test_multisig_sign() {
+        let secp = Secp256k1::new();
+        let keys: Vec<SecretKey> = (1..=3u8)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        let pubkeys: Vec<PublicKey> = keys.iter().map(|k| k.public_key(&secp)).collect();
+        let ms = MultiSig::new_sorted(2, pubkeys.clone()).unwrap();
+        assert_eq!(
+            ms,
+            MultiSig::new_sorted(2, pubkeys.into_iter().rev().collect()).unwrap()
+        );
+
+        let mut tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn::with_outpoint(OutPoint::default())],
+            output: vec![TxOut {
+                value: 100_000_000,
+                script_pubkey: ms.script_pubkey(),
+            }],
+        };
+        let sign = |tx: &Transaction, key: &SecretKey| {
+            let sighasher = SighashCache::new(tx);
+            let msg = sighasher
+                .signature_hash(0, &ms.redeem_script(), EcdsaSighashType::All)
+                .unwrap();
+            let signature = secp.sign_ecdsa(&msg.into(), key);
+            let sig = SighashSignature {
+                signature,
+                sighash_type: EcdsaSighashType::All,
+            };
+            let i = ms.match_signature(tx, 0, &sig).unwrap().unwrap();
+            ms.script_sig(&BTreeMap::from([(i, sig)]))
+        };
+
+        // each co-signer signs the unsigned transaction
+        let partial2 = sign(&tx, &keys[2]);
+        let partial0 = sign(&tx, &keys[0]);
+        let (script, complete) = ms
+            .combine_script_sigs(&tx, 0, &[&partial2, &partial0])
+            .unwrap();
+        assert!(complete);
+        tx.input[0].script = script;
+        verify_input(&tx, 0, &ms.script_pubkey(), STANDARD_SCRIPT_VERIFY_FLAGS).unwrap();
+
+        // a single signature is not enough
+        let mut partial_tx = tx.clone();
+        partial_tx.input[0].script = partial2.clone();
+        assert!(verify_input(
+            &partial_tx,
+            0,
+            &ms.script_pubkey(),
+            STANDARD_SCRIPT_VERIFY_FLAGS
+        )
+        .is_err());
+
+        let mut sigs = ms.extract_signatures(&tx, 0, &partial2).unwrap();
+        sigs.extend(ms.extract_signatures(&tx, 0, &partial0).unwrap());
+        let mut cache = SighashCache::new(&mut partial_tx);
+        cache
+            .set_input_multisig_script(
+                0,
+                &sigs.into_values().collect::<Vec<_>>(),
+                &ms.redeem_script(),
+            )
+            .unwrap();
+        assert_eq!(partial_tx, tx);
+    }
+}
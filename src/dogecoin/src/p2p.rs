@@ -0,0 +1,784 @@
+use bitcoin::consensus::{encode, Decodable, Encodable};
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::VarInt;
+use bitcoin_io::{Error, Read, Write};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+use crate::block::{Block, BlockHash, BlockHeader, MerkleTx};
+use crate::chainparams::ChainParams;
+use crate::consensus_encode_vec;
+use crate::transaction::{Transaction, Txid};
+
+/// Protocol version of Dogecoin Core 1.14.
+pub const PROTOCOL_VERSION: u32 = 70015;
+/// magic(4) + command(12) + payload length(4) + checksum(4)
+pub const HEADER_SIZE: usize = 24;
+pub const COMMAND_SIZE: usize = 12;
+/// Maximum payload size accepted by the codec, Core's MAX_SIZE.
+pub const MAX_PAYLOAD_SIZE: usize = 0x02000000;
+pub const MAX_INV_SIZE: usize = 50_000;
+pub const MAX_HEADERS_RESULTS: usize = 2000;
+pub const MAX_ADDR_SIZE: usize = 1000;
+
+pub const NODE_NETWORK: u64 = 1;
+pub const NODE_BLOOM: u64 = 1 << 2;
+
+pub const REJECT_MALFORMED: u8 = 0x01;
+pub const REJECT_INVALID: u8 = 0x10;
+pub const REJECT_OBSOLETE: u8 = 0x11;
+pub const REJECT_DUPLICATE: u8 = 0x12;
+pub const REJECT_NONSTANDARD: u8 = 0x40;
+pub const REJECT_DUST: u8 = 0x41;
+pub const REJECT_INSUFFICIENTFEE: u8 = 0x42;
+pub const REJECT_CHECKPOINT: u8 = 0x43;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum P2PError {
+    /// The message is not complete, at least this many bytes are needed.
+    Incomplete(usize),
+    WrongMagic([u8; 4]),
+    InvalidCommand,
+    PayloadTooLarge(usize),
+    Checksum,
+    Decode {
+        command: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for P2PError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            P2PError::Incomplete(size) => write!(f, "incomplete message, need {} bytes", size),
+            P2PError::WrongMagic(magic) => write!(f, "wrong network magic {:02x?}", magic),
+            P2PError::InvalidCommand => write!(f, "invalid command"),
+            P2PError::PayloadTooLarge(size) => write!(f, "payload size {} is too large", size),
+            P2PError::Checksum => write!(f, "invalid payload checksum"),
+            P2PError::Decode { command, reason } => {
+                write!(f, "failed to decode {} message: {}", command, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for P2PError {}
+
+/// A network address without the timestamp, as used in the version message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct NetAddress {
+    pub services: u64,
+    /// IPv6 address, IPv4 addresses are IPv4-mapped.
+    pub ip: Ipv6Addr,
+    pub port: u16,
+}
+
+impl NetAddress {
+    pub fn new(addr: &SocketAddr, services: u64) -> Self {
+        let ip = match addr.ip() {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            IpAddr::V6(ip) => ip,
+        };
+        NetAddress {
+            services,
+            ip,
+            port: addr.port(),
+        }
+    }
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        match self.ip.to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(IpAddr::V4(ip), self.port),
+            None => SocketAddr::new(IpAddr::V6(self.ip), self.port),
+        }
+    }
+}
+
+impl Encodable for NetAddress {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.services.consensus_encode(w)?;
+        len += self.ip.octets().consensus_encode(w)?;
+        // the port is big-endian
+        len += self.port.to_be_bytes().consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for NetAddress {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        Ok(NetAddress {
+            services: Decodable::consensus_decode_from_finite_reader(r)?,
+            ip: Ipv6Addr::from(<[u8; 16]>::consensus_decode_from_finite_reader(r)?),
+            port: u16::from_be_bytes(Decodable::consensus_decode_from_finite_reader(r)?),
+        })
+    }
+}
+
+/// An entry of the addr message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct TimedNetAddress {
+    pub time: u32,
+    pub addr: NetAddress,
+}
+
+impl Encodable for TimedNetAddress {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.time.consensus_encode(w)?;
+        len += self.addr.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for TimedNetAddress {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        Ok(TimedNetAddress {
+            time: Decodable::consensus_decode_from_finite_reader(r)?,
+            addr: Decodable::consensus_decode_from_finite_reader(r)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VersionMessage {
+    pub version: u32,
+    pub services: u64,
+    pub timestamp: i64,
+    pub receiver: NetAddress,
+    pub sender: NetAddress,
+    /// Random nonce used to detect connections to self.
+    pub nonce: u64,
+    pub user_agent: String,
+    pub start_height: i32,
+    /// Whether the peer should relay transactions before a filter is set (BIP37).
+    pub relay: bool,
+}
+
+impl Encodable for VersionMessage {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.version.consensus_encode(w)?;
+        len += self.services.consensus_encode(w)?;
+        len += self.timestamp.consensus_encode(w)?;
+        len += self.receiver.consensus_encode(w)?;
+        len += self.sender.consensus_encode(w)?;
+        len += self.nonce.consensus_encode(w)?;
+        len += self.user_agent.consensus_encode(w)?;
+        len += self.start_height.consensus_encode(w)?;
+        len += self.relay.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Inventory {
+    Error,
+    Tx(Txid),
+    Block(BlockHash),
+    FilteredBlock(BlockHash),
+    Unknown { inv_type: u32, hash: [u8; 32] },
+}
+
+impl Encodable for Inventory {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let (inv_type, hash) = match self {
+            Inventory::Error => (0u32, [0u8; 32]),
+            Inventory::Tx(txid) => (1, txid.to_byte_array()),
+            Inventory::Block(hash) => (2, hash.to_byte_array()),
+            Inventory::FilteredBlock(hash) => (3, hash.to_byte_array()),
+            Inventory::Unknown { inv_type, hash } => (*inv_type, *hash),
+        };
+        Ok(inv_type.consensus_encode(w)? + hash.consensus_encode(w)?)
+    }
+}
+
+impl Decodable for Inventory {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let inv_type = u32::consensus_decode_from_finite_reader(r)?;
+        let hash = <[u8; 32]>::consensus_decode_from_finite_reader(r)?;
+        Ok(match inv_type {
+            0 => Inventory::Error,
+            1 => Inventory::Tx(Txid::from_byte_array(hash)),
+            2 => Inventory::Block(BlockHash::from_byte_array(hash)),
+            3 => Inventory::FilteredBlock(BlockHash::from_byte_array(hash)),
+            _ => Inventory::Unknown { inv_type, hash },
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GetHeadersMessage {
+    pub version: u32,
+    /// Block hashes from the tip back to genesis, dense at first then exponentially sparse.
+    pub locator_hashes: Vec<BlockHash>,
+    /// The last header to return, all zeros for as many as possible.
+    pub stop_hash: BlockHash,
+}
+
+impl Encodable for GetHeadersMessage {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.version.consensus_encode(w)?;
+        len += consensus_encode_vec(&self.locator_hashes, w)?;
+        len += self.stop_hash.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for GetHeadersMessage {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        Ok(GetHeadersMessage {
+            version: Decodable::consensus_decode_from_finite_reader(r)?,
+            locator_hashes: decode_vec(r, MAX_INV_SIZE)?,
+            stop_hash: Decodable::consensus_decode_from_finite_reader(r)?,
+        })
+    }
+}
+
+/// A header of the headers message, merge-mined headers carry their AuxPoW proof.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AuxPowHeader {
+    pub header: BlockHeader,
+    pub auxpow: Option<MerkleTx>,
+}
+
+impl AuxPowHeader {
+    pub fn block_hash(&self) -> BlockHash {
+        self.header.block_hash()
+    }
+}
+
+impl From<&Block> for AuxPowHeader {
+    fn from(block: &Block) -> Self {
+        AuxPowHeader {
+            header: block.header.clone(),
+            auxpow: block.auxpow.clone(),
+        }
+    }
+}
+
+impl Encodable for AuxPowHeader {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.header.consensus_encode(w)?;
+        if let Some(ref auxpow) = self.auxpow {
+            len += auxpow.consensus_encode(w)?;
+        }
+        // headers are sent as blocks without transactions
+        len += VarInt(0).consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for AuxPowHeader {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let header = BlockHeader::consensus_decode_from_finite_reader(r)?;
+        let auxpow = if header.is_auxpow() {
+            Some(Decodable::consensus_decode_from_finite_reader(r)?)
+        } else {
+            None
+        };
+        if VarInt::consensus_decode_from_finite_reader(r)?.0 != 0 {
+            return Err(encode::Error::ParseFailed("header with transactions"));
+        }
+        Ok(AuxPowHeader { header, auxpow })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RejectMessage {
+    /// Command of the rejected message.
+    pub message: String,
+    pub ccode: u8,
+    pub reason: String,
+    /// Hash of the rejected transaction or block.
+    pub hash: Option<sha256d::Hash>,
+}
+
+impl Encodable for RejectMessage {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, Error> {
+        let mut len = 0;
+        len += self.message.consensus_encode(w)?;
+        len += self.ccode.consensus_encode(w)?;
+        len += self.reason.consensus_encode(w)?;
+        if let Some(hash) = self.hash {
+            len += hash.consensus_encode(w)?;
+        }
+        Ok(len)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NetworkMessage {
+    Version(VersionMessage),
+    Verack,
+    Ping(u64),
+    Pong(u64),
+    Inv(Vec<Inventory>),
+    GetData(Vec<Inventory>),
+    NotFound(Vec<Inventory>),
+    GetHeaders(GetHeadersMessage),
+    Headers(Vec<AuxPowHeader>),
+    SendHeaders,
+    Block(Box<Block>),
+    Tx(Transaction),
+    MemPool,
+    Reject(RejectMessage),
+    GetAddr,
+    Addr(Vec<TimedNetAddress>),
+    /// Minimum fee rate in units per 1000 bytes of transactions to relay (BIP133).
+    FeeFilter(u64),
+    /// A message this codec does not interpret.
+    Unknown {
+        command: String,
+        payload: Vec<u8>,
+    },
+}
+
+impl NetworkMessage {
+    pub fn command(&self) -> &str {
+        match self {
+            NetworkMessage::Version(_) => "version",
+            NetworkMessage::Verack => "verack",
+            NetworkMessage::Ping(_) => "ping",
+            NetworkMessage::Pong(_) => "pong",
+            NetworkMessage::Inv(_) => "inv",
+            NetworkMessage::GetData(_) => "getdata",
+            NetworkMessage::NotFound(_) => "notfound",
+            NetworkMessage::GetHeaders(_) => "getheaders",
+            NetworkMessage::Headers(_) => "headers",
+            NetworkMessage::SendHeaders => "sendheaders",
+            NetworkMessage::Block(_) => "block",
+            NetworkMessage::Tx(_) => "tx",
+            NetworkMessage::MemPool => "mempool",
+            NetworkMessage::Reject(_) => "reject",
+            NetworkMessage::GetAddr => "getaddr",
+            NetworkMessage::Addr(_) => "addr",
+            NetworkMessage::FeeFilter(_) => "feefilter",
+            NetworkMessage::Unknown { command, .. } => command,
+        }
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let w = &mut buf;
+        match self {
+            NetworkMessage::Version(msg) => msg.consensus_encode(w),
+            NetworkMessage::Verack
+            | NetworkMessage::SendHeaders
+            | NetworkMessage::MemPool
+            | NetworkMessage::GetAddr => Ok(0),
+            NetworkMessage::Ping(nonce) | NetworkMessage::Pong(nonce) => nonce.consensus_encode(w),
+            NetworkMessage::Inv(inv)
+            | NetworkMessage::GetData(inv)
+            | NetworkMessage::NotFound(inv) => consensus_encode_vec(inv, w),
+            NetworkMessage::GetHeaders(msg) => msg.consensus_encode(w),
+            NetworkMessage::Headers(headers) => consensus_encode_vec(headers, w),
+            NetworkMessage::Block(block) => block.consensus_encode(w),
+            NetworkMessage::Tx(tx) => tx.consensus_encode(w),
+            NetworkMessage::Reject(msg) => msg.consensus_encode(w),
+            NetworkMessage::Addr(addrs) => consensus_encode_vec(addrs, w),
+            NetworkMessage::FeeFilter(rate) => rate.consensus_encode(w),
+            NetworkMessage::Unknown { payload, .. } => {
+                w.extend_from_slice(payload);
+                Ok(payload.len())
+            }
+        }
+        .expect("vec writers don't error");
+        buf
+    }
+
+    /// Decodes the payload of a message with the given command.
+    pub fn from_payload(command: &str, payload: &[u8]) -> Result<Self, P2PError> {
+        Self::decode_payload(command, payload).map_err(|err| P2PError::Decode {
+            command: command.to_string(),
+            reason: err.to_string(),
+        })
+    }
+
+    fn decode_payload(command: &str, payload: &[u8]) -> Result<Self, encode::Error> {
+        let r = &mut &payload[..];
+        let msg = match command {
+            "version" => {
+                let mut msg = VersionMessage {
+                    version: Decodable::consensus_decode_from_finite_reader(r)?,
+                    services: Decodable::consensus_decode_from_finite_reader(r)?,
+                    timestamp: Decodable::consensus_decode_from_finite_reader(r)?,
+                    receiver: Decodable::consensus_decode_from_finite_reader(r)?,
+                    sender: Decodable::consensus_decode_from_finite_reader(r)?,
+                    nonce: Decodable::consensus_decode_from_finite_reader(r)?,
+                    user_agent: Decodable::consensus_decode_from_finite_reader(r)?,
+                    start_height: Decodable::consensus_decode_from_finite_reader(r)?,
+                    relay: true,
+                };
+                // relay is optional, later protocol versions may append more fields
+                if !r.is_empty() {
+                    msg.relay = Decodable::consensus_decode_from_finite_reader(r)?;
+                }
+                return Ok(NetworkMessage::Version(msg));
+            }
+            "verack" => NetworkMessage::Verack,
+            "ping" => NetworkMessage::Ping(Decodable::consensus_decode_from_finite_reader(r)?),
+            "pong" => NetworkMessage::Pong(Decodable::consensus_decode_from_finite_reader(r)?),
+            "inv" => NetworkMessage::Inv(decode_vec(r, MAX_INV_SIZE)?),
+            "getdata" => NetworkMessage::GetData(decode_vec(r, MAX_INV_SIZE)?),
+            "notfound" => NetworkMessage::NotFound(decode_vec(r, MAX_INV_SIZE)?),
+            "getheaders" => {
+                NetworkMessage::GetHeaders(Decodable::consensus_decode_from_finite_reader(r)?)
+            }
+            "headers" => NetworkMessage::Headers(decode_vec(r, MAX_HEADERS_RESULTS)?),
+            "sendheaders" => NetworkMessage::SendHeaders,
+            "block" => {
+                NetworkMessage::Block(Box::new(Decodable::consensus_decode_from_finite_reader(r)?))
+            }
+            "tx" => NetworkMessage::Tx(Decodable::consensus_decode_from_finite_reader(r)?),
+            "mempool" => NetworkMessage::MemPool,
+            "reject" => {
+                let mut msg = RejectMessage {
+                    message: Decodable::consensus_decode_from_finite_reader(r)?,
+                    ccode: Decodable::consensus_decode_from_finite_reader(r)?,
+                    reason: Decodable::consensus_decode_from_finite_reader(r)?,
+                    hash: None,
+                };
+                // only tx and block rejects carry the hash
+                if r.len() >= 32 {
+                    msg.hash = Some(Decodable::consensus_decode_from_finite_reader(r)?);
+                }
+                NetworkMessage::Reject(msg)
+            }
+            "getaddr" => NetworkMessage::GetAddr,
+            "addr" => NetworkMessage::Addr(decode_vec(r, MAX_ADDR_SIZE)?),
+            "feefilter" => {
+                NetworkMessage::FeeFilter(Decodable::consensus_decode_from_finite_reader(r)?)
+            }
+            _ => {
+                return Ok(NetworkMessage::Unknown {
+                    command: command.to_string(),
+                    payload: payload.to_vec(),
+                })
+            }
+        };
+        if !r.is_empty() {
+            return Err(encode::Error::ParseFailed("data not consumed entirely"));
+        }
+        Ok(msg)
+    }
+}
+
+/// A message with its envelope: magic, command, payload length and checksum.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RawNetworkMessage {
+    pub magic: [u8; 4],
+    pub payload: NetworkMessage,
+}
+
+impl RawNetworkMessage {
+    pub fn new(chain: &ChainParams, payload: NetworkMessage) -> Self {
+        RawNetworkMessage {
+            magic: chain.magic,
+            payload,
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        self.payload.command()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let payload = self.payload.payload();
+        let mut buf = Vec::with_capacity(HEADER_SIZE + payload.len());
+        buf.extend_from_slice(&self.magic);
+        let mut command = [0u8; COMMAND_SIZE];
+        let name = self.command().as_bytes();
+        let n = name.len().min(COMMAND_SIZE);
+        command[..n].copy_from_slice(&name[..n]);
+        buf.extend_from_slice(&command);
+        buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&checksum(&payload));
+        buf.extend_from_slice(&payload);
+        buf
+    }
+
+    /// Decodes a message of the chain from the start of `data`, returns the message and
+    /// the number of bytes consumed. Returns [`P2PError::Incomplete`] when more data
+    /// should be read from the peer.
+    pub fn decode(data: &[u8], chain: &ChainParams) -> Result<(Self, usize), P2PError> {
+        if data.len() < HEADER_SIZE {
+            return Err(P2PError::Incomplete(HEADER_SIZE));
+        }
+        let magic: [u8; 4] = data[0..4].try_into().unwrap();
+        if magic != chain.magic {
+            return Err(P2PError::WrongMagic(magic));
+        }
+        let command = parse_command(&data[4..16])?;
+        let size = u32::from_le_bytes(data[16..20].try_into().unwrap()) as usize;
+        if size > MAX_PAYLOAD_SIZE {
+            return Err(P2PError::PayloadTooLarge(size));
+        }
+        if data.len() < HEADER_SIZE + size {
+            return Err(P2PError::Incomplete(HEADER_SIZE + size));
+        }
+        let payload = &data[HEADER_SIZE..HEADER_SIZE + size];
+        if checksum(payload) != data[20..24] {
+            return Err(P2PError::Checksum);
+        }
+        let payload = NetworkMessage::from_payload(command, payload)?;
+        Ok((RawNetworkMessage { magic, payload }, HEADER_SIZE + size))
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = sha256d::Hash::hash(payload);
+    hash[..4].try_into().unwrap()
+}
+
+// the command is printable ASCII padded with NUL bytes
+fn parse_command(data: &[u8]) -> Result<&str, P2PError> {
+    let len = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    if len == 0
+        || data[len..].iter().any(|b| *b != 0)
+        || !data[..len].iter().all(|b| b.is_ascii_graphic())
+    {
+        return Err(P2PError::InvalidCommand);
+    }
+    Ok(std::str::from_utf8(&data[..len]).unwrap())
+}
+
+// like consensus_decode_from_vec, with a limit on the number of items sent by the peer
+fn decode_vec<T, R>(r: &mut R, max: usize) -> Result<Vec<T>, encode::Error>
+where
+    T: Decodable,
+    R: Read + ?Sized,
+{
+    let len = VarInt::consensus_decode_from_finite_reader(r)?.0 as usize;
+    if len > max {
+        return Err(encode::Error::ParseFailed("too many items"));
+    }
+    let mut vv = Vec::with_capacity(len.min(1024));
+    for _ in 0..len {
+        vv.push(Decodable::consensus_decode_from_finite_reader(r)?);
+    }
+    Ok(vv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::{DOGE_MAIN_NET_CHAIN, DOGE_TEST_NET_CHAIN};
+    use hex::test_hex_unwrap as hex;
+    use std::str::FromStr;
+
+    fn roundtrip(msg: NetworkMessage) -> Vec<u8> {
+        let raw = RawNetworkMessage::new(&DOGE_MAIN_NET_CHAIN, msg);
+        let data = raw.to_bytes();
+        let (decoded, n) = RawNetworkMessage::decode(&data, &DOGE_MAIN_NET_CHAIN).unwrap();
+        assert_eq!(decoded, raw);
+        assert_eq!(n, data.len());
+        data
+    }
+
+    #[test]
+    fn test_envelope() {
+        let data = roundtrip(NetworkMessage::Verack);
+        assert_eq!(
+            data,
+            hex!("c0c0c0c076657261636b000000000000000000005df6e0e2")
+        );
+        let mut stream = data.clone();
+        stream.extend(roundtrip(NetworkMessage::Ping(0x0102030405060708)));
+        assert_eq!(
+            &stream[24..],
+            &hex!("c0c0c0c070696e670000000000000000080000003b5a75130807060504030201")[..]
+        );
+        let (msg, n) = RawNetworkMessage::decode(&stream, &DOGE_MAIN_NET_CHAIN).unwrap();
+        assert_eq!(msg.payload, NetworkMessage::Verack);
+        let (msg, _) = RawNetworkMessage::decode(&stream[n..], &DOGE_MAIN_NET_CHAIN).unwrap();
+        assert_eq!(msg.payload, NetworkMessage::Ping(0x0102030405060708));
+
+        assert_eq!(
+            RawNetworkMessage::decode(&stream[..10], &DOGE_MAIN_NET_CHAIN),
+            Err(P2PError::Incomplete(HEADER_SIZE))
+        );
+        assert_eq!(
+            RawNetworkMessage::decode(&stream[n..n + 30], &DOGE_MAIN_NET_CHAIN),
+            Err(P2PError::Incomplete(32))
+        );
+        assert_eq!(
+            RawNetworkMessage::decode(&data, &DOGE_TEST_NET_CHAIN),
+            Err(P2PError::WrongMagic([0xc0; 4]))
+        );
+        let mut bad = data.clone();
+        bad[23] ^= 1;
+        assert_eq!(
+            RawNetworkMessage::decode(&bad, &DOGE_MAIN_NET_CHAIN),
+            Err(P2PError::Checksum)
+        );
+        let mut bad = data.clone();
+        bad[11] = b'x';
+        assert_eq!(
+            RawNetworkMessage::decode(&bad, &DOGE_MAIN_NET_CHAIN),
+            Err(P2PError::InvalidCommand)
+        );
+        let mut bad = data.clone();
+        bad[16..20].copy_from_slice(&(MAX_PAYLOAD_SIZE as u32 + 1).to_le_bytes());
+        assert_eq!(
+            RawNetworkMessage::decode(&bad, &DOGE_MAIN_NET_CHAIN),
+            Err(P2PError::PayloadTooLarge(MAX_PAYLOAD_SIZE + 1))
+        );
+
+        // trailing data in the payload is rejected
+        let mut bad = RawNetworkMessage::new(
+            &DOGE_MAIN_NET_CHAIN,
+            NetworkMessage::Unknown {
+                command: "pong".to_string(),
+                payload: vec![0; 9],
+            },
+        );
+        assert!(matches!(
+            RawNetworkMessage::decode(&bad.to_bytes(), &DOGE_MAIN_NET_CHAIN),
+            Err(P2PError::Decode { .. })
+        ));
+        bad.payload = NetworkMessage::Unknown {
+            command: "sendcmpct".to_string(),
+            payload: vec![0; 9],
+        };
+        roundtrip(bad.payload);
+    }
+
+    #[test]
+    fn test_messages() {
+        let addr = NetAddress::new(
+            &SocketAddr::from_str("1.2.3.4:22556").unwrap(),
+            NODE_NETWORK,
+        );
+        assert_eq!(addr.socket_addr().to_string(), "1.2.3.4:22556");
+        let version = VersionMessage {
+            version: PROTOCOL_VERSION,
+            services: NODE_NETWORK | NODE_BLOOM,
+            timestamp: 1692251695,
+            receiver: addr,
+            sender: NetAddress::new(&SocketAddr::from_str("[::1]:0").unwrap(), 0),
+            nonce: 42,
+            user_agent: "/Shibetoshi:1.14.6/".to_string(),
+            start_height: 4862051,
+            relay: false,
+        };
+        let data = roundtrip(NetworkMessage::Version(version.clone()));
+        assert_eq!(
+            &data[24..],
+            &hex!("7f11010005000000000000002fb6dd6400000000010000000000000000000000000000000000ffff01020304581c00000000000000000000000000000000000000000000000100002a00000000000000132f5368696265746f7368693a312e31342e362f63304a0000")[..]
+        );
+        // relay is optional
+        let mut data = data[24..data.len() - 1].to_vec();
+        assert_eq!(
+            NetworkMessage::from_payload("version", &data).unwrap(),
+            NetworkMessage::Version(VersionMessage {
+                relay: true,
+                ..version
+            })
+        );
+        data.truncate(50);
+        assert!(NetworkMessage::from_payload("version", &data).is_err());
+
+        let hash =
+            BlockHash::from_str("fb5f5b5b7d70e660c2c67bca8d3328afae32ae8bb4c8d6cbc42d96ff876b0859")
+                .unwrap();
+        let inv = vec![
+            Inventory::Tx(Txid::from_byte_array([1u8; 32])),
+            Inventory::Block(hash),
+            Inventory::Unknown {
+                inv_type: 0x40000002,
+                hash: [2u8; 32],
+            },
+        ];
+        let data = roundtrip(NetworkMessage::Inv(inv.clone()));
+        assert_eq!(data.len(), 24 + 1 + 36 * 3);
+        assert_eq!(&data[25..29], &[1, 0, 0, 0]);
+        roundtrip(NetworkMessage::GetData(inv.clone()));
+        roundtrip(NetworkMessage::NotFound(inv));
+        roundtrip(NetworkMessage::GetHeaders(GetHeadersMessage {
+            version: PROTOCOL_VERSION,
+            locator_hashes: vec![hash, BlockHash::default()],
+            stop_hash: BlockHash::default(),
+        }));
+        roundtrip(NetworkMessage::SendHeaders);
+        roundtrip(NetworkMessage::MemPool);
+        roundtrip(NetworkMessage::GetAddr);
+        roundtrip(NetworkMessage::Pong(7));
+        let data = roundtrip(NetworkMessage::FeeFilter(100_000_000));
+        assert_eq!(&data[24..], &hex!("00e1f50500000000")[..]);
+        let data = roundtrip(NetworkMessage::Addr(vec![TimedNetAddress {
+            time: 1692251695,
+            addr,
+        }]));
+        assert_eq!(data.len(), 24 + 1 + 30);
+
+        let reject = RejectMessage {
+            message: "tx".to_string(),
+            ccode: REJECT_INSUFFICIENTFEE,
+            reason: "min relay fee not met".to_string(),
+            hash: Some(sha256d::Hash::from_byte_array([3u8; 32])),
+        };
+        roundtrip(NetworkMessage::Reject(reject.clone()));
+        roundtrip(NetworkMessage::Reject(RejectMessage {
+            message: "version".to_string(),
+            ccode: REJECT_OBSOLETE,
+            reason: "Version must be 70003 or greater".to_string(),
+            hash: None,
+        }));
+
+        // too many items
+        let mut data = vec![0xfd, 0x51, 0xc3];
+        data.extend([0u8; 36]);
+        assert!(NetworkMessage::from_payload("inv", &data).is_err());
+    }
+
+    #[test]
+    fn test_block_messages() {
+        // header and AuxPoW of the block in block::tests::test_block, followed by a zero tx count
+        let data = hex!("040162000da1809fa62c133c8550bfd8b9cd10d4fe092bfde21e2878b1cb4e58af6a89bc832687bd8628066e1f0438bbb024fa0d887f8628481aa188235a13d5988b3bab2fb6dd641b3c011a0000000001000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4f03639426082f5669614254432f2cfabe6d6d2c1f86704524b309442ddaf53a0b12befc612d768b4061ef16f8c756d0ac8eae10000000000000001042e8fb01b6dd8dec3dc58ab82b00000000000000ffffffff0270084e25000000001976a914e16c28146ed4869c190b3f0bdc18d80d45f9213488ac0000000000000000266a24aa21a9edb157e30de8b33073d3306bc698a3a195af1ad403fd60039dce6949fd5c07f75400000000f8e994042c682997e6cbc8896cafc1272b0f7463e15de79c5c6dac28efc1399e07d9e698cf2defb464daad1ae9eafb4f8b322c4173aa42ebc416a266c04af1d1eac77feb6d1ecd018947547f09be57c71b5a124f8c92bb2e68f51ac188bdcc7dcd0b37b84bf014510523a0d947743663738cd9751725374a564ad84d8c2439a80297100afdaec455ff3bfd03dc6dbb2020392cffe6ad0c8aaf9df6ce6ac4fb00e81465b6291762e2637e44e6fee4da05e3f3daa1e0479795f8570389c61153ee4d2affa7b1efa60214706e02e0e2b6efb6e4a421dff1c1b3a1ce50b4cd153365c2a71619dc93c90d7ad10ee5c58343218020c497c82c43c8f1ca3a3e474eb31bea00000000040000000000000000000000000000000000000000000000000000000000000000e2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf97d24db2bfa41474bfb2f877d688fac5faa5e10a2808cf9de307370b93352e54894857d3e08918f70395d9206410fbfa942f1a889aa5ab8188ec33c2f6e207dc70800000000000020cb69a881eb9f5d7fd4bc222efd87cb2a9f5e6ffc415e53dd0e73366ab83c746d92bd765c621aba82551149c76600200f289f96e2994271ab9145d43648310d902fb6dd643ea1001a849aa3bf00");
+        let mut payload = vec![1u8];
+        payload.extend_from_slice(&data);
+        let msg = NetworkMessage::from_payload("headers", &payload).unwrap();
+        let headers = match &msg {
+            NetworkMessage::Headers(headers) => headers.clone(),
+            _ => panic!("expected headers"),
+        };
+        assert_eq!(headers.len(), 1);
+        assert_eq!(
+            headers[0].block_hash().to_string(),
+            "fb5f5b5b7d70e660c2c67bca8d3328afae32ae8bb4c8d6cbc42d96ff876b0859"
+        );
+        assert!(headers[0].auxpow.is_some());
+        headers[0]
+            .header
+            .validate_pow(headers[0].auxpow.as_ref(), &DOGE_MAIN_NET_CHAIN)
+            .unwrap();
+        assert_eq!(msg.payload(), payload);
+        roundtrip(msg);
+
+        // headers must not carry transactions
+        *payload.last_mut().unwrap() = 1;
+        assert!(NetworkMessage::from_payload("headers", &payload).is_err());
+
+        // block 0 and its coinbase tx
+        let data = hex!("010000000000000000000000000000000000000000000000000000000000000000000000696ad20e2dd4365c7459b4a4a5af743d5e92c6da3229e6532cd605f6533f2a5b24a6a152f0ff0f1e678601000101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff1004ffff001d0104084e696e746f6e646fffffffff010058850c020000004341040184710fa689ad5023690c80f3a49c8f13f8d45b8c857fbcbc8bc4a8e4d3eb4b10f4d4604fa08dce601aaf0f470216fe1b51850b4acf21b179c45070ac7b03a9ac00000000");
+        let block = Block::consensus_decode_from_finite_reader(&mut &data[..]).unwrap();
+        let msg = NetworkMessage::from_payload("block", &data).unwrap();
+        assert_eq!(msg, NetworkMessage::Block(Box::new(block.clone())));
+        roundtrip(msg);
+        let headers = roundtrip(NetworkMessage::Headers(vec![AuxPowHeader::from(&block)]));
+        assert_eq!(headers.len(), 24 + 1 + 80 + 1);
+        roundtrip(NetworkMessage::Tx(block.txdata[0].clone()));
+    }
+}
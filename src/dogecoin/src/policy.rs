@@ -0,0 +1,337 @@
+use bitcoin::secp256k1::ecdsa::Signature;
+
+use crate::amount::{fee_by_size, DUST_LIMIT, MIN_FEE_RATE};
+use crate::builder::MAX_OP_RETURN_DATA_SIZE;
+use crate::chainparams::DOGE_MAIN_NET_CHAIN;
+use crate::interpreter::is_valid_signature_encoding;
+use crate::opcodes::*;
+use crate::script::{classify_script, get_op, PushBytes, ScriptBuf, ScriptType};
+use crate::transaction::Transaction;
+
+// https://github.com/dogecoin/dogecoin/blob/master/src/policy/policy.h
+pub const MAX_STANDARD_VERSION: u32 = 2;
+pub const MAX_STANDARD_TX_SIZE: usize = 100_000;
+pub const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+/// OP_RETURN, a push opcode of up to 2 bytes and the data.
+pub const MAX_OP_RETURN_RELAY: usize = MAX_OP_RETURN_DATA_SIZE + 3;
+pub const MAX_STANDARD_MULTISIG_KEYS: u8 = 3;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PolicyError {
+    Version(u32),
+    TxSize(usize),
+    ScriptSigSize {
+        index: usize,
+        size: usize,
+    },
+    ScriptSigNotPushOnly(usize),
+    /// A signature in the input's scriptSig has a high S value.
+    HighS(usize),
+    ScriptPubKey(usize),
+    Dust {
+        index: usize,
+        value: u64,
+    },
+    MultipleDataOutputs,
+    FeeTooLow {
+        fee: u64,
+        required: u64,
+    },
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyError::Version(v) => write!(f, "version: {}", v),
+            PolicyError::TxSize(size) => write!(f, "tx-size: {}", size),
+            PolicyError::ScriptSigSize { index, size } => {
+                write!(f, "scriptsig-size: input {} size {}", index, size)
+            }
+            PolicyError::ScriptSigNotPushOnly(i) => {
+                write!(f, "scriptsig-not-pushonly: input {}", i)
+            }
+            PolicyError::HighS(i) => write!(f, "non-canonical signature: input {}", i),
+            PolicyError::ScriptPubKey(i) => write!(f, "scriptpubkey: output {}", i),
+            PolicyError::Dust { index, value } => write!(
+                f,
+                "dust: output {} value {} is below {}",
+                index, value, DUST_LIMIT
+            ),
+            PolicyError::MultipleDataOutputs => write!(f, "multi-op-return"),
+            PolicyError::FeeTooLow { fee, required } => write!(
+                f,
+                "min relay fee not met, expected: {}, got {}",
+                required, fee
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// Checks the transaction against Dogecoin Core's IsStandardTx, plus low-S
+/// signatures (SCRIPT_VERIFY_LOW_S) which peers enforce on relay. Nodes drop
+/// non-standard transactions even if they are valid in a block.
+pub fn check_standard(tx: &Transaction) -> Result<(), PolicyError> {
+    if tx.version == 0 || tx.version > MAX_STANDARD_VERSION {
+        return Err(PolicyError::Version(tx.version));
+    }
+    let size = tx.size();
+    if size > MAX_STANDARD_TX_SIZE {
+        return Err(PolicyError::TxSize(size));
+    }
+
+    for (index, input) in tx.input.iter().enumerate() {
+        let script = input.script.as_bytes();
+        if script.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+            return Err(PolicyError::ScriptSigSize {
+                index,
+                size: script.len(),
+            });
+        }
+        if !is_push_only(script) {
+            return Err(PolicyError::ScriptSigNotPushOnly(index));
+        }
+        let mut pc = 0;
+        while let Some((_, data)) = get_op(script, &mut pc) {
+            if is_high_s(data) {
+                return Err(PolicyError::HighS(index));
+            }
+        }
+    }
+
+    let mut data_outputs = 0;
+    for (index, output) in tx.output.iter().enumerate() {
+        let script = output.script_pubkey.as_bytes();
+        match classify_script(script, &DOGE_MAIN_NET_CHAIN).0 {
+            ScriptType::PubKey | ScriptType::PubKeyHash | ScriptType::ScriptHash => {}
+            ScriptType::MultiSig => {
+                let m = script[0] - (OP_1 - 1);
+                let n = script[script.len() - 2] - (OP_1 - 1);
+                if n > MAX_STANDARD_MULTISIG_KEYS || m > n {
+                    return Err(PolicyError::ScriptPubKey(index));
+                }
+            }
+            ScriptType::NullData => {
+                if script.len() > MAX_OP_RETURN_RELAY || !is_push_only(&script[1..]) {
+                    return Err(PolicyError::ScriptPubKey(index));
+                }
+                data_outputs += 1;
+                continue;
+            }
+            _ => return Err(PolicyError::ScriptPubKey(index)),
+        }
+        if output.value < DUST_LIMIT {
+            return Err(PolicyError::Dust {
+                index,
+                value: output.value,
+            });
+        }
+    }
+    if data_outputs > 1 {
+        return Err(PolicyError::MultipleDataOutputs);
+    }
+    Ok(())
+}
+
+/// Checks the fee against the minimum relay fee for the transaction's size.
+pub fn check_fee(tx: &Transaction, fee: u64) -> Result<(), PolicyError> {
+    let required = fee_by_size(tx.size() as u64, MIN_FEE_RATE);
+    if fee < required {
+        return Err(PolicyError::FeeTooLow { fee, required });
+    }
+    Ok(())
+}
+
+/// Rewrites high-S signatures in push-only scriptSigs to their low-S form, which
+/// keeps them valid. Returns the number of signatures changed.
+pub fn normalize_signatures(tx: &mut Transaction) -> usize {
+    let mut changed = 0;
+    for input in tx.input.iter_mut() {
+        let script = input.script.as_bytes();
+        if !is_push_only(script) {
+            continue;
+        }
+        let mut res = ScriptBuf::new();
+        let mut pc = 0;
+        while pc < script.len() {
+            let start = pc;
+            let (_, data) = get_op(script, &mut pc).expect("push-only script");
+            if is_high_s(data) {
+                let (hash_type, der) = data.split_last().unwrap();
+                let mut sig = Signature::from_der_lax(der).expect("valid signature encoding");
+                sig.normalize_s();
+                let mut data = sig.serialize_der().to_vec();
+                data.push(*hash_type);
+                res.push_slice(<&PushBytes>::try_from(data.as_slice()).unwrap());
+                changed += 1;
+            } else {
+                let mut bytes = res.into_bytes();
+                bytes.extend_from_slice(&script[start..pc]);
+                res = ScriptBuf::from_bytes(bytes);
+            }
+        }
+        if res.as_bytes() != script {
+            input.script = res;
+        }
+    }
+    changed
+}
+
+// CScript::IsPushOnly, which counts OP_RESERVED as a push.
+fn is_push_only(script: &[u8]) -> bool {
+    let mut pc = 0;
+    while pc < script.len() {
+        match get_op(script, &mut pc) {
+            Some((opcode, _)) if opcode <= OP_16 => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+// Whether the push is a DER signature with a high S value.
+fn is_high_s(data: &[u8]) -> bool {
+    if !is_valid_signature_encoding(data) {
+        return false;
+    }
+    match Signature::from_der_lax(&data[..data.len() - 1]) {
+        Ok(sig) => {
+            let mut normalized = sig;
+            normalized.normalize_s();
+            normalized != sig
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::MIN_FEE;
+    use crate::pow::U256;
+    use crate::transaction::TxOut;
+    use hex::test_hex_unwrap as hex;
+
+    // flips the S value of a DER signature + sighash type push to n - s
+    fn high_s(sig: &[u8]) -> Vec<u8> {
+        let compact = Signature::from_der(&sig[..sig.len() - 1])
+            .unwrap()
+            .serialize_compact();
+        let be_to_u256 = |data: &[u8]| {
+            let mut le: [u8; 32] = data.try_into().unwrap();
+            le.reverse();
+            U256::from_le_bytes(le)
+        };
+        let order = hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141");
+        let mut s = (be_to_u256(&order) - be_to_u256(&compact[32..])).to_le_bytes();
+        s.reverse();
+        let mut compact_high = compact;
+        compact_high[32..].copy_from_slice(&s);
+        let mut res = Signature::from_compact(&compact_high)
+            .unwrap()
+            .serialize_der()
+            .to_vec();
+        res.push(*sig.last().unwrap());
+        res
+    }
+
+    #[test]
+    fn test_standard_checks() {
+        // https://dogechain.info/tx/f875c9959d2013caedf7b3acce278d1bed7be0e7d45aa266db1c262e6743cb99
+        let tx = Transaction::try_from(&hex!("0100000003607ee3f9c2c8eb4db9297d38c3ed1493fbdd257283eabf5a89999ebd5676f13f000000006a473044022045228179cc4fed581b5d9e6411402fd34c40866397094f0d97843ee257b30abc0220310c33c4452d47113c51257883c780d40928ba6b9e2bf14c234f1bd5991607e6012102ca3e8f77965b91cbe15203817cce0170bc066d5d7a9acb2070e9b3e4b2077bb8feffffff538cde51d37d84ec955410476c18c7dddaf5531a7aa07e60b75dd106ca50b447000000006b483045022100b58824fe1e036320a5cc267fe65fec100988df2a0d0ea594b945088c4945765b0220091e3dead2cec11ce5c4139f126452437b8ba8d957ae7dcee0fba60d854d46df0121026079f574275f68fd88fbd01c0af3364524b3071419dc24bddbf60003be974288feffffff7e24f4f31a3a6eaf109c3368352975c1e0ef10d175905e991553e4040b049e8c010000006b483045022100f3d369af38220916afb0c805581cd14b08ff0ee83b093c71fdeccb9b8fea197f022053e648c6a0ed5e1b42042d0e4654be8e039082c8106f147726e420cf1c986d03012103a0e805a231331c414b0423adef1ddedb23cc801acfd90ba7bd95907048a3b908feffffff02a4700106000000001976a91467f5672ce989470f4dcba16c1e930f80c03c887488acf23b03c3360000001976a91489248eee4e9d99729ebebbca00efb75ceb1ed01888acf9ed4900")[..]).unwrap();
+        check_standard(&tx).unwrap();
+        check_fee(&tx, MIN_FEE).unwrap();
+        assert_eq!(
+            check_fee(&tx, MIN_FEE - 1),
+            Err(PolicyError::FeeTooLow {
+                fee: MIN_FEE - 1,
+                required: MIN_FEE
+            })
+        );
+
+        // high-S signatures are rejected and can be normalized back
+        let mut high_tx = tx.clone();
+        let mut pc = 0;
+        let script = tx.input[1].script.as_bytes();
+        let (_, sig) = get_op(script, &mut pc).unwrap();
+        let (_, pubkey) = get_op(script, &mut pc).unwrap();
+        let mut script_sig = ScriptBuf::new();
+        script_sig.push_slice(<&PushBytes>::try_from(&high_s(sig)[..]).unwrap());
+        script_sig.push_slice(<&PushBytes>::try_from(pubkey).unwrap());
+        high_tx.input[1].script = script_sig;
+        assert_eq!(check_standard(&high_tx), Err(PolicyError::HighS(1)));
+        assert_eq!(normalize_signatures(&mut high_tx), 1);
+        assert_eq!(high_tx, tx);
+        assert_eq!(normalize_signatures(&mut high_tx), 0);
+
+        let mut bad = tx.clone();
+        bad.version = 3;
+        assert_eq!(check_standard(&bad), Err(PolicyError::Version(3)));
+
+        let mut bad = tx.clone();
+        bad.input[0].script = ScriptBuf::from_bytes(vec![OP_1, OP_DUP]);
+        assert_eq!(
+            check_standard(&bad),
+            Err(PolicyError::ScriptSigNotPushOnly(0))
+        );
+        let mut bad = tx.clone();
+        bad.input[2].script = ScriptBuf::from_bytes(vec![OP_0; 1651]);
+        assert_eq!(
+            check_standard(&bad),
+            Err(PolicyError::ScriptSigSize {
+                index: 2,
+                size: 1651
+            })
+        );
+
+        let mut bad = tx.clone();
+        bad.output[1].value = DUST_LIMIT - 1;
+        assert_eq!(
+            check_standard(&bad),
+            Err(PolicyError::Dust {
+                index: 1,
+                value: DUST_LIMIT - 1
+            })
+        );
+
+        // OP_RETURN outputs are exempt from the dust limit, one per tx
+        let data_out = |size: usize| {
+            let mut script = ScriptBuf::new();
+            script.push_opcode(OP_RETURN.into());
+            script.push_slice(<&PushBytes>::try_from(&vec![7u8; size][..]).unwrap());
+            TxOut {
+                value: 0,
+                script_pubkey: script,
+            }
+        };
+        let mut tx2 = tx.clone();
+        tx2.output.push(data_out(80));
+        check_standard(&tx2).unwrap();
+        tx2.output.push(data_out(1));
+        assert_eq!(check_standard(&tx2), Err(PolicyError::MultipleDataOutputs));
+        let mut bad = tx.clone();
+        bad.output.push(data_out(81));
+        assert_eq!(check_standard(&bad), Err(PolicyError::ScriptPubKey(2)));
+
+        // bare multisig up to 3 keys
+        let key = &hex!("02ca3e8f77965b91cbe15203817cce0170bc066d5d7a9acb2070e9b3e4b2077bb8")[..];
+        let multisig = |m: u8, n: u8| {
+            let mut script = ScriptBuf::new();
+            script.push_opcode((OP_1 + m - 1).into());
+            for _ in 0..n {
+                script.push_slice(<&PushBytes>::try_from(key).unwrap());
+            }
+            script.push_opcode((OP_1 + n - 1).into());
+            script.push_opcode(OP_CHECKMULTISIG.into());
+            script
+        };
+        let mut tx2 = tx.clone();
+        tx2.output[0].script_pubkey = multisig(2, 3);
+        check_standard(&tx2).unwrap();
+        tx2.output[0].script_pubkey = multisig(2, 4);
+        assert_eq!(check_standard(&tx2), Err(PolicyError::ScriptPubKey(0)));
+        tx2.output[0].script_pubkey = ScriptBuf::from_bytes(vec![OP_TRUE]);
+        assert_eq!(check_standard(&tx2), Err(PolicyError::ScriptPubKey(0)));
+    }
+}
@@ -0,0 +1,635 @@
+// https://github.com/dogecoin/dogecoin/blob/master/src/pow.cpp
+// https://github.com/dogecoin/dogecoin/blob/master/src/arith_uint256.cpp
+
+use std::{cmp::Ordering, fmt, ops, str::FromStr};
+
+use crate::block::{BlockHash, BlockHeader};
+use crate::chainparams::ChainParams;
+
+/// Computes the scrypt(N=1024, r=1, p=1) proof-of-work hash of a serialized block header.
+/// The header is used as both the password and the salt.
+pub fn scrypt_hash(data: &[u8]) -> [u8; 32] {
+    let params = scrypt::Params::new(10, 1, 1, 32).expect("valid scrypt params");
+    let mut output = [0u8; 32];
+    scrypt::scrypt(data, data, &params, &mut output).expect("valid scrypt output length");
+    output
+}
+
+/// 256-bit unsigned integer used for proof-of-work targets, stored as little-endian u64 limbs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub struct U256(pub [u64; 4]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0, 0, 0, 0]);
+
+    pub fn from_u64(v: u64) -> U256 {
+        U256([v, 0, 0, 0])
+    }
+
+    pub fn from_le_bytes(data: [u8; 32]) -> U256 {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&data[i * 8..i * 8 + 8]);
+            *limb = u64::from_le_bytes(buf);
+        }
+        U256(limbs)
+    }
+
+    pub fn to_le_bytes(&self) -> [u8; 32] {
+        let mut data = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            data[i * 8..i * 8 + 8].copy_from_slice(&limb.to_le_bytes());
+        }
+        data
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0, 0, 0, 0]
+    }
+
+    pub fn low_u64(&self) -> u64 {
+        self.0[0]
+    }
+
+    pub fn shl(&self, shift: u32) -> U256 {
+        let mut res = [0u64; 4];
+        let words = (shift / 64) as usize;
+        let bits = shift % 64;
+        for i in (words..4).rev() {
+            res[i] = self.0[i - words] << bits;
+            if bits > 0 && i > words {
+                res[i] |= self.0[i - words - 1] >> (64 - bits);
+            }
+        }
+        U256(res)
+    }
+
+    pub fn shr(&self, shift: u32) -> U256 {
+        let mut res = [0u64; 4];
+        let words = (shift / 64) as usize;
+        let bits = shift % 64;
+        for (i, limb) in res
+            .iter_mut()
+            .enumerate()
+            .take(4usize.saturating_sub(words))
+        {
+            *limb = self.0[i + words] >> bits;
+            if bits > 0 && i + words + 1 < 4 {
+                *limb |= self.0[i + words + 1] << (64 - bits);
+            }
+        }
+        U256(res)
+    }
+
+    /// Expands a compact "nBits" value into a target.
+    /// Returns (target, negative, overflow), mirroring arith_uint256::SetCompact.
+    pub fn from_compact(bits: u32) -> (U256, bool, bool) {
+        let size = bits >> 24;
+        let mut word = bits & 0x007fffff;
+        let target = if size <= 3 {
+            word >>= 8 * (3 - size);
+            U256::from_u64(word as u64)
+        } else {
+            U256::from_u64(word as u64).shl(8 * (size - 3))
+        };
+        let negative = word != 0 && (bits & 0x00800000) != 0;
+        let overflow = word != 0
+            && ((size > 34) || (word > 0xff && size > 33) || (word > 0xffff && size > 32));
+        (target, negative, overflow)
+    }
+
+    /// Encodes the value as a compact "nBits", mirroring arith_uint256::GetCompact.
+    pub fn to_compact(&self) -> u32 {
+        let mut size = self.bits().div_ceil(8);
+        let mut compact = if size <= 3 {
+            (self.low_u64() << (8 * (3 - size))) as u32
+        } else {
+            self.shr(8 * (size - 3)).low_u64() as u32
+        };
+        // The 0x00800000 bit denotes the sign, so divide the mantissa by 256
+        // and increase the exponent if it is already set.
+        if compact & 0x00800000 != 0 {
+            compact >>= 8;
+            size += 1;
+        }
+        compact | (size << 24)
+    }
+
+    /// Returns the number of significant bits.
+    pub fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return 64 * i as u32 + 64 - self.0[i].leading_zeros();
+            }
+        }
+        0
+    }
+
+    pub fn overflowing_add(&self, other: &U256) -> (U256, bool) {
+        let mut res = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in res.iter_mut().enumerate() {
+            let (v, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (v, c2) = v.overflowing_add(carry as u64);
+            *limb = v;
+            carry = c1 || c2;
+        }
+        (U256(res), carry)
+    }
+
+    pub fn overflowing_sub(&self, other: &U256) -> (U256, bool) {
+        let mut res = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in res.iter_mut().enumerate() {
+            let (v, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (v, b2) = v.overflowing_sub(borrow as u64);
+            *limb = v;
+            borrow = b1 || b2;
+        }
+        (U256(res), borrow)
+    }
+
+    /// Multiplies by a u64, wrapping on overflow like arith_uint256.
+    pub fn mul_u64(&self, rhs: u64) -> U256 {
+        let mut res = [0u64; 4];
+        let mut carry = 0u128;
+        for (i, limb) in res.iter_mut().enumerate() {
+            let v = self.0[i] as u128 * rhs as u128 + carry;
+            *limb = v as u64;
+            carry = v >> 64;
+        }
+        U256(res)
+    }
+
+    /// Returns (quotient, remainder). Panics if `divisor` is zero.
+    pub fn div_rem(&self, divisor: &U256) -> (U256, U256) {
+        assert!(!divisor.is_zero(), "division by zero");
+        if self < divisor {
+            return (U256::ZERO, *self);
+        }
+        let shift = self.bits() - divisor.bits();
+        let mut rem = *self;
+        let mut div = divisor.shl(shift);
+        let mut quo = U256::ZERO;
+        for i in (0..=shift).rev() {
+            if rem >= div {
+                rem = rem - div;
+                quo.0[(i / 64) as usize] |= 1 << (i % 64);
+            }
+            div = div.shr(1);
+        }
+        (quo, rem)
+    }
+}
+
+impl ops::Add for U256 {
+    type Output = U256;
+
+    fn add(self, rhs: U256) -> U256 {
+        self.overflowing_add(&rhs).0
+    }
+}
+
+impl ops::AddAssign for U256 {
+    fn add_assign(&mut self, rhs: U256) {
+        *self = *self + rhs;
+    }
+}
+
+impl ops::Sub for U256 {
+    type Output = U256;
+
+    fn sub(self, rhs: U256) -> U256 {
+        self.overflowing_sub(&rhs).0
+    }
+}
+
+impl ops::Div for U256 {
+    type Output = U256;
+
+    fn div(self, rhs: U256) -> U256 {
+        self.div_rem(&rhs).0
+    }
+}
+
+impl ops::Not for U256 {
+    type Output = U256;
+
+    fn not(self) -> U256 {
+        U256([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for i in (0..4).rev() {
+            match self.0[i].cmp(&other.0[i]) {
+                Ordering::Equal => continue,
+                ord => return ord,
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<&BlockHash> for U256 {
+    fn from(hash: &BlockHash) -> U256 {
+        U256::from_le_bytes(**hash)
+    }
+}
+
+/// Formats as 64 big-endian hex digits, like uint256::GetHex (e.g. the "chainwork" RPC field).
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:016x}{:016x}{:016x}{:016x}",
+            self.0[3], self.0[2], self.0[1], self.0[0]
+        )
+    }
+}
+
+impl FromStr for U256 {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        if s.is_empty() || s.len() > 64 {
+            return Err(format!("invalid U256 hex length {}", s.len()));
+        }
+        // slicing below needs single byte chars, and from_str_radix accepts a leading '+'
+        if !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("invalid U256 hex {:?}", s));
+        }
+        let mut limbs = [0u64; 4];
+        let mut end = s.len();
+        for limb in limbs.iter_mut() {
+            if end == 0 {
+                break;
+            }
+            let start = end.saturating_sub(16);
+            *limb = u64::from_str_radix(&s[start..end], 16).map_err(|err| err.to_string())?;
+            end = start;
+        }
+        Ok(U256(limbs))
+    }
+}
+
+/// Returns the amount of work represented by a block with the given compact target,
+/// i.e. 2**256 / (target+1), mirroring GetBlockProof.
+pub fn block_proof(bits: u32) -> U256 {
+    let (target, negative, overflow) = U256::from_compact(bits);
+    if negative || overflow || target.is_zero() {
+        return U256::ZERO;
+    }
+    // 2**256 / (target+1) == ~target / (target+1) + 1, without overflowing
+    (!target / (target + U256::from_u64(1))) + U256::from_u64(1)
+}
+
+/// Returns how many times harder `bits` is than the minimum difficulty target,
+/// mirroring GetDifficulty.
+pub fn difficulty(bits: u32) -> f64 {
+    let mut shift = (bits >> 24) & 0xff;
+    let mut diff = 0x0000ffff as f64 / (bits & 0x00ffffff) as f64;
+    while shift < 29 {
+        diff *= 256.0;
+        shift += 1;
+    }
+    while shift > 29 {
+        diff /= 256.0;
+        shift -= 1;
+    }
+    diff
+}
+
+/// Sums the work of a sequence of headers, starting from `prev_chainwork`.
+pub fn chain_work<'a>(
+    prev_chainwork: U256,
+    headers: impl IntoIterator<Item = &'a BlockHeader>,
+) -> U256 {
+    headers
+        .into_iter()
+        .fold(prev_chainwork, |acc, h| acc + block_proof(h.bits))
+}
+
+/// Returns the compact bits required for the block following `ancestors`, mirroring
+/// GetNextWorkRequired. `ancestors` must be consecutive headers ending with the parent
+/// block at `last_height`; at most `difficulty_adjustment_interval + 1` trailing headers are used.
+/// `time` is the timestamp of the new block.
+pub fn next_work_required(
+    chain: &ChainParams,
+    last_height: u32,
+    ancestors: &[BlockHeader],
+    time: u32,
+) -> Result<u32, String> {
+    let last = ancestors.last().ok_or("no ancestor headers")?;
+    let limit = chain.pow_limit_bits;
+    let min_difficulty = chain.allow_min_difficulty_blocks(last_height)
+        && time as i64 > last.time as i64 + chain.pow_target_spacing as i64 * 2;
+
+    // Dogecoin: special rules for minimum difficulty blocks with DigiShield
+    if min_difficulty && chain.is_digishield(last_height) {
+        return Ok(limit);
+    }
+
+    let interval = chain.difficulty_adjustment_interval(last_height);
+    if !(last_height + 1).is_multiple_of(interval) {
+        if chain.allow_min_difficulty_blocks(last_height) {
+            if min_difficulty {
+                return Ok(limit);
+            }
+            // Return the last non-special-min-difficulty-rules-block
+            let mut height = last_height;
+            let mut i = ancestors.len() - 1;
+            while height > 0 && !height.is_multiple_of(interval) && ancestors[i].bits == limit {
+                if i == 0 {
+                    return Err(format!(
+                        "not enough ancestors to find the difficulty at height {}",
+                        height
+                    ));
+                }
+                i -= 1;
+                height -= 1;
+            }
+            return Ok(ancestors[i].bits);
+        }
+        return Ok(last.bits);
+    }
+
+    // Litecoin: go back the full period unless it's the first retarget after genesis.
+    let blocks_to_go_back = if last_height + 1 == interval {
+        interval - 1
+    } else {
+        interval
+    } as usize;
+    if ancestors.len() <= blocks_to_go_back {
+        return Err(format!(
+            "need {} ancestors to retarget at height {}, got {}",
+            blocks_to_go_back + 1,
+            last_height + 1,
+            ancestors.len()
+        ));
+    }
+    let first = &ancestors[ancestors.len() - 1 - blocks_to_go_back];
+    Ok(calculate_next_work_required(
+        chain,
+        last_height,
+        last,
+        first.time,
+    ))
+}
+
+/// Retargets from the parent block at `last_height` given the timestamp of the first block
+/// of the retarget window, mirroring CalculateDogecoinNextWorkRequired.
+pub fn calculate_next_work_required(
+    chain: &ChainParams,
+    last_height: u32,
+    last: &BlockHeader,
+    first_block_time: u32,
+) -> u32 {
+    if chain.pow_no_retargeting {
+        return last.bits;
+    }
+
+    let height = last_height + 1;
+    let retarget_timespan = chain.target_timespan(last_height) as i64;
+    let actual_timespan = last.time as i64 - first_block_time as i64;
+    let mut modulated_timespan = actual_timespan;
+    let (min_timespan, max_timespan) = if chain.is_digishield(last_height) {
+        // DigiShield amplitude filter
+        modulated_timespan = retarget_timespan + (modulated_timespan - retarget_timespan) / 8;
+        (
+            retarget_timespan - retarget_timespan / 4,
+            retarget_timespan + retarget_timespan / 2,
+        )
+    } else if height > 10000 {
+        (retarget_timespan / 4, retarget_timespan * 4)
+    } else if height > 5000 {
+        (retarget_timespan / 8, retarget_timespan * 4)
+    } else {
+        (retarget_timespan / 16, retarget_timespan * 4)
+    };
+    let modulated_timespan = modulated_timespan.clamp(min_timespan, max_timespan);
+
+    let pow_limit = U256::from_compact(chain.pow_limit_bits).0;
+    let target = U256::from_compact(last.bits)
+        .0
+        .mul_u64(modulated_timespan as u64)
+        / U256::from_u64(retarget_timespan as u64);
+    if target > pow_limit {
+        pow_limit.to_compact()
+    } else {
+        target.to_compact()
+    }
+}
+
+/// Checks whether a proof-of-work hash satisfies the target encoded in `bits`.
+pub fn check_proof_of_work(pow_hash: &BlockHash, bits: u32) -> Result<(), String> {
+    let (target, negative, overflow) = U256::from_compact(bits);
+    if negative || overflow || target.is_zero() {
+        return Err(format!("invalid compact target bits {:#010x}", bits));
+    }
+    if U256::from(pow_hash) > target {
+        return Err(format!(
+            "proof of work hash {} does not meet target bits {:#010x}",
+            pow_hash, bits
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::{DOGE_MAIN_NET_CHAIN, DOGE_REG_TEST_CHAIN, DOGE_TEST_NET_CHAIN};
+
+    #[test]
+    fn test_from_compact() {
+        let (target, negative, overflow) = U256::from_compact(0x1e0ffff0);
+        assert!(!negative && !overflow);
+        let mut expected = [0u8; 32];
+        expected[27] = 0xf0;
+        expected[28] = 0xff;
+        expected[29] = 0x0f;
+        assert_eq!(target.to_le_bytes(), expected);
+
+        assert_eq!(U256::from_compact(0x01003456).0, U256::ZERO);
+        assert_eq!(U256::from_compact(0x01123456).0, U256::from_u64(0x12));
+        assert_eq!(U256::from_compact(0x04123456).0, U256::from_u64(0x12345600));
+        assert!(U256::from_compact(0x04923456).1);
+        assert!(U256::from_compact(0xff123456).2);
+    }
+
+    #[test]
+    fn test_shift() {
+        let v = U256::from_u64(0x8000_0000_0000_0001);
+        assert_eq!(v.shl(1), U256([2, 1, 0, 0]));
+        assert_eq!(v.shl(192), U256([0, 0, 0, 0x8000_0000_0000_0001]));
+        assert_eq!(v.shl(192).shr(192), v);
+        assert_eq!(v.shl(1).shr(1), v);
+        assert!(v.shl(64) > v);
+    }
+
+    #[test]
+    fn test_to_compact() {
+        for bits in [
+            0x1e0ffff0, 0x1e0fffff, 0x1a013c1b, 0x1d00ffff, 0x207fffff, 0x05009234,
+        ] {
+            let (target, _, _) = U256::from_compact(bits);
+            assert_eq!(target.to_compact(), bits, "bits {:#010x}", bits);
+        }
+        assert_eq!(U256::ZERO.to_compact(), 0);
+        assert_eq!(U256::from_u64(0x80).to_compact(), 0x02008000);
+        assert_eq!(U256::from_u64(0x12345600).to_compact(), 0x04123456);
+    }
+
+    #[test]
+    fn test_arith() {
+        let a = U256([u64::MAX, u64::MAX, 0, 0]);
+        let one = U256::from_u64(1);
+        assert_eq!(a + one, U256([0, 0, 1, 0]));
+        assert_eq!((a + one) - one, a);
+        assert_eq!(U256::ZERO.overflowing_sub(&one), (!U256::ZERO, true));
+        assert_eq!(
+            a / U256::from_u64(0xffff_ffff),
+            U256([0x1_0000_0001, 0x1_0000_0001, 0, 0])
+        );
+        assert_eq!(
+            U256::from_u64(100).div_rem(&U256::from_u64(7)),
+            (U256::from_u64(14), U256::from_u64(2))
+        );
+        assert_eq!(a.bits(), 128);
+        assert_eq!(U256::ZERO.bits(), 0);
+    }
+
+    fn header(time: u32, bits: u32) -> BlockHeader {
+        BlockHeader {
+            version: 1,
+            prev_blockhash: BlockHash::default(),
+            merkle_root: Default::default(),
+            time,
+            bits,
+            nonce: 0,
+        }
+    }
+
+    #[test]
+    fn test_calculate_next_work_required() {
+        // https://github.com/dogecoin/dogecoin/blob/master/src/test/dogecoin_tests.cpp
+        let chain = &DOGE_MAIN_NET_CHAIN;
+        let cases = [
+            // (last_height, last_time, last_bits, first_block_time, expected)
+            (239, 1386475638, 0x1e0ffff0, 1386474927, 0x1e00ffff),
+            (9599, 1386954113, 0x1c1a1206, 1386942008, 0x1c15ea59),
+            (145000, 1395094679, 0x1b499dfd, 1395094427, 0x1b671062),
+            (145107, 1395101360, 0x1b3439cd, 1395100835, 0x1b4e56b3),
+            (149423, 1395380447, 0x1b446f21, 1395380517, 0x1b335358),
+            (145001, 1395094727, 0x1b671062, 1395094679, 0x1b6558a4),
+        ];
+        for (height, time, bits, first, expected) in cases {
+            assert_eq!(
+                calculate_next_work_required(chain, height, &header(time, bits), first),
+                expected,
+                "height {}",
+                height
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_work_required() {
+        let chain = &DOGE_MAIN_NET_CHAIN;
+        assert!(next_work_required(chain, 0, &[], 0).is_err());
+
+        // no retarget between intervals before DigiShield
+        let ancestors = vec![header(1000, 0x1c1a1206); 3];
+        assert_eq!(
+            next_work_required(chain, 9000, &ancestors, 5000).unwrap(),
+            0x1c1a1206
+        );
+        // retarget needs the full window
+        assert!(next_work_required(chain, 9599, &ancestors, 5000).is_err());
+        let mut ancestors = vec![header(1386942008, 0x1c1a1206); 241];
+        ancestors.last_mut().unwrap().time = 1386954113;
+        assert_eq!(
+            next_work_required(chain, 9599, &ancestors, 1386954173).unwrap(),
+            0x1c15ea59
+        );
+
+        // DigiShield retargets every block
+        let ancestors = [
+            header(1395094427, 0x1b499dfd),
+            header(1395094679, 0x1b499dfd),
+        ];
+        assert_eq!(
+            next_work_required(chain, 145000, &ancestors, 1395094800).unwrap(),
+            0x1b671062
+        );
+        assert_eq!(chain.difficulty_adjustment_interval(145000), 1);
+        assert_eq!(chain.difficulty_adjustment_interval(144999), 240);
+
+        // testnet minimum difficulty
+        let chain = &DOGE_TEST_NET_CHAIN;
+        let ancestors = [
+            header(1000, 0x1c1a1206),
+            header(1060, 0x1e0fffff),
+            header(1120, 0x1e0fffff),
+        ];
+        assert_eq!(
+            next_work_required(chain, 1002, &ancestors, 1300).unwrap(),
+            0x1e0fffff
+        );
+        assert_eq!(
+            next_work_required(chain, 1002, &ancestors, 1150).unwrap(),
+            0x1c1a1206
+        );
+        assert!(next_work_required(chain, 1002, &ancestors[1..], 1150).is_err());
+        // not allowed between DigiShield and the min difficulty fork
+        let ancestors = [header(1000, 0x1b499dfd), header(1060, 0x1b499dfd)];
+        assert_ne!(
+            next_work_required(chain, 150000, &ancestors, 5000).unwrap(),
+            0x1e0fffff
+        );
+        assert_eq!(
+            next_work_required(chain, 157500, &ancestors, 5000).unwrap(),
+            0x1e0fffff
+        );
+
+        // regtest never retargets
+        let chain = &DOGE_REG_TEST_CHAIN;
+        let ancestors = [header(1000, 0x207fffff), header(1001, 0x207fffff)];
+        assert_eq!(
+            next_work_required(chain, 100, &ancestors, 1002).unwrap(),
+            0x207fffff
+        );
+    }
+
+    #[test]
+    fn test_block_proof() {
+        assert_eq!(block_proof(0x1d00ffff), U256::from_u64(0x0100010001));
+        // dogecoin genesis block chainwork
+        assert_eq!(block_proof(0x1e0ffff0), U256::from_u64(0x100010));
+        assert_eq!(block_proof(0), U256::ZERO);
+
+        let work =
+            U256::from_str("0000000000000000000000000000000000000000000000000000000000100010")
+                .unwrap();
+        assert_eq!(work, U256::from_u64(0x100010));
+        assert_eq!(
+            work.to_string(),
+            "0000000000000000000000000000000000000000000000000000000000100010"
+        );
+        assert!(U256::from_str("xyz").is_err());
+        // a multi-byte char across a 16-digit boundary
+        assert!(U256::from_str(&format!("{}é{}", "0".repeat(46), "0".repeat(15))).is_err());
+        assert!(U256::from_str("+f").is_err());
+        assert!(U256::from_str(&"f".repeat(65)).is_err());
+    }
+}
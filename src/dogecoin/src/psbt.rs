@@ -0,0 +1,636 @@
+// A BIP174-style partially signed transaction container for Dogecoin.
+// https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki
+//
+// Dogecoin has no segwit, so every input carries its prevout as a bare `TxOut`
+// under the PSBT_IN_WITNESS_UTXO key type (0x01) instead of the full previous transaction.
+
+use base64::{engine::general_purpose::STANDARD as base64_std, Engine};
+use bitcoin::consensus::{encode, Decodable, Encodable};
+use bitcoin::hashes::{hash160, Hash};
+use bitcoin::secp256k1::{PublicKey, Secp256k1};
+use hex::DisplayHex;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::err_string;
+use crate::interpreter::{verify_input, STANDARD_SCRIPT_VERIFY_FLAGS};
+use crate::multisig::MultiSig;
+use crate::opcodes::OP_0;
+use crate::script::{PushBytes, Script, ScriptBuf};
+use crate::sighash::{EcdsaSighashType, Sighash, SighashCache, SighashSignature};
+use crate::transaction::{Transaction, TxOut};
+
+pub const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff]; // "psbt" 0xff
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_IN_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
+const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+
+/// Master key fingerprint and derivation path of a public key.
+pub type KeySource = ([u8; 4], Vec<u32>);
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct PsbtInput {
+    /// The output being spent.
+    pub prevout: Option<TxOut>,
+    pub redeem_script: Option<ScriptBuf>,
+    pub derivation_paths: BTreeMap<PublicKey, KeySource>,
+    pub partial_sigs: BTreeMap<PublicKey, SighashSignature>,
+    pub sighash_type: Option<EcdsaSighashType>,
+    pub final_script_sig: Option<ScriptBuf>,
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct PsbtOutput {
+    pub redeem_script: Option<ScriptBuf>,
+    pub derivation_paths: BTreeMap<PublicKey, KeySource>,
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Psbt {
+    pub unsigned_tx: Transaction,
+    pub inputs: Vec<PsbtInput>,
+    pub outputs: Vec<PsbtOutput>,
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Psbt {
+    pub fn from_unsigned_tx(tx: Transaction) -> Result<Self, String> {
+        if tx.input.iter().any(|i| !i.script.is_empty()) {
+            return Err("psbt: transaction inputs must have empty scriptSigs".to_string());
+        }
+        Ok(Psbt {
+            inputs: vec![PsbtInput::default(); tx.input.len()],
+            outputs: vec![PsbtOutput::default(); tx.output.len()],
+            unsigned_tx: tx,
+            unknown: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the fee of the transaction, requires all prevouts.
+    pub fn fee(&self) -> Result<u64, String> {
+        let mut input_value: u64 = 0;
+        for (i, input) in self.inputs.iter().enumerate() {
+            let prevout = input
+                .prevout
+                .as_ref()
+                .ok_or_else(|| format!("psbt: input {} has no prevout", i))?;
+            input_value = input_value.saturating_add(prevout.value);
+        }
+        let output_value: u64 = self.unsigned_tx.output.iter().map(|o| o.value).sum();
+        input_value
+            .checked_sub(output_value)
+            .ok_or("psbt: outputs exceed inputs".to_string())
+    }
+
+    /// Returns the script code signed by the input: the redeem script for P2SH,
+    /// otherwise the prevout script_pubkey.
+    pub fn script_code(&self, input_index: usize) -> Result<&Script, String> {
+        let input = self.input(input_index)?;
+        let prevout = input
+            .prevout
+            .as_ref()
+            .ok_or_else(|| format!("psbt: input {} has no prevout", input_index))?;
+        if prevout.script_pubkey.is_p2sh() {
+            let redeem_script = input
+                .redeem_script
+                .as_ref()
+                .ok_or_else(|| format!("psbt: input {} has no redeem script", input_index))?;
+            if ScriptBuf::new_p2sh(&redeem_script.script_hash()) != prevout.script_pubkey {
+                return Err(format!(
+                    "psbt: input {} redeem script does not match prevout",
+                    input_index
+                ));
+            }
+            return Ok(redeem_script);
+        }
+        Ok(&prevout.script_pubkey)
+    }
+
+    /// Computes the signature hash of the input for its sighash type (SIGHASH_ALL by default).
+    pub fn sighash(&self, input_index: usize) -> Result<Sighash, String> {
+        let script_code = self.script_code(input_index)?.to_owned();
+        let sighash_type = self
+            .input(input_index)?
+            .sighash_type
+            .unwrap_or(EcdsaSighashType::All);
+        SighashCache::new(&self.unsigned_tx).signature_hash(input_index, &script_code, sighash_type)
+    }
+
+    /// Verifies and adds a partial signature to the input.
+    pub fn add_signature(
+        &mut self,
+        input_index: usize,
+        pubkey: PublicKey,
+        sig: SighashSignature,
+    ) -> Result<(), String> {
+        let expected = self
+            .input(input_index)?
+            .sighash_type
+            .unwrap_or(EcdsaSighashType::All);
+        if sig.sighash_type != expected {
+            return Err(format!(
+                "psbt: input {} expects sighash type {}",
+                input_index, expected
+            ));
+        }
+        let msg = self.sighash(input_index)?;
+        Secp256k1::verification_only()
+            .verify_ecdsa(&msg.into(), &sig.signature, &pubkey)
+            .map_err(|err| format!("psbt: input {} invalid signature: {}", input_index, err))?;
+        self.inputs[input_index].partial_sigs.insert(pubkey, sig);
+        Ok(())
+    }
+
+    /// Merges the metadata and signatures of another PSBT for the same transaction.
+    pub fn combine(&mut self, other: Psbt) -> Result<(), String> {
+        if self.unsigned_tx.compute_txid() != other.unsigned_tx.compute_txid() {
+            return Err("psbt: cannot combine different transactions".to_string());
+        }
+        for (a, b) in self.inputs.iter_mut().zip(other.inputs) {
+            a.prevout = a.prevout.take().or(b.prevout);
+            if a.final_script_sig.is_none() {
+                a.final_script_sig = b.final_script_sig;
+            }
+            if a.final_script_sig.is_some() {
+                a.clear_signing_data();
+                continue;
+            }
+            a.redeem_script = a.redeem_script.take().or(b.redeem_script);
+            a.sighash_type = a.sighash_type.or(b.sighash_type);
+            a.derivation_paths.extend(b.derivation_paths);
+            a.partial_sigs.extend(b.partial_sigs);
+            a.unknown.extend(b.unknown);
+        }
+        for (a, b) in self.outputs.iter_mut().zip(other.outputs) {
+            a.redeem_script = a.redeem_script.take().or(b.redeem_script);
+            a.derivation_paths.extend(b.derivation_paths);
+            a.unknown.extend(b.unknown);
+        }
+        self.unknown.extend(other.unknown);
+        Ok(())
+    }
+
+    /// Builds the final scriptSig of every input that has enough signatures,
+    /// returns whether all inputs are finalized.
+    pub fn finalize(&mut self) -> Result<bool, String> {
+        for i in 0..self.inputs.len() {
+            if self.inputs[i].final_script_sig.is_some() {
+                continue;
+            }
+            if let Some(script_sig) = self.build_script_sig(i)? {
+                let input = &mut self.inputs[i];
+                input.final_script_sig = Some(script_sig);
+                input.clear_signing_data();
+            }
+        }
+        Ok(self.is_finalized())
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.inputs.iter().all(|i| i.final_script_sig.is_some())
+    }
+
+    /// Extracts the signed transaction from a finalized PSBT and verifies its scripts.
+    pub fn extract_tx(&self) -> Result<Transaction, String> {
+        let mut tx = self.unsigned_tx.clone();
+        for (i, input) in self.inputs.iter().enumerate() {
+            let script_sig = input
+                .final_script_sig
+                .as_ref()
+                .ok_or_else(|| format!("psbt: input {} is not finalized", i))?;
+            tx.input[i].script = script_sig.clone();
+        }
+        for (i, input) in self.inputs.iter().enumerate() {
+            if let Some(prevout) = &input.prevout {
+                verify_input(&tx, i, &prevout.script_pubkey, STANDARD_SCRIPT_VERIFY_FLAGS)?;
+            }
+        }
+        Ok(tx)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&PSBT_MAGIC);
+        write_pair(
+            &mut buf,
+            vec![PSBT_GLOBAL_UNSIGNED_TX],
+            self.unsigned_tx.to_bytes(),
+        );
+        write_unknown(&mut buf, &self.unknown);
+        buf.push(0x00);
+
+        for input in &self.inputs {
+            if let Some(prevout) = &input.prevout {
+                write_pair(&mut buf, vec![PSBT_IN_UTXO], prevout.to_bytes());
+            }
+            for (pk, sig) in &input.partial_sigs {
+                write_pair(
+                    &mut buf,
+                    key_with(PSBT_IN_PARTIAL_SIG, &pk.serialize()),
+                    sig.to_vec(),
+                );
+            }
+            if let Some(sighash_type) = input.sighash_type {
+                write_pair(
+                    &mut buf,
+                    vec![PSBT_IN_SIGHASH_TYPE],
+                    sighash_type.to_u32().to_le_bytes().to_vec(),
+                );
+            }
+            if let Some(script) = &input.redeem_script {
+                write_pair(&mut buf, vec![PSBT_IN_REDEEM_SCRIPT], script.to_bytes());
+            }
+            write_derivation_paths(&mut buf, PSBT_IN_BIP32_DERIVATION, &input.derivation_paths);
+            if let Some(script) = &input.final_script_sig {
+                write_pair(&mut buf, vec![PSBT_IN_FINAL_SCRIPTSIG], script.to_bytes());
+            }
+            write_unknown(&mut buf, &input.unknown);
+            buf.push(0x00);
+        }
+
+        for output in &self.outputs {
+            if let Some(script) = &output.redeem_script {
+                write_pair(&mut buf, vec![PSBT_OUT_REDEEM_SCRIPT], script.to_bytes());
+            }
+            write_derivation_paths(
+                &mut buf,
+                PSBT_OUT_BIP32_DERIVATION,
+                &output.derivation_paths,
+            );
+            write_unknown(&mut buf, &output.unknown);
+            buf.push(0x00);
+        }
+        buf
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, String> {
+        if !data.starts_with(&PSBT_MAGIC) {
+            return Err("psbt: invalid magic".to_string());
+        }
+        let mut r = &data[PSBT_MAGIC.len()..];
+
+        let mut unsigned_tx: Option<Transaction> = None;
+        let mut unknown = BTreeMap::new();
+        for (key, value) in read_map(&mut r)? {
+            match key[0] {
+                PSBT_GLOBAL_UNSIGNED_TX if key.len() == 1 => {
+                    unsigned_tx = Some(decode_value(&value)?);
+                }
+                _ => {
+                    unknown.insert(key, value);
+                }
+            }
+        }
+        let unsigned_tx = unsigned_tx.ok_or("psbt: missing unsigned transaction")?;
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx)?;
+        psbt.unknown = unknown;
+
+        for input in psbt.inputs.iter_mut() {
+            for (key, value) in read_map(&mut r)? {
+                match key[0] {
+                    PSBT_IN_UTXO if key.len() == 1 => {
+                        input.prevout = Some(decode_value(&value)?);
+                    }
+                    PSBT_IN_PARTIAL_SIG => {
+                        let pk = PublicKey::from_slice(&key[1..]).map_err(err_string)?;
+                        let sig = SighashSignature::from_slice(&value).map_err(err_string)?;
+                        input.partial_sigs.insert(pk, sig);
+                    }
+                    PSBT_IN_SIGHASH_TYPE if key.len() == 1 => {
+                        let v: [u8; 4] = value
+                            .as_slice()
+                            .try_into()
+                            .map_err(|_| "psbt: invalid sighash type")?;
+                        let t = EcdsaSighashType::from_standard(u32::from_le_bytes(v))
+                            .map_err(err_string)?;
+                        input.sighash_type = Some(t);
+                    }
+                    PSBT_IN_REDEEM_SCRIPT if key.len() == 1 => {
+                        input.redeem_script = Some(ScriptBuf::from_bytes(value));
+                    }
+                    PSBT_IN_BIP32_DERIVATION => {
+                        let pk = PublicKey::from_slice(&key[1..]).map_err(err_string)?;
+                        input
+                            .derivation_paths
+                            .insert(pk, decode_key_source(&value)?);
+                    }
+                    PSBT_IN_FINAL_SCRIPTSIG if key.len() == 1 => {
+                        input.final_script_sig = Some(ScriptBuf::from_bytes(value));
+                    }
+                    _ => {
+                        input.unknown.insert(key, value);
+                    }
+                }
+            }
+        }
+
+        for output in psbt.outputs.iter_mut() {
+            for (key, value) in read_map(&mut r)? {
+                match key[0] {
+                    PSBT_OUT_REDEEM_SCRIPT if key.len() == 1 => {
+                        output.redeem_script = Some(ScriptBuf::from_bytes(value));
+                    }
+                    PSBT_OUT_BIP32_DERIVATION => {
+                        let pk = PublicKey::from_slice(&key[1..]).map_err(err_string)?;
+                        output
+                            .derivation_paths
+                            .insert(pk, decode_key_source(&value)?);
+                    }
+                    _ => {
+                        output.unknown.insert(key, value);
+                    }
+                }
+            }
+        }
+
+        if !r.is_empty() {
+            return Err("psbt: trailing data".to_string());
+        }
+        Ok(psbt)
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64_std.encode(self.serialize())
+    }
+
+    pub fn from_base64(s: &str) -> Result<Self, String> {
+        let data = base64_std.decode(s).map_err(err_string)?;
+        Self::deserialize(&data)
+    }
+
+    fn input(&self, input_index: usize) -> Result<&PsbtInput, String> {
+        self.inputs
+            .get(input_index)
+            .ok_or_else(|| format!("psbt: input index {} out of range", input_index))
+    }
+
+    fn build_script_sig(&self, input_index: usize) -> Result<Option<ScriptBuf>, String> {
+        let input = &self.inputs[input_index];
+        let script_code = self.script_code(input_index)?;
+        let prevout = input.prevout.as_ref().unwrap();
+        let mut script_sig = ScriptBuf::new();
+
+        if script_code.is_p2pkh() {
+            let hash = &script_code.as_bytes()[3..23];
+            // the signing key may be serialized compressed or uncompressed
+            let found = input.partial_sigs.iter().find_map(|(pk, sig)| {
+                [
+                    pk.serialize().to_vec(),
+                    pk.serialize_uncompressed().to_vec(),
+                ]
+                .into_iter()
+                .find(|key| hash160::Hash::hash(key).as_byte_array() == hash)
+                .map(|key| (key, sig))
+            });
+            let (pk_bytes, sig) = match found {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+            script_sig.push_slice(sig.serialize());
+            script_sig.push_slice(<&PushBytes>::try_from(&pk_bytes[..]).map_err(err_string)?);
+        } else if script_code.is_p2pk() {
+            let pk = script_code
+                .p2pk_public_key()
+                .ok_or("psbt: invalid P2PK script")?;
+            match input.partial_sigs.get(&pk.inner) {
+                Some(sig) => script_sig.push_slice(sig.serialize()),
+                None => return Ok(None),
+            }
+        } else if let Ok(ms) = MultiSig::from_redeem_script(script_code) {
+            let sigs: Vec<&SighashSignature> = ms
+                .pubkeys
+                .iter()
+                .filter_map(|pk| input.partial_sigs.get(pk))
+                .take(ms.required)
+                .collect();
+            if sigs.len() < ms.required {
+                return Ok(None);
+            }
+            // A bug in OP_CHECKMULTISIG consumes one extra stack element.
+            script_sig.push_opcode(OP_0.into());
+            for sig in sigs {
+                script_sig.push_slice(sig.serialize());
+            }
+        } else {
+            return Err(format!(
+                "psbt: input {} has an unsupported script type",
+                input_index
+            ));
+        }
+
+        if prevout.script_pubkey.is_p2sh() {
+            let redeem_script = input.redeem_script.as_ref().unwrap();
+            script_sig
+                .push_slice(<&PushBytes>::try_from(redeem_script.as_bytes()).map_err(err_string)?);
+        }
+        Ok(Some(script_sig))
+    }
+}
+
+impl PsbtInput {
+    // BIP174: the finalizer removes everything but the UTXO and final scripts.
+    fn clear_signing_data(&mut self) {
+        self.partial_sigs.clear();
+        self.sighash_type = None;
+        self.redeem_script = None;
+        self.derivation_paths.clear();
+    }
+}
+
+impl std::fmt::Display for Psbt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_base64())
+    }
+}
+
+impl FromStr for Psbt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_base64(s)
+    }
+}
+
+fn key_with(key_type: u8, data: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(data.len() + 1);
+    key.push(key_type);
+    key.extend_from_slice(data);
+    key
+}
+
+fn write_pair(buf: &mut Vec<u8>, key: Vec<u8>, value: Vec<u8>) {
+    key.consensus_encode(buf).unwrap();
+    value.consensus_encode(buf).unwrap();
+}
+
+fn write_unknown(buf: &mut Vec<u8>, unknown: &BTreeMap<Vec<u8>, Vec<u8>>) {
+    for (key, value) in unknown {
+        write_pair(buf, key.clone(), value.clone());
+    }
+}
+
+fn write_derivation_paths(buf: &mut Vec<u8>, key_type: u8, paths: &BTreeMap<PublicKey, KeySource>) {
+    for (pk, (fingerprint, path)) in paths {
+        let mut value = Vec::with_capacity(4 + path.len() * 4);
+        value.extend_from_slice(fingerprint);
+        for i in path {
+            value.extend_from_slice(&i.to_le_bytes());
+        }
+        write_pair(buf, key_with(key_type, &pk.serialize()), value);
+    }
+}
+
+fn read_map(r: &mut &[u8]) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, String> {
+    let mut pairs = BTreeMap::new();
+    loop {
+        let key: Vec<u8> = Decodable::consensus_decode(r).map_err(err_string)?;
+        if key.is_empty() {
+            return Ok(pairs);
+        }
+        let value: Vec<u8> = Decodable::consensus_decode(r).map_err(err_string)?;
+        if pairs.contains_key(&key) {
+            return Err(format!("psbt: duplicate key {}", key.to_lower_hex_string()));
+        }
+        pairs.insert(key, value);
+    }
+}
+
+fn decode_value<T: Decodable>(data: &[u8]) -> Result<T, String> {
+    encode::deserialize(data).map_err(err_string)
+}
+
+fn decode_key_source(data: &[u8]) -> Result<KeySource, String> {
+    if data.len() < 4 || !data.len().is_multiple_of(4) {
+        return Err("psbt: invalid key source".to_string());
+    }
+    let mut fingerprint = [0u8; 4];
+    fingerprint.copy_from_slice(&data[..4]);
+    let path = data[4..]
+        .chunks(4)
+        .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    Ok((fingerprint, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sighash::SecretKey;
+    use crate::transaction::{OutPoint, TxIn, Txid};
+
+    fn sign(psbt: &mut Psbt, input_index: usize, key: &SecretKey) -> Result<(), String> {
+        let secp = Secp256k1::new();
+        let msg = psbt.sighash(input_index)?;
+        let sig = SighashSignature {
+            signature: secp.sign_ecdsa(&msg.into(), key),
+            sighash_type: EcdsaSighashType::All,
+        };
+        psbt.add_signature(input_index, key.public_key(&secp), sig)
+    }
+
+    #[test]
+    fn test_psbt() {
+        let secp = Secp256k1::new();
+        let keys: Vec<SecretKey> = (1..=3u8)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        let pubkeys: Vec<PublicKey> = keys.iter().map(|k| k.public_key(&secp)).collect();
+        let ms = MultiSig::new(2, pubkeys.clone()).unwrap();
+        let p2pkh = ScriptBuf::new_p2pkh(&crate::script::PubkeyHash::hash(
+            &pubkeys[0].serialize_uncompressed(),
+        ));
+
+        let tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![
+                TxIn::with_outpoint(OutPoint {
+                    txid: Txid::hash(b"p2pkh"),
+                    vout: 0,
+                }),
+                TxIn::with_outpoint(OutPoint {
+                    txid: Txid::hash(b"p2sh"),
+                    vout: 1,
+                }),
+            ],
+            output: vec![TxOut {
+                value: 290_000_000,
+                script_pubkey: p2pkh.clone(),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+        psbt.inputs[0].prevout = Some(TxOut {
+            value: 100_000_000,
+            script_pubkey: p2pkh.clone(),
+        });
+        psbt.inputs[1].prevout = Some(TxOut {
+            value: 200_000_000,
+            script_pubkey: ms.script_pubkey(),
+        });
+        assert!(psbt.sighash(1).is_err());
+        psbt.inputs[1].redeem_script = Some(ms.redeem_script());
+        psbt.inputs[1].derivation_paths.insert(
+            pubkeys[1],
+            ([1, 2, 3, 4], vec![0x8000002c, 0x80000003, 0x80000000, 0, 1]),
+        );
+        psbt.outputs[0]
+            .unknown
+            .insert(vec![0xfc, 0x01], vec![0xde, 0xad]);
+        assert_eq!(psbt.fee().unwrap(), 10_000_000);
+
+        let decoded = Psbt::from_str(&psbt.to_string()).unwrap();
+        assert_eq!(decoded, psbt);
+
+        // two co-signers sign their copies
+        let mut psbt1 = decoded.clone();
+        sign(&mut psbt1, 0, &keys[0]).unwrap();
+        sign(&mut psbt1, 1, &keys[2]).unwrap();
+        let mut psbt2 = decoded.clone();
+        // a signature for another input is rejected
+        let msg = psbt2.sighash(0).unwrap();
+        let sig = SighashSignature {
+            signature: secp.sign_ecdsa(&msg.into(), &keys[1]),
+            sighash_type: EcdsaSighashType::All,
+        };
+        assert!(psbt2.add_signature(1, pubkeys[1], sig).is_err());
+        sign(&mut psbt2, 1, &keys[1]).unwrap();
+
+        assert!(!psbt1.clone().finalize().unwrap());
+        assert!(psbt1.extract_tx().is_err());
+
+        let mut combined = Psbt::deserialize(&psbt1.serialize()).unwrap();
+        combined.combine(psbt2).unwrap();
+        assert_eq!(combined.inputs[1].partial_sigs.len(), 2);
+        assert!(combined.finalize().unwrap());
+        assert!(combined.inputs[1].partial_sigs.is_empty());
+        assert!(combined.inputs[1].derivation_paths.is_empty());
+
+        let signed = Psbt::from_base64(&combined.to_base64())
+            .unwrap()
+            .extract_tx()
+            .unwrap();
+        assert_eq!(signed.output, tx.output);
+        assert!(!signed.input[0].script.is_empty());
+
+        assert!(Psbt::from_unsigned_tx(signed.clone()).is_err());
+        let mut changed = tx.clone();
+        changed.lock_time = 1;
+        assert!(combined
+            .combine(Psbt::from_unsigned_tx(changed).unwrap())
+            .is_err());
+
+        assert!(Psbt::deserialize(&[0x70, 0x73, 0x62, 0x74, 0x00]).is_err());
+        let mut data = combined.serialize();
+        data.push(0);
+        assert!(Psbt::deserialize(&data).is_err());
+    }
+}
@@ -2,7 +2,9 @@ use bitcoin::base58;
 use bitcoin::hashes::{hash160, Hash};
 use std::str::FromStr;
 
+use crate::address;
 use crate::chainparams::ChainParams;
+use crate::err_string;
 use crate::opcodes::*;
 
 pub use bitcoin::key::PubkeyHash;
@@ -43,6 +45,8 @@ pub const ECPRIV_KEY_LEN: usize = 32; // bytes.
 pub const ECPUB_KEY_COMPRESSED_LEN: usize = 33; // bytes: [x02/x03][32-X] 2=even 3=odd
 pub const ECPUB_KEY_UNCOMPRESSED_LEN: usize = 65; // bytes: [x04][32-X][32-Y]
 
+/// Raw version byte and hash160, not checked against any chain.
+/// Use `address::Address::parse` to validate addresses from users.
 #[derive(Clone, PartialEq, Eq, Debug, Hash, Default)]
 pub struct Address(pub [u8; 21]); // Dogecoin address (base-58 Public Key Hash aka PKH)
 impl Address {
@@ -58,13 +62,34 @@ impl Address {
         self.0[0] == chain.p2pkh_address_prefix || self.0[0] == chain.p2sh_address_prefix
     }
 
+    /// Parses a base58check address of the given chain.
+    pub fn parse(s: &str, chain: &ChainParams) -> Result<Self, String> {
+        let addr = address::Address::parse(s, chain).map_err(err_string)?;
+        Ok(addr.into())
+    }
+
+    /// Returns the P2PKH or P2SH script_pubkey, or an empty script if the address is not
+    /// of the chain.
+    #[deprecated(note = "use `try_to_script`, which rejects addresses of other chains")]
     pub fn to_script(&self, chain: &ChainParams) -> ScriptBuf {
+        self.try_to_script(chain).unwrap_or_default()
+    }
+
+    /// Returns the P2PKH or P2SH script_pubkey, or an error if the address is not of the chain.
+    pub fn try_to_script(&self, chain: &ChainParams) -> Result<ScriptBuf, String> {
         if self.is_p2pkh(chain) {
-            ScriptBuf::new_p2pkh(&PubkeyHash::from_slice(&self.0[1..]).unwrap())
+            Ok(ScriptBuf::new_p2pkh(
+                &PubkeyHash::from_slice(&self.0[1..]).unwrap(),
+            ))
         } else if self.is_p2sh(chain) {
-            ScriptBuf::new_p2sh(&ScriptHash::from_slice(&self.0[1..]).unwrap())
+            Ok(ScriptBuf::new_p2sh(
+                &ScriptHash::from_slice(&self.0[1..]).unwrap(),
+            ))
         } else {
-            ScriptBuf::default()
+            Err(format!(
+                "try_to_script: address is not valid on the {} chain",
+                chain.chain_name
+            ))
         }
     }
 }
@@ -75,6 +100,7 @@ impl std::fmt::Display for Address {
     }
 }
 
+/// Deprecated: the network is not checked, use [`Address::parse`] instead.
 impl FromStr for Address {
     type Err = String;
 
@@ -199,6 +225,45 @@ pub fn classify_script(script: &[u8], chain: &ChainParams) -> (ScriptType, Optio
     (ScriptType::NonStandard, None)
 }
 
+/// Reads the operation at `pc` and advances it, returning the opcode and its push data,
+/// mirroring CScript::GetOp. Returns None at the end of the script or on a truncated push.
+pub fn get_op<'a>(script: &'a [u8], pc: &mut usize) -> Option<(u8, &'a [u8])> {
+    let opcode = *script.get(*pc)?;
+    let mut i = *pc + 1;
+    let size = match opcode {
+        op if op < OP_PUSHDATA1 => op as usize,
+        OP_PUSHDATA1 => {
+            let n = *script.get(i)? as usize;
+            i += 1;
+            n
+        }
+        OP_PUSHDATA2 => {
+            let n = u16::from_le_bytes(script.get(i..i + 2)?.try_into().unwrap()) as usize;
+            i += 2;
+            n
+        }
+        OP_PUSHDATA4 => {
+            let n = u32::from_le_bytes(script.get(i..i + 4)?.try_into().unwrap()) as usize;
+            i += 4;
+            n
+        }
+        _ => 0,
+    };
+    let data = script.get(i..i.checked_add(size)?)?;
+    *pc = i + size;
+    Some((opcode, data))
+}
+
+/// Iterates over the (start, end, opcode) of each operation until the end of the script
+/// or a truncated push.
+pub fn script_ops(script: &[u8]) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+    let mut pc = 0;
+    std::iter::from_fn(move || {
+        let start = pc;
+        get_op(script, &mut pc).map(|(opcode, _)| (start, pc, opcode))
+    })
+}
+
 fn is_op_n1(op: u8) -> bool {
     (OP_1..=OP_16).contains(&op)
 }
@@ -1,4 +1,3 @@
-use bitcoin::base58;
 use bitcoin::consensus::Encodable;
 use bitcoin::hashes::{hash_newtype, sha256d, Hash};
 use bitcoin_io::Write;
@@ -9,8 +8,9 @@ pub use bitcoin::ecdsa::Signature as SighashSignature;
 pub use bitcoin::secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
 pub use bitcoin::EcdsaSighashType;
 
-use crate::chainparams::chain_from_wif;
-use crate::script::ScriptBuf;
+use crate::key::PrivateKey;
+use crate::opcodes::{OP_0, OP_CODESEPARATOR};
+use crate::script::{script_ops, PushBytes, Script, ScriptBuf};
 use crate::{err_string, transaction::*};
 
 hash_newtype! {
@@ -66,17 +66,35 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
         input_index: usize,
         script_pubkey: &ScriptBuf,
         sighash_type: EcdsaSighashType,
+    ) -> Result<bool, String> {
+        self.encode_legacy_signing_data_to(
+            writer,
+            input_index,
+            script_pubkey.as_script(),
+            sighash_type.to_u32(),
+        )
+    }
+
+    /// Encodes the signing data with a raw sighash type, as it appears at the end of a signature.
+    /// Like Dogecoin Core, undefined types are treated as SIGHASH_ALL but still committed to,
+    /// and OP_CODESEPARATORs are removed from the script code.
+    pub fn encode_legacy_signing_data_to<W: Write + ?Sized>(
+        &self,
+        writer: &mut W,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: u32,
     ) -> Result<bool, String> {
         // Validate input_index.
         if input_index >= self.tx.borrow().input.len() {
             return Err(format!("input index {} out of range", input_index));
         }
 
-        if is_invalid_use_of_sighash_single(
-            sighash_type,
-            input_index,
-            self.tx.borrow().output.len(),
-        ) {
+        let sighash = sighash_type & 0x1f;
+        let anyone_can_pay = sighash_type & 0x80 != 0;
+        let sighash_none = sighash == EcdsaSighashType::None as u32;
+        let sighash_single = sighash == EcdsaSighashType::Single as u32;
+        if sighash_single && input_index >= self.tx.borrow().output.len() {
             // We cannot correctly handle the SIGHASH_SINGLE bug here because usage of this function
             // will result in the data written to the writer being hashed, however the correct
             // handling of the SIGHASH_SINGLE bug is to return the 'one array' - either implement
@@ -84,7 +102,7 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
             return Ok(false);
         }
 
-        let (sighash, anyone_can_pay) = split_anyonecanpay_flag(sighash_type);
+        let script_code = remove_codeseparators(script_code);
         let stx = self.tx.borrow();
         // Build tx to sign
         let mut tx = Transaction {
@@ -97,7 +115,7 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
         if anyone_can_pay {
             tx.input = vec![TxIn {
                 prevout: stx.input[input_index].prevout,
-                script: script_pubkey.clone(),
+                script: script_code.clone(),
                 sequence: stx.input[input_index].sequence,
                 witness: Witness::default(),
             }];
@@ -107,14 +125,11 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
                 tx.input.push(TxIn {
                     prevout: input.prevout,
                     script: if n == input_index {
-                        script_pubkey.clone()
+                        script_code.clone()
                     } else {
                         ScriptBuf::new()
                     },
-                    sequence: if n != input_index
-                        && (sighash == EcdsaSighashType::Single
-                            || sighash == EcdsaSighashType::None)
-                    {
+                    sequence: if n != input_index && (sighash_single || sighash_none) {
                         0
                     } else {
                         input.sequence
@@ -124,30 +139,27 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
             }
         }
         // ..then all outputs
-        tx.output = match sighash {
-            EcdsaSighashType::All => stx.output.clone(),
-            EcdsaSighashType::Single => {
-                let output_iter = stx
-                    .output
-                    .iter()
-                    .take(input_index + 1) // sign all outputs up to and including this one, but erase
-                    .enumerate() // all of them except for this one
-                    .map(|(n, out)| {
-                        if n == input_index {
-                            out.clone()
-                        } else {
-                            TxOut::default()
-                        }
-                    });
-                output_iter.collect()
-            }
-            EcdsaSighashType::None => vec![],
-            _ => unreachable!(),
+        tx.output = if sighash_single {
+            stx.output
+                .iter()
+                .take(input_index + 1) // sign all outputs up to and including this one, but erase
+                .enumerate() // all of them except for this one
+                .map(|(n, out)| {
+                    if n == input_index {
+                        out.clone()
+                    } else {
+                        TxOut::default()
+                    }
+                })
+                .collect()
+        } else if sighash_none {
+            vec![]
+        } else {
+            stx.output.clone()
         };
         // hash the result
         tx.consensus_encode(writer).map_err(|err| err.to_string())?;
         sighash_type
-            .to_u32()
             .to_le_bytes()
             .consensus_encode(writer)
             .map_err(|err| err.to_string())?;
@@ -168,6 +180,27 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
             Err(e) => Err(e),
         }
     }
+
+    /// Computes a signature hash for a given input index with a raw sighash type,
+    /// as used by the script interpreter.
+    pub fn legacy_signature_hash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: u32,
+    ) -> Result<Sighash, String> {
+        let mut engine = Sighash::engine();
+        match self.encode_legacy_signing_data_to(
+            &mut engine,
+            input_index,
+            script_code,
+            sighash_type,
+        ) {
+            Ok(true) => Ok(Sighash::from_engine(engine)),
+            Ok(false) => Ok(Sighash::from_byte_array(UINT256_ONE)),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 impl<R: BorrowMut<Transaction>> SighashCache<R> {
@@ -193,41 +226,54 @@ impl<R: BorrowMut<Transaction>> SighashCache<R> {
             })
             .ok_or("input index out of range".to_string())
     }
+
+    /// Sets a P2SH multisig scriptSig: OP_0 <signature*m> <redeem script>.
+    /// Signatures must be in the same order as their public keys in the redeem script.
+    pub fn set_input_multisig_script(
+        &mut self,
+        input_index: usize,
+        signatures: &[SighashSignature],
+        redeem_script: &Script,
+    ) -> Result<(), String> {
+        let redeem_script = <&PushBytes>::try_from(redeem_script.as_bytes()).map_err(err_string)?;
+        self.tx
+            .borrow_mut()
+            .input
+            .get_mut(input_index)
+            .map(|i| {
+                let mut buf = ScriptBuf::new();
+                buf.push_opcode(OP_0.into());
+                for sig in signatures {
+                    buf.push_slice(sig.serialize());
+                }
+                buf.push_slice(redeem_script);
+                i.script = buf;
+            })
+            .ok_or("input index out of range".to_string())
+    }
 }
 
 // https://en.bitcoin.it/wiki/Wallet_import_format
 pub fn decode_secretkey_wif(sk: &str) -> Result<SecretKey, String> {
-    match base58::decode_check(sk) {
-        Ok(data) => {
-            let chain = chain_from_wif(sk);
-            if data[0] != chain.pkey_prefix {
-                return Err("wrong key prefix".to_string());
-            }
-            let key = SecretKey::from_slice(&data[1..33]).map_err(err_string)?;
-            Ok(key)
-        }
-        Err(_) => Err("invalid base58 secret key".to_string()),
-    }
+    PrivateKey::from_wif(sk).map(|key| key.inner)
 }
 
-fn split_anyonecanpay_flag(st: EcdsaSighashType) -> (EcdsaSighashType, bool) {
-    use EcdsaSighashType::*;
-    match st {
-        All => (All, false),
-        None => (None, false),
-        Single => (Single, false),
-        AllPlusAnyoneCanPay => (All, true),
-        NonePlusAnyoneCanPay => (None, true),
-        SinglePlusAnyoneCanPay => (Single, true),
+// Dogecoin Core's CTransactionSignatureSerializer skips OP_CODESEPARATORs in the script code.
+fn remove_codeseparators(script: &Script) -> ScriptBuf {
+    if !script.as_bytes().contains(&OP_CODESEPARATOR) {
+        return script.to_owned();
     }
-}
-
-fn is_invalid_use_of_sighash_single(
-    ty: EcdsaSighashType,
-    input_index: usize,
-    outputs_len: usize,
-) -> bool {
-    ty == EcdsaSighashType::Single && input_index >= outputs_len
+    let data = script.as_bytes();
+    let mut res = Vec::with_capacity(data.len());
+    let mut last = 0;
+    for (start, end, opcode) in script_ops(data) {
+        if opcode == OP_CODESEPARATOR {
+            res.extend_from_slice(&data[last..start]);
+            last = end;
+        }
+    }
+    res.extend_from_slice(&data[last..]);
+    ScriptBuf::from_bytes(res)
 }
 
 #[cfg(test)]
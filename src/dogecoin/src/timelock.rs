@@ -0,0 +1,448 @@
+//! Timelocks and P2SH timelock scripts.
+//!
+//! Dogecoin mainnet enforces nLockTime and OP_CHECKLOCKTIMEVERIFY (BIP65) by consensus,
+//! but never activated BIP68, BIP112 and BIP113. OP_CHECKSEQUENCEVERIFY is a NOP to
+//! consensus, relative locks and the median-time-past rule are only policy of the
+//! nodes' mempools, and a miner can include a transaction that breaks them.
+//! Use an absolute lock (CLTV) for funds that must stay locked, e.g. a treasury.
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::chainparams::ChainParams;
+use crate::interpreter::{scriptnum_decode, scriptnum_encode, LOCKTIME_THRESHOLD};
+use crate::opcodes::*;
+use crate::script::{get_op, p2sh_address, Address, PushBytes, Script, ScriptBuf};
+use crate::sighash::SighashSignature;
+use crate::transaction::{Transaction, TxIn};
+
+/// Input sequence that enables nLockTime without signaling replacement.
+pub const SEQUENCE_ENABLE_LOCKTIME: u32 = 0xfffffffe;
+
+/// Absolute timelock of nLockTime and OP_CHECKLOCKTIMEVERIFY (BIP65).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum LockTime {
+    /// Block height, below LOCKTIME_THRESHOLD.
+    Blocks(u32),
+    /// UNIX timestamp, compared against the median time past (BIP113) by policy
+    /// and against the block time by consensus.
+    Seconds(u32),
+}
+
+impl LockTime {
+    pub const ZERO: LockTime = LockTime::Blocks(0);
+
+    pub fn from_consensus(n: u32) -> Self {
+        if n < LOCKTIME_THRESHOLD {
+            LockTime::Blocks(n)
+        } else {
+            LockTime::Seconds(n)
+        }
+    }
+
+    pub fn from_height(height: u32) -> Result<Self, String> {
+        if height >= LOCKTIME_THRESHOLD {
+            return Err(format!("timelock: invalid block height {}", height));
+        }
+        Ok(LockTime::Blocks(height))
+    }
+
+    pub fn from_time(time: u32) -> Result<Self, String> {
+        if time < LOCKTIME_THRESHOLD {
+            return Err(format!("timelock: invalid timestamp {}", time));
+        }
+        Ok(LockTime::Seconds(time))
+    }
+
+    pub fn to_consensus_u32(self) -> u32 {
+        match self {
+            LockTime::Blocks(n) | LockTime::Seconds(n) => n,
+        }
+    }
+
+    pub fn is_same_unit(self, other: LockTime) -> bool {
+        matches!(
+            (self, other),
+            (LockTime::Blocks(_), LockTime::Blocks(_))
+                | (LockTime::Seconds(_), LockTime::Seconds(_))
+        )
+    }
+
+    /// Returns true if a transaction with this lock time can be included in the block
+    /// at `height`, `time` is the median time past of the previous block.
+    pub fn is_satisfied_by(self, height: u32, time: u32) -> bool {
+        match self {
+            LockTime::Blocks(n) => n < height,
+            LockTime::Seconds(n) => n < time,
+        }
+    }
+}
+
+/// Relative timelock of nSequence and OP_CHECKSEQUENCEVERIFY (BIP68, BIP112).
+/// It is only enforced for transactions with version 2 or higher, and only by policy
+/// on Dogecoin, see the module docs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum RelativeLockTime {
+    Blocks(u16),
+    /// Number of 512 second intervals.
+    Time(u16),
+}
+
+impl RelativeLockTime {
+    /// Decodes an input sequence, returns None when the relative lock time is disabled.
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & TxIn::SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let n = (sequence & TxIn::SEQUENCE_LOCKTIME_MASK) as u16;
+        if sequence & TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            Some(RelativeLockTime::Time(n))
+        } else {
+            Some(RelativeLockTime::Blocks(n))
+        }
+    }
+
+    /// Rounds the seconds up to the next 512 second interval.
+    pub fn from_seconds_ceil(seconds: u32) -> Result<Self, String> {
+        let intervals = seconds.div_ceil(1 << TxIn::SEQUENCE_LOCKTIME_GRANULARITY);
+        if intervals > u16::MAX as u32 {
+            return Err(format!("timelock: relative time {}s overflows", seconds));
+        }
+        Ok(RelativeLockTime::Time(intervals as u16))
+    }
+
+    pub fn to_sequence(self) -> u32 {
+        match self {
+            RelativeLockTime::Blocks(n) => n as u32,
+            RelativeLockTime::Time(n) => TxIn::SEQUENCE_LOCKTIME_TYPE_FLAG | n as u32,
+        }
+    }
+
+    /// Returns true if an output confirmed `height_delta` blocks and `time_delta` seconds
+    /// (of median time past) ago can be spent with this lock time.
+    pub fn is_satisfied_by(self, height_delta: u32, time_delta: u32) -> bool {
+        match self {
+            RelativeLockTime::Blocks(n) => height_delta >= n as u32,
+            RelativeLockTime::Time(n) => {
+                time_delta >= (n as u32) << TxIn::SEQUENCE_LOCKTIME_GRANULARITY
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum TimeLock {
+    /// Guarded by OP_CHECKLOCKTIMEVERIFY.
+    Absolute(LockTime),
+    /// Guarded by OP_CHECKSEQUENCEVERIFY, which Dogecoin enforces by policy only,
+    /// not by consensus. Prefer `Absolute` for funds that must stay locked.
+    Relative(RelativeLockTime),
+}
+
+impl TimeLock {
+    fn script_num(&self) -> i64 {
+        match self {
+            TimeLock::Absolute(lock) => lock.to_consensus_u32() as i64,
+            TimeLock::Relative(lock) => lock.to_sequence() as i64,
+        }
+    }
+
+    // size of the lock time push in the redeem script
+    pub(crate) fn push_size(&self) -> usize {
+        match self.script_num() {
+            0..=16 => 1,
+            n => 1 + scriptnum_encode(n).len(),
+        }
+    }
+
+    fn opcode(&self) -> u8 {
+        match self {
+            TimeLock::Absolute(_) => OP_CHECKLOCKTIMEVERIFY,
+            TimeLock::Relative(_) => OP_CHECKSEQUENCEVERIFY,
+        }
+    }
+
+    /// Returns true if an output locked by this timelock can be spent in the block at
+    /// `height` with median time past `time`. `coin_height` and `coin_time` are the
+    /// height of the block that confirmed the output and the median time past of its
+    /// previous block, they are only used by relative timelocks.
+    pub fn is_satisfied_by(
+        &self,
+        height: u32,
+        time: u32,
+        coin_height: u32,
+        coin_time: u32,
+    ) -> bool {
+        match self {
+            TimeLock::Absolute(lock) => lock.is_satisfied_by(height, time),
+            TimeLock::Relative(lock) => lock.is_satisfied_by(
+                height.saturating_sub(coin_height),
+                time.saturating_sub(coin_time),
+            ),
+        }
+    }
+}
+
+/// A timelocked P2SH redeem script:
+/// <n> OP_CHECKLOCKTIMEVERIFY|OP_CHECKSEQUENCEVERIFY OP_DROP <pubkey> OP_CHECKSIG
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TimeLockScript {
+    pub lock: TimeLock,
+    pub pubkey: PublicKey,
+}
+
+impl TimeLockScript {
+    pub fn new(lock: TimeLock, pubkey: PublicKey) -> Self {
+        TimeLockScript { lock, pubkey }
+    }
+
+    pub fn from_redeem_script(script: &Script) -> Result<Self, String> {
+        let data = script.as_bytes();
+        let mut pc = 0;
+        let mut ops = Vec::new();
+        while pc < data.len() {
+            ops.push(get_op(data, &mut pc).ok_or("timelock: bad opcode")?);
+        }
+        if ops.len() != 5 || ops[2].0 != OP_DROP || ops[4].0 != OP_CHECKSIG {
+            return Err("timelock: not a timelock script".to_string());
+        }
+        let n = match ops[0] {
+            (OP_0, _) => 0,
+            (op, _) if (OP_1..=OP_16).contains(&op) => (op - OP_1 + 1) as i64,
+            (op, push) if op <= OP_PUSHDATA4 => scriptnum_decode(push, 5)?,
+            _ => return Err("timelock: invalid lock time".to_string()),
+        };
+        let n = u32::try_from(n).map_err(|_| "timelock: negative lock time")?;
+        let lock = match ops[1].0 {
+            OP_CHECKLOCKTIMEVERIFY => TimeLock::Absolute(LockTime::from_consensus(n)),
+            OP_CHECKSEQUENCEVERIFY => TimeLock::Relative(
+                RelativeLockTime::from_sequence(n).ok_or("timelock: relative lock disabled")?,
+            ),
+            _ => return Err("timelock: not a timelock script".to_string()),
+        };
+        let pubkey = PublicKey::from_slice(ops[3].1).map_err(|err| format!("timelock: {}", err))?;
+        let ts = TimeLockScript { lock, pubkey };
+        // reject non-minimal numbers, extra sequence bits and uncompressed keys
+        if ts.redeem_script().as_script() != script {
+            return Err("timelock: non-canonical redeem script".to_string());
+        }
+        Ok(ts)
+    }
+
+    pub fn redeem_script(&self) -> ScriptBuf {
+        let mut script = ScriptBuf::new();
+        match self.lock.script_num() {
+            0 => script.push_opcode(OP_0.into()),
+            n @ 1..=16 => script.push_opcode((OP_1 + n as u8 - 1).into()),
+            n => script.push_slice(<&PushBytes>::try_from(&scriptnum_encode(n)[..]).unwrap()),
+        }
+        script.push_opcode(self.lock.opcode().into());
+        script.push_opcode(OP_DROP.into());
+        script.push_slice(self.pubkey.serialize());
+        script.push_opcode(OP_CHECKSIG.into());
+        script
+    }
+
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        ScriptBuf::new_p2sh(&self.redeem_script().script_hash())
+    }
+
+    pub fn address(&self, chain: &ChainParams) -> Address {
+        p2sh_address(self.redeem_script().as_bytes(), chain).unwrap()
+    }
+
+    /// Sets the lock time, sequence and version of the spending transaction so that
+    /// the timelock of the input is satisfied. Must be called before signing.
+    pub fn prepare_spend(&self, tx: &mut Transaction, input_index: usize) -> Result<(), String> {
+        if input_index >= tx.input.len() {
+            return Err(format!(
+                "timelock: input index {} out of range",
+                input_index
+            ));
+        }
+        match self.lock {
+            TimeLock::Absolute(lock) => {
+                let tx_lock = LockTime::from_consensus(tx.lock_time);
+                if tx.lock_time > 0 && !tx_lock.is_same_unit(lock) {
+                    return Err("timelock: lock time unit mismatch".to_string());
+                }
+                tx.lock_time = tx.lock_time.max(lock.to_consensus_u32());
+                if tx.input[input_index].sequence == u32::MAX {
+                    tx.input[input_index].sequence = SEQUENCE_ENABLE_LOCKTIME;
+                }
+            }
+            TimeLock::Relative(lock) => {
+                tx.version = tx.version.max(2);
+                tx.input[input_index].sequence = lock.to_sequence();
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the P2SH scriptSig: <sig> <redeem script>.
+    pub fn script_sig(&self, sig: &SighashSignature) -> ScriptBuf {
+        let mut script = ScriptBuf::new();
+        script.push_slice(sig.serialize());
+        script.push_slice(<&PushBytes>::try_from(self.redeem_script().as_bytes()).unwrap());
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::InputKind;
+    use crate::chainparams::DOGE_MAIN_NET_CHAIN;
+    use crate::interpreter::{verify_input, STANDARD_SCRIPT_VERIFY_FLAGS};
+    use crate::sighash::{EcdsaSighashType, SecretKey, SighashCache};
+    use crate::transaction::{OutPoint, TxOut};
+    use bitcoin::secp256k1::Secp256k1;
+
+    #[test]
+    fn test_lock_time() {
+        assert_eq!(
+            LockTime::from_consensus(499_999_999),
+            LockTime::Blocks(499_999_999)
+        );
+        assert_eq!(
+            LockTime::from_consensus(500_000_000),
+            LockTime::Seconds(500_000_000)
+        );
+        assert!(LockTime::from_height(500_000_000).is_err());
+        assert!(LockTime::from_time(1000).is_err());
+        assert!(LockTime::Blocks(100).is_satisfied_by(101, 0));
+        assert!(!LockTime::Blocks(100).is_satisfied_by(100, u32::MAX));
+        assert!(LockTime::Seconds(1_700_000_000).is_satisfied_by(0, 1_700_000_001));
+        assert!(!LockTime::Seconds(1_700_000_000).is_satisfied_by(u32::MAX, 1_700_000_000));
+
+        assert_eq!(RelativeLockTime::from_sequence(u32::MAX), None);
+        assert_eq!(
+            RelativeLockTime::from_sequence(0x00400010),
+            Some(RelativeLockTime::Time(16))
+        );
+        assert_eq!(
+            RelativeLockTime::from_sequence(0x00010010),
+            Some(RelativeLockTime::Blocks(16))
+        );
+        let one_day = RelativeLockTime::from_seconds_ceil(86_400).unwrap();
+        assert_eq!(one_day, RelativeLockTime::Time(169));
+        assert_eq!(one_day.to_sequence(), 0x004000a9);
+        assert!(one_day.is_satisfied_by(0, 169 * 512));
+        assert!(!one_day.is_satisfied_by(u32::MAX, 169 * 512 - 1));
+        assert!(RelativeLockTime::from_seconds_ceil(u16::MAX as u32 * 512 + 1).is_err());
+
+        let mut tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn::with_outpoint(OutPoint::default())],
+            output: vec![],
+        };
+        assert!(tx.is_final(0, 0));
+        tx.lock_time = 1000;
+        assert!(tx.is_final(1001, 0));
+        // final sequences disable the lock time
+        assert!(tx.is_final(1000, 0));
+        tx.input[0].sequence = SEQUENCE_ENABLE_LOCKTIME;
+        assert!(!tx.is_final(1000, u32::MAX));
+        tx.lock_time = 1_700_000_000;
+        assert!(!tx.is_final(u32::MAX, 1_700_000_000));
+        assert!(tx.is_final(0, 1_700_000_001));
+    }
+
+    #[test]
+    fn test_timelock_script() {
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let pubkey = key.public_key(&secp);
+
+        for (lock, minimal) in [
+            (TimeLock::Absolute(LockTime::Blocks(5_000_000)), false),
+            (TimeLock::Absolute(LockTime::Blocks(16)), true),
+            (TimeLock::Absolute(LockTime::Seconds(1_700_000_000)), false),
+            (TimeLock::Relative(RelativeLockTime::Blocks(1440)), false),
+            (TimeLock::Relative(RelativeLockTime::Time(169)), false),
+        ] {
+            let ts = TimeLockScript::new(lock, pubkey);
+            let redeem_script = ts.redeem_script();
+            assert_eq!(redeem_script.as_bytes()[0] == OP_16, minimal);
+            assert_eq!(redeem_script.len(), lock.push_size() + 37);
+            assert_eq!(
+                TimeLockScript::from_redeem_script(&redeem_script).unwrap(),
+                ts
+            );
+            assert_eq!(
+                ts.address(&DOGE_MAIN_NET_CHAIN)
+                    .try_to_script(&DOGE_MAIN_NET_CHAIN)
+                    .unwrap(),
+                ts.script_pubkey()
+            );
+
+            let mut tx = Transaction {
+                version: 1,
+                lock_time: 0,
+                input: vec![TxIn::with_outpoint(OutPoint::default())],
+                output: vec![TxOut {
+                    value: 100_000_000,
+                    script_pubkey: ts.script_pubkey(),
+                }],
+            };
+            let sign = |tx: &mut Transaction| {
+                let msg = SighashCache::new(&*tx)
+                    .signature_hash(0, &redeem_script, EcdsaSighashType::All)
+                    .unwrap();
+                let sig = SighashSignature {
+                    signature: secp.sign_ecdsa(&msg.into(), &key),
+                    sighash_type: EcdsaSighashType::All,
+                };
+                tx.input[0].script = ts.script_sig(&sig);
+            };
+
+            // the unprepared transaction does not satisfy the timelock
+            sign(&mut tx);
+            assert!(
+                verify_input(&tx, 0, &ts.script_pubkey(), STANDARD_SCRIPT_VERIFY_FLAGS).is_err()
+            );
+
+            ts.prepare_spend(&mut tx, 0).unwrap();
+            sign(&mut tx);
+            verify_input(&tx, 0, &ts.script_pubkey(), STANDARD_SCRIPT_VERIFY_FLAGS).unwrap();
+            let size = InputKind::P2SHTimeLock { lock }.script_sig_size();
+            assert!(size >= tx.input[0].script.len() && size - tx.input[0].script.len() <= 2);
+            match lock {
+                TimeLock::Absolute(lock) => {
+                    assert_eq!(tx.lock_time, lock.to_consensus_u32());
+                    assert_eq!(tx.input[0].sequence, SEQUENCE_ENABLE_LOCKTIME);
+                }
+                TimeLock::Relative(lock) => {
+                    assert_eq!(tx.version, 2);
+                    assert_eq!(tx.input[0].relative_lock_time(), Some(lock));
+                }
+            }
+        }
+
+        let ts = TimeLockScript::new(TimeLock::Absolute(LockTime::Blocks(100)), pubkey);
+        let mut tx = Transaction {
+            version: 1,
+            lock_time: 1_700_000_000,
+            input: vec![TxIn::with_outpoint(OutPoint::default())],
+            output: vec![],
+        };
+        assert!(ts.prepare_spend(&mut tx, 0).is_err());
+        assert!(ts.prepare_spend(&mut tx, 1).is_err());
+        assert!(ts.lock.is_satisfied_by(101, 0, 0, 0));
+        assert!(!ts.lock.is_satisfied_by(100, u32::MAX, 0, 0));
+
+        let csv = TimeLock::Relative(RelativeLockTime::Blocks(10));
+        assert!(csv.is_satisfied_by(110, 0, 100, 0));
+        assert!(!csv.is_satisfied_by(109, 0, 100, 0));
+
+        // non-minimal push of 16
+        let mut script = ScriptBuf::new();
+        script.push_slice([16u8]);
+        script.push_opcode(OP_CHECKLOCKTIMEVERIFY.into());
+        script.push_opcode(OP_DROP.into());
+        script.push_slice(pubkey.serialize());
+        script.push_opcode(OP_CHECKSIG.into());
+        assert!(TimeLockScript::from_redeem_script(&script).is_err());
+        assert!(TimeLockScript::from_redeem_script(&ts.script_pubkey()).is_err());
+    }
+}
@@ -5,10 +5,11 @@
 use bitcoin::consensus::{encode, Decodable, Encodable};
 use bitcoin::hashes::{hash_newtype, sha256d, Hash};
 use bitcoin::{ScriptBuf, VarInt};
-use bitcoin_io::{Read, Error, Write};
+use bitcoin_io::{Error, Read, Write};
 use core::cmp;
 use std::ops::Deref;
 
+use crate::timelock::{LockTime, RelativeLockTime};
 use crate::{consensus_decode_from_vec, consensus_encode_vec, err_string};
 
 hash_newtype! {
@@ -48,6 +49,7 @@ impl Decodable for Txid {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutPoint {
     /// The referenced transaction's txid.
     pub txid: Txid,
@@ -93,6 +95,7 @@ impl Decodable for OutPoint {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Witness {
     pub stack: Vec<u8>,
 }
@@ -117,6 +120,7 @@ impl Decodable for Witness {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TxIn {
     pub prevout: OutPoint,
     pub script: ScriptBuf,
@@ -139,6 +143,11 @@ impl TxIn {
         }
     }
 
+    /// Returns the relative lock time (BIP68) of the sequence, None when it is disabled.
+    pub fn relative_lock_time(&self) -> Option<RelativeLockTime> {
+        RelativeLockTime::from_sequence(self.sequence)
+    }
+
     /// Returns the base size of this input.
     ///
     /// Base size excludes the witness data (see [`Self::total_size`]).
@@ -213,6 +222,7 @@ impl Decodable for TxIn {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TxOut {
     pub value: u64,
     pub script_pubkey: ScriptBuf,
@@ -293,6 +303,7 @@ impl Decodable for TxOut {
  * - uint32_t nLockTime
  */
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transaction {
     pub version: u32,
     pub lock_time: u32,
@@ -338,6 +349,19 @@ impl Transaction {
         self.input.len() == 1 && self.input[0].prevout.is_null()
     }
 
+    /// Returns true if the transaction can be included in the block at `height`,
+    /// `time` is the median time past of the previous block (BIP113).
+    /// Dogecoin never activated BIP113, so this is the mempool policy. Consensus
+    /// compares time locks against the block time instead.
+    pub fn is_final(&self, height: u32, time: u32) -> bool {
+        if self.lock_time == 0
+            || LockTime::from_consensus(self.lock_time).is_satisfied_by(height, time)
+        {
+            return true;
+        }
+        self.input.iter().all(|input| input.sequence == u32::MAX)
+    }
+
     /// Returns the base transaction size.
     ///
     /// > Base transaction size is the size of the transaction serialised with the witness data stripped.
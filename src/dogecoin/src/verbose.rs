@@ -0,0 +1,373 @@
+//! JSON renderings of transactions and blocks in the shape of dogecoind's
+//! `decoderawtransaction` and `getblock <hash> 2`.
+//! Amounts are emitted as f64 DOGE (`value` of VoutJson), so they may print with
+//! fewer decimals than dogecoind's fixed 8 (e.g. `1.0` instead of `1.00000000`),
+//! and amounts above 2^53 units (about 90 million DOGE) are not exact.
+//! Use the raw transaction for exact amounts.
+
+use bitcoin::consensus::Encodable;
+use hex::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::amount::DOGE;
+use crate::asm::script_to_asm;
+use crate::block::{Block, BlockHash, BlockHeader, MerkleTx, TxMerkleNode};
+use crate::chainparams::ChainParams;
+use crate::opcodes::*;
+use crate::pow::difficulty;
+use crate::script::{classify_script, get_op, p2pkh_address, ScriptType};
+use crate::transaction::{Transaction, TxIn, TxOut, Txid};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScriptSigJson {
+    pub asm: String,
+    pub hex: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScriptPubKeyJson {
+    pub asm: String,
+    pub hex: String,
+    #[serde(rename = "reqSigs", default, skip_serializing_if = "Option::is_none")]
+    pub req_sigs: Option<usize>,
+    #[serde(rename = "type")]
+    pub script_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VinJson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub txid: Option<Txid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vout: Option<u32>,
+    #[serde(rename = "scriptSig", default, skip_serializing_if = "Option::is_none")]
+    pub script_sig: Option<ScriptSigJson>,
+    pub sequence: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoutJson {
+    /// In DOGE, see the module docs on precision.
+    pub value: f64,
+    pub n: u32,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: ScriptPubKeyJson,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionJson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hex: Option<String>,
+    pub txid: Txid,
+    pub hash: Txid,
+    pub size: usize,
+    pub vsize: usize,
+    pub version: u32,
+    pub locktime: u32,
+    pub vin: Vec<VinJson>,
+    pub vout: Vec<VoutJson>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuxPowJson {
+    pub tx: TransactionJson,
+    pub index: i32,
+    pub chainindex: i32,
+    pub merklebranch: Vec<TxMerkleNode>,
+    pub chainmerklebranch: Vec<TxMerkleNode>,
+    pub parentblock: String,
+}
+
+/// `getblock <hash> 2`. The fields that depend on the node's chain state
+/// (confirmations, height, mediantime, chainwork, nextblockhash) are only
+/// rendered when set by the caller.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockJson {
+    pub hash: BlockHash,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmations: Option<i64>,
+    pub strippedsize: usize,
+    pub size: usize,
+    pub weight: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+    pub version: u32,
+    #[serde(rename = "versionHex")]
+    pub version_hex: String,
+    pub merkleroot: TxMerkleNode,
+    pub tx: Vec<TransactionJson>,
+    pub time: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mediantime: Option<u32>,
+    pub nonce: u32,
+    pub bits: String,
+    pub difficulty: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chainwork: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previousblockhash: Option<BlockHash>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nextblockhash: Option<BlockHash>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auxpow: Option<AuxPowJson>,
+}
+
+impl ScriptPubKeyJson {
+    pub fn new(script: &[u8], chain: &ChainParams) -> Self {
+        let (script_type, addr) = classify_script(script, chain);
+        let (req_sigs, addresses) = match (&script_type, addr) {
+            (_, Some(addr)) => (Some(1), vec![addr.to_string()]),
+            (ScriptType::PubKey, None) => (Some(1), pubkey_addresses(script, chain)),
+            (ScriptType::MultiSig, None) => (
+                Some((script[0] - (OP_1 - 1)) as usize),
+                pubkey_addresses(script, chain),
+            ),
+            _ => (None, Vec::new()),
+        };
+        ScriptPubKeyJson {
+            asm: script_to_asm(script, false),
+            hex: script.to_lower_hex_string(),
+            req_sigs,
+            script_type: script_type.to_string(),
+            addresses,
+        }
+    }
+}
+
+// P2PKH addresses of the public keys pushed by a P2PK or bare multisig script,
+// like ExtractDestinations.
+fn pubkey_addresses(script: &[u8], chain: &ChainParams) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut pc = 0;
+    while let Some((_, data)) = get_op(script, &mut pc) {
+        if let Ok(addr) = p2pkh_address(data, chain) {
+            res.push(addr.to_string());
+        }
+    }
+    res
+}
+
+impl VinJson {
+    pub fn new(input: &TxIn, is_coinbase: bool) -> Self {
+        if is_coinbase {
+            return VinJson {
+                coinbase: Some(input.script.as_bytes().to_lower_hex_string()),
+                txid: None,
+                vout: None,
+                script_sig: None,
+                sequence: input.sequence,
+            };
+        }
+        VinJson {
+            coinbase: None,
+            txid: Some(input.prevout.txid),
+            vout: Some(input.prevout.vout),
+            script_sig: Some(ScriptSigJson {
+                asm: script_to_asm(input.script.as_bytes(), true),
+                hex: input.script.as_bytes().to_lower_hex_string(),
+            }),
+            sequence: input.sequence,
+        }
+    }
+}
+
+impl VoutJson {
+    pub fn new(output: &TxOut, n: u32, chain: &ChainParams) -> Self {
+        VoutJson {
+            value: output.value as f64 / DOGE as f64,
+            n,
+            script_pubkey: ScriptPubKeyJson::new(output.script_pubkey.as_bytes(), chain),
+        }
+    }
+}
+
+impl TransactionJson {
+    /// `decoderawtransaction`, the raw hex is included when `with_hex` is set
+    /// like `getrawtransaction <txid> true`.
+    pub fn new(tx: &Transaction, chain: &ChainParams, with_hex: bool) -> Self {
+        let txid = tx.compute_txid();
+        let is_coinbase = tx.is_coinbase();
+        TransactionJson {
+            hex: with_hex.then(|| tx.to_bytes().to_lower_hex_string()),
+            txid,
+            hash: txid,
+            size: tx.size(),
+            vsize: tx.size(),
+            version: tx.version,
+            locktime: tx.lock_time,
+            vin: tx
+                .input
+                .iter()
+                .map(|input| VinJson::new(input, is_coinbase))
+                .collect(),
+            vout: tx
+                .output
+                .iter()
+                .enumerate()
+                .map(|(n, output)| VoutJson::new(output, n as u32, chain))
+                .collect(),
+        }
+    }
+}
+
+impl AuxPowJson {
+    pub fn new(auxpow: &MerkleTx, chain: &ChainParams) -> Self {
+        AuxPowJson {
+            tx: TransactionJson::new(&auxpow.coinbase_tx, chain, true),
+            index: auxpow.coinbase_branch.side_mask as i32,
+            chainindex: auxpow.blockchain_branch.side_mask as i32,
+            merklebranch: auxpow.coinbase_branch.hash.clone(),
+            chainmerklebranch: auxpow.blockchain_branch.hash.clone(),
+            parentblock: header_hex(&auxpow.parent_block),
+        }
+    }
+}
+
+impl BlockJson {
+    pub fn new(block: &Block, chain: &ChainParams) -> Self {
+        let header = &block.header;
+        let size = block_size(block);
+        BlockJson {
+            hash: block.block_hash(),
+            confirmations: None,
+            strippedsize: size,
+            size,
+            weight: size * 4,
+            height: None,
+            version: header.version,
+            version_hex: format!("{:08x}", header.version),
+            merkleroot: header.merkle_root,
+            tx: block
+                .txdata
+                .iter()
+                .map(|tx| TransactionJson::new(tx, chain, false))
+                .collect(),
+            time: header.time,
+            mediantime: None,
+            nonce: header.nonce,
+            bits: format!("{:08x}", header.bits),
+            difficulty: difficulty(header.bits),
+            chainwork: None,
+            previousblockhash: (header.prev_blockhash != BlockHash::default())
+                .then_some(header.prev_blockhash),
+            nextblockhash: None,
+            auxpow: block
+                .auxpow
+                .as_ref()
+                .map(|auxpow| AuxPowJson::new(auxpow, chain)),
+        }
+    }
+}
+
+fn header_hex(header: &BlockHeader) -> String {
+    let mut buf = Vec::with_capacity(80);
+    header
+        .consensus_encode(&mut buf)
+        .expect("vec writers don't error");
+    buf.to_lower_hex_string()
+}
+
+fn block_size(block: &Block) -> usize {
+    let mut counter = bitcoin_io::sink();
+    block
+        .consensus_encode(&mut counter)
+        .expect("sinks don't error")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::*;
+    use bitcoin::consensus::Decodable;
+    use hex::test_hex_unwrap as hex;
+
+    #[test]
+    fn test_transaction_json() {
+        // https://github.com/dogecoin/dogecoin/blob/master/src/test/data/tx_valid.json
+        let tx = Transaction::consensus_decode(&mut hex!("0100000001892bc2522115e7bbf227a93aa5708e22b621616a398ab4c7119237b1da0b37d84f000000da00483045022100c5163a753aa37f9dfed708ed9167474f2b10a2b57238b00a95b3edb4f302d3fe022073fdc9c7a2147c79fb04e121a0aa64ce82901e31d15f65fa9c7a0633f2923112014730440220368808f54756249843c30cbf9ff964d1eddf4473f13352084dc1ab2229209e3302202578414b8fa035ffb58c861e965f0bcb9111636eedd173a936a4a326c138259d0147522102e60e9ac8a490768b3cd74fa55acf36d92491012c46dda806367f6dd567ac7b012103e5ed64e1c73f6d341d2a36d3f987a3b325117843f65afc24a616879047c7971652ae000000000200ca9a3b000000001976a914c7935d8f471d3472d31b6c5682f4bc2aaf2b806488acc54b563e0000000017a914f033fcfbdeafc8d9547c9015e021375694c316008700000000").as_slice()).unwrap();
+        let json =
+            serde_json::to_value(TransactionJson::new(&tx, &DOGE_MAIN_NET_CHAIN, false)).unwrap();
+        assert_eq!(json["txid"], tx.compute_txid().to_string());
+        assert_eq!(json["size"], tx.size());
+        assert!(json.get("hex").is_none());
+        let vin = &json["vin"][0];
+        assert_eq!(
+            vin["txid"],
+            "d8370bdab1379211c7b48a396a6121b6228e70a53aa927f2bbe7152152c22b89"
+        );
+        assert_eq!(vin["vout"], 79);
+        assert!(vin["scriptSig"]["asm"]
+            .as_str()
+            .unwrap()
+            .starts_with("0 3045022100c5163a75"));
+        assert!(vin["scriptSig"]["asm"]
+            .as_str()
+            .unwrap()
+            .contains("[ALL] 30440220"));
+        let vout = &json["vout"];
+        assert_eq!(vout[0]["value"], 10.0);
+        assert_eq!(vout[0]["n"], 0);
+        assert_eq!(vout[0]["scriptPubKey"]["type"], "pubkeyhash");
+        assert_eq!(vout[0]["scriptPubKey"]["reqSigs"], 1);
+        assert_eq!(
+            vout[0]["scriptPubKey"]["asm"],
+            "OP_DUP OP_HASH160 c7935d8f471d3472d31b6c5682f4bc2aaf2b8064 OP_EQUALVERIFY OP_CHECKSIG"
+        );
+        assert_eq!(vout[1]["value"], 10.45842885);
+        assert_eq!(vout[1]["scriptPubKey"]["type"], "scripthash");
+        assert_eq!(
+            vout[1]["scriptPubKey"]["addresses"][0]
+                .as_str()
+                .unwrap()
+                .chars()
+                .next(),
+            Some('A')
+        );
+
+        let decoded: TransactionJson = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            decoded,
+            TransactionJson::new(&tx, &DOGE_MAIN_NET_CHAIN, false)
+        );
+
+        // the serde derives round trip
+        let data = serde_json::to_string(&tx).unwrap();
+        assert_eq!(serde_json::from_str::<Transaction>(&data).unwrap(), tx);
+    }
+
+    #[test]
+    fn test_block_json() {
+        // genesis block, paying to a P2PK output
+        let block = Block::consensus_decode(&mut hex!("010000000000000000000000000000000000000000000000000000000000000000000000696ad20e2dd4365c7459b4a4a5af743d5e92c6da3229e6532cd605f6533f2a5b24a6a152f0ff0f1e678601000101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff1004ffff001d0104084e696e746f6e646fffffffff010058850c020000004341040184710fa689ad5023690c80f3a49c8f13f8d45b8c857fbcbc8bc4a8e4d3eb4b10f4d4604fa08dce601aaf0f470216fe1b51850b4acf21b179c45070ac7b03a9ac00000000").as_slice()).unwrap();
+        let json = serde_json::to_value(BlockJson::new(&block, &DOGE_MAIN_NET_CHAIN)).unwrap();
+        assert_eq!(
+            json["hash"],
+            "1a91e3dace36e2be3bf030a65679fe821aa1d6ef92e7c9902eb318182c355691"
+        );
+        assert!(json.get("previousblockhash").is_none());
+        assert_eq!(json["versionHex"], "00000001");
+        assert_eq!(json["bits"], "1e0ffff0");
+        assert_eq!(json["size"], 224);
+        assert_eq!(json["difficulty"], 0.000244140625);
+        assert!(json.get("height").is_none());
+        assert!(json.get("auxpow").is_none());
+        let tx = &json["tx"][0];
+        assert_eq!(tx["vin"][0]["coinbase"], "04ffff001d0104084e696e746f6e646f");
+        assert_eq!(tx["vout"][0]["value"], 88.0);
+        assert_eq!(tx["vout"][0]["scriptPubKey"]["type"], "pubkey");
+        assert_eq!(
+            tx["vout"][0]["scriptPubKey"]["addresses"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let data = serde_json::to_string(&block).unwrap();
+        assert_eq!(serde_json::from_str::<Block>(&data).unwrap(), block);
+    }
+}
@@ -0,0 +1,326 @@
+use async_trait::async_trait;
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+
+use crate::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use crate::chainparams::{ChainParams, DOGE_MAIN_NET_CHAIN};
+use crate::err_string;
+use crate::key::PrivateKey;
+use crate::script::Address;
+
+pub use bip39::Mnemonic;
+
+pub const BIP44_PURPOSE: u32 = 44;
+pub const COIN_TYPE_DOGE: u32 = 3;
+pub const COIN_TYPE_TESTNET: u32 = 1;
+pub const EXTERNAL_CHAIN: u32 = 0;
+pub const INTERNAL_CHAIN: u32 = 1;
+// https://github.com/bitcoin/bips/blob/master/bip-0044.mediawiki#address-gap-limit
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// Creates an English mnemonic from 16, 20, 24, 28 or 32 bytes of entropy (12 to 24 words).
+/// Entropy must come from a secure source, e.g. `raw_rand` in a canister.
+pub fn generate_mnemonic(entropy: &[u8]) -> Result<Mnemonic, String> {
+    Mnemonic::from_entropy(entropy).map_err(err_string)
+}
+
+/// Parses and validates a mnemonic, including its checksum.
+pub fn parse_mnemonic(phrase: &str) -> Result<Mnemonic, String> {
+    Mnemonic::parse(phrase).map_err(err_string)
+}
+
+pub fn coin_type(chain: &ChainParams) -> u32 {
+    if chain == &DOGE_MAIN_NET_CHAIN {
+        COIN_TYPE_DOGE
+    } else {
+        COIN_TYPE_TESTNET
+    }
+}
+
+/// m/44'/coin_type'/account'
+pub fn account_path(chain: &ChainParams, account: u32) -> Result<DerivationPath, String> {
+    Ok(DerivationPath::from(vec![
+        ChildNumber::from_hardened_idx(BIP44_PURPOSE).map_err(err_string)?,
+        ChildNumber::from_hardened_idx(coin_type(chain)).map_err(err_string)?,
+        ChildNumber::from_hardened_idx(account).map_err(err_string)?,
+    ]))
+}
+
+/// m/44'/coin_type'/account'/change/index
+pub fn address_path(
+    chain: &ChainParams,
+    account: u32,
+    change: u32,
+    index: u32,
+) -> Result<DerivationPath, String> {
+    Ok(account_path(chain, account)?.extend(change_path(change, index)?))
+}
+
+fn change_path(change: u32, index: u32) -> Result<[ChildNumber; 2], String> {
+    if change != EXTERNAL_CHAIN && change != INTERNAL_CHAIN {
+        return Err(format!("invalid change {}, expected 0 or 1", change));
+    }
+    Ok([
+        ChildNumber::from_normal_idx(change).map_err(err_string)?,
+        ChildNumber::from_normal_idx(index).map_err(err_string)?,
+    ])
+}
+
+/// BIP44 wallet rooted at a BIP32 master key.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HDWallet {
+    pub master: ExtendedPrivKey,
+}
+
+impl HDWallet {
+    pub fn from_seed(chain: &'static ChainParams, seed: &[u8]) -> Result<Self, String> {
+        Ok(HDWallet {
+            master: ExtendedPrivKey::new_master(chain, seed)?,
+        })
+    }
+
+    pub fn from_mnemonic(
+        chain: &'static ChainParams,
+        mnemonic: &Mnemonic,
+        passphrase: &str,
+    ) -> Result<Self, String> {
+        Self::from_seed(chain, &mnemonic.to_seed(passphrase))
+    }
+
+    pub fn chain(&self) -> &'static ChainParams {
+        self.master.chain
+    }
+
+    pub fn account_key<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        account: u32,
+    ) -> Result<ExtendedPrivKey, String> {
+        self.master
+            .derive_priv(secp, &account_path(self.chain(), account)?)
+    }
+
+    /// The account's extended public key, which is enough to watch its addresses.
+    pub fn account_xpub<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        account: u32,
+    ) -> Result<ExtendedPubKey, String> {
+        Ok(ExtendedPubKey::from_priv(
+            secp,
+            &self.account_key(secp, account)?,
+        ))
+    }
+
+    pub fn private_key<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        account: u32,
+        change: u32,
+        index: u32,
+    ) -> Result<PrivateKey, String> {
+        let path = address_path(self.chain(), account, change, index)?;
+        Ok(self.master.derive_priv(secp, &path)?.to_private_key())
+    }
+
+    pub fn address<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        account: u32,
+        change: u32,
+        index: u32,
+    ) -> Result<Address, String> {
+        Ok(self
+            .private_key(secp, account, change, index)?
+            .p2pkh_address(secp))
+    }
+}
+
+/// P2PKH address of an account's xpub at change/index.
+pub fn derive_address<C: Verification>(
+    secp: &Secp256k1<C>,
+    account_xpub: &ExtendedPubKey,
+    change: u32,
+    index: u32,
+) -> Result<Address, String> {
+    Ok(account_xpub
+        .derive_pub(secp, &change_path(change, index)?)?
+        .p2pkh_address())
+}
+
+/// Reports whether an address has been used on chain.
+#[async_trait]
+pub trait AddressHistory {
+    async fn has_history(&self, address: &Address) -> Result<bool, String>;
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UsedAddress {
+    pub change: u32,
+    pub index: u32,
+    pub address: Address,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AccountUsage {
+    pub xpub: ExtendedPubKey,
+    pub used: Vec<UsedAddress>,
+    // next unused index of the external and internal chains
+    pub next_index: [u32; 2],
+}
+
+/// Scans the external and internal chains of an account until `gap_limit`
+/// consecutive addresses without history are found.
+pub async fn discover_account<C: Verification, H: AddressHistory + ?Sized>(
+    secp: &Secp256k1<C>,
+    account_xpub: &ExtendedPubKey,
+    history: &H,
+    gap_limit: u32,
+) -> Result<AccountUsage, String> {
+    if gap_limit == 0 {
+        return Err("gap limit must be greater than 0".to_string());
+    }
+    let mut usage = AccountUsage {
+        xpub: *account_xpub,
+        used: Vec::new(),
+        next_index: [0, 0],
+    };
+    for change in [EXTERNAL_CHAIN, INTERNAL_CHAIN] {
+        let mut gap = 0;
+        let mut index = 0;
+        while gap < gap_limit {
+            let address = derive_address(secp, account_xpub, change, index)?;
+            if history.has_history(&address).await? {
+                usage.used.push(UsedAddress {
+                    change,
+                    index,
+                    address,
+                });
+                usage.next_index[change as usize] = index + 1;
+                gap = 0;
+            } else {
+                gap += 1;
+            }
+            index = index
+                .checked_add(1)
+                .ok_or("address index overflow".to_string())?;
+        }
+    }
+    Ok(usage)
+}
+
+/// BIP44 account discovery: scans accounts in order and stops at the first one
+/// whose external and internal chains have no history.
+pub async fn discover_accounts<C: Signing + Verification, H: AddressHistory + ?Sized>(
+    secp: &Secp256k1<C>,
+    wallet: &HDWallet,
+    history: &H,
+    gap_limit: u32,
+) -> Result<Vec<AccountUsage>, String> {
+    let mut accounts = Vec::new();
+    for account in 0..(1u32 << 31) {
+        let xpub = wallet.account_xpub(secp, account)?;
+        let usage = discover_account(secp, &xpub, history, gap_limit).await?;
+        if usage.used.is_empty() {
+            break;
+        }
+        accounts.push(usage);
+    }
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainparams::*;
+    use bitcoin::base58;
+    use std::collections::HashSet;
+
+    struct MockHistory(HashSet<Address>);
+
+    #[async_trait]
+    impl AddressHistory for MockHistory {
+        async fn has_history(&self, address: &Address) -> Result<bool, String> {
+            Ok(self.0.contains(address))
+        }
+    }
+
+    #[test]
+    fn test_mnemonic_and_paths() {
+        // https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+        let mnemonic = generate_mnemonic(&[0u8; 16]).unwrap();
+        assert_eq!(
+            mnemonic.to_string(),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        let wallet = HDWallet::from_mnemonic(&DOGE_MAIN_NET_CHAIN, &mnemonic, "TREZOR").unwrap();
+        let mut xprv = base58::decode_check("xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF").unwrap();
+        xprv[0..4].copy_from_slice(&DOGE_MAIN_NET_CHAIN.bip32_privkey_prefix.to_be_bytes());
+        assert_eq!(wallet.master.encode().to_vec(), xprv);
+        assert!(parse_mnemonic(&mnemonic.to_string()).is_ok());
+        assert!(parse_mnemonic(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon"
+        )
+        .is_err());
+        assert!(generate_mnemonic(&[0u8; 15]).is_err());
+
+        assert_eq!(
+            address_path(&DOGE_MAIN_NET_CHAIN, 0, 1, 5)
+                .unwrap()
+                .to_string(),
+            "44'/3'/0'/1/5"
+        );
+        assert_eq!(
+            address_path(&DOGE_TEST_NET_CHAIN, 2, 0, 0)
+                .unwrap()
+                .to_string(),
+            "44'/1'/2'/0/0"
+        );
+        assert!(address_path(&DOGE_MAIN_NET_CHAIN, 0, 2, 0).is_err());
+        assert!(account_path(&DOGE_MAIN_NET_CHAIN, 1 << 31).is_err());
+
+        let secp = Secp256k1::new();
+        let wallet = HDWallet::from_mnemonic(&DOGE_MAIN_NET_CHAIN, &mnemonic, "").unwrap();
+        assert_eq!(
+            wallet.address(&secp, 0, 0, 0).unwrap().to_string(),
+            "DBus3bamQjgJULBJtYXpEzDWQRwF5iwxgC"
+        );
+        let xpub = wallet.account_xpub(&secp, 0).unwrap();
+        for (change, index) in [(0, 0), (0, 7), (1, 3)] {
+            assert_eq!(
+                wallet.address(&secp, 0, change, index).unwrap(),
+                derive_address(&secp, &xpub, change, index).unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_account_discovery() {
+        let secp = Secp256k1::new();
+        let wallet = HDWallet::from_seed(&DOGE_MAIN_NET_CHAIN, &[1u8; 32]).unwrap();
+        let used = [(0, 0, 0), (0, 0, 3), (0, 1, 0), (0, 0, 22), (1, 0, 19)];
+        let history = MockHistory(
+            used.iter()
+                .map(|&(a, c, i)| wallet.address(&secp, a, c, i).unwrap())
+                .collect(),
+        );
+
+        let accounts = discover_accounts(&secp, &wallet, &history, 20)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].used.len(), 4);
+        assert_eq!(accounts[0].next_index, [23, 1]);
+        assert_eq!(accounts[1].used.len(), 1);
+        assert_eq!(accounts[1].next_index, [20, 0]);
+
+        // index 22 is beyond the gap after index 3
+        let accounts = discover_accounts(&secp, &wallet, &history, 5)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].next_index, [4, 1]);
+        assert!(discover_accounts(&secp, &wallet, &history, 0)
+            .await
+            .is_err());
+    }
+}